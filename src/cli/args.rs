@@ -1,16 +1,34 @@
+use crate::cli::check_ignore::CheckIgnoreArgs;
+use crate::cli::restore::RestoreMetadataArgs;
+use crate::cli::verify::VerifyArgs;
 use crate::config::config_command::ConfigCommand;
-use crate::config::loader::{load_config, load_config_file};
+use crate::config::loader::{load_config_file, load_config_with_path};
 use crate::config::schema::Config;
-use crate::error::{CpxError, CpxResult};
+use crate::error::{CpxError, CpxResult, ExcludeError};
+use crate::utility::events::EventEmitter;
 use crate::utility::helper::parse_progress_bar;
+use crate::utility::manifest::{HashAlgorithm, Manifest};
 use crate::utility::progress_bar::ProgressOptions;
 use crate::utility::{
-    exclude::{ExcludePattern, ExcludeRules, build_exclude_rules, parse_exclude_pattern_list},
-    helper::{parse_backup_mode, parse_follow_symlink, parse_reflink_mode, parse_symlink_mode},
-    preserve::PreserveAttr,
+    chmod::ChmodRules,
+    exclude::{
+        ExcludeRules, ExclusionMarkers, FilterGroup, FilterKind, GITIGNORE_FILES, RuleOrigin,
+        build_filter_rules, parse_exclude_pattern, parse_exclude_pattern_list, parse_filter_rule,
+    },
+    exclude_presets::preset_patterns,
+    file_list::{read_list, read_patterns, resolve_listed},
+    filter_expr::FilterExpr,
+    helper::{
+        parse_backup_mode, parse_follow_symlink, parse_hash_cache_mode, parse_reflink_mode,
+        parse_source_change_mode, parse_special_mode, parse_symlink_mode, process_umask,
+    },
+    idmap::OwnerMap,
+    preserve::{PreserveAttr, PreservePolicy, PreserveRules},
+    sidecar::{MetadataSidecar, SIDECAR_NAME},
+    xattr_filter::XattrFilter,
 };
 use clap::{Args, Parser, Subcommand, ValueEnum};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::sync::atomic::AtomicBool;
 
@@ -38,6 +56,26 @@ pub enum BackupMode {
     Simple,
 }
 
+#[derive(Debug, Clone, Copy, ValueEnum, PartialEq)]
+pub enum SpecialMode {
+    Recreate,
+    Skip,
+    Read,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum, PartialEq)]
+pub enum SourceChangeMode {
+    Retry,
+    Warn,
+    Fail,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum, PartialEq)]
+pub enum HashCacheMode {
+    None,
+    Xattr,
+}
+
 #[derive(Debug, Clone, Copy, ValueEnum)]
 pub enum FollowSymlink {
     NoDereference,
@@ -45,7 +83,22 @@ pub enum FollowSymlink {
     CommandLineSymlink,
 }
 
+#[derive(Debug, Clone, Copy, ValueEnum, PartialEq)]
+pub enum ProgressFormat {
+    Bar,
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FilterArg {
+    Include,
+    Exclude,
+    ExcludeFrom,
+    ExcludePreset,
+}
+
 #[derive(Debug, Subcommand)]
+#[allow(clippy::large_enum_variant)]
 pub enum Commands {
     /// Default (Implicit)
     Copy(CopyArgs),
@@ -55,6 +108,15 @@ pub enum Commands {
         #[command(subcommand)]
         command: ConfigCommand,
     },
+
+    /// Verify a directory against a checksum manifest
+    Verify(VerifyArgs),
+
+    /// Reapply metadata recorded with --metadata-sidecar
+    RestoreMetadata(RestoreMetadataArgs),
+
+    /// Show which filter rule excludes or includes the given paths
+    CheckIgnore(CheckIgnoreArgs),
 }
 
 #[derive(Parser, Debug)]
@@ -64,6 +126,85 @@ pub struct CLIArgs {
     pub command: Commands,
 }
 
+/// Options deciding which entries of a source directory are copied, shared by
+/// `copy` and `check-ignore`.
+#[derive(Args, Debug, Clone, Default)]
+pub struct FilterArgs {
+    #[arg(
+        short = 'e',
+        long = "exclude",
+        value_name = "PATTERN",
+        help = "Exclude files matching pattern (can be specified multiple times, supports comma-separated values)"
+    )]
+    pub exclude: Vec<String>,
+
+    #[arg(
+        long = "include",
+        value_name = "PATTERN",
+        help = "Include files matching pattern; --include and --exclude apply in order, first match wins"
+    )]
+    pub include: Vec<String>,
+
+    #[arg(
+        long = "exclude-from",
+        value_name = "FILE",
+        help = "Read exclude patterns from FILE, one per line ('-' for stdin)"
+    )]
+    pub exclude_from: Vec<PathBuf>,
+
+    #[arg(
+        long = "exclude-preset",
+        value_name = "NAMES",
+        value_delimiter = ',',
+        help = "Exclude what the named presets list, e.g. rust,node,python,macos-junk (see 'cpx config presets')"
+    )]
+    pub exclude_preset: Vec<String>,
+
+    #[arg(
+        long = "filter",
+        value_name = "EXPR",
+        help = "only copy files matching EXPR, e.g. 'size > 100M and mtime < 7d' (repeated filters must all match)"
+    )]
+    pub filter: Vec<String>,
+
+    /// Which filter option each `--include`/`--exclude`/`--exclude-from`/`--exclude-preset`
+    /// value came from, in command-line order
+    #[arg(skip)]
+    pub filter_order: Vec<FilterArg>,
+
+    #[arg(
+        long = "exclude-nodump",
+        help = "skip files and directories that have the nodump inode flag (chattr +d)"
+    )]
+    pub exclude_nodump: bool,
+
+    #[arg(
+        long = "exclude-caches",
+        help = "skip directories containing a CACHEDIR.TAG file with the standard signature"
+    )]
+    pub exclude_caches: bool,
+
+    #[arg(
+        long = "exclude-if-present",
+        value_name = "NAME",
+        help = "skip directories containing a file called NAME (can be specified multiple times)"
+    )]
+    pub exclude_if_present: Vec<String>,
+
+    #[arg(
+        long = "respect-gitignore",
+        help = "skip what .gitignore and .cpxignore files found in copied directories ignore"
+    )]
+    pub respect_gitignore: bool,
+
+    #[arg(
+        long = "ignore-file",
+        value_name = "NAME",
+        help = "also read per-directory ignore files called NAME (can be specified multiple times)"
+    )]
+    pub ignore_file: Vec<String>,
+}
+
 #[derive(Args, Debug, Clone)]
 pub struct CopyArgs {
     // Input/Output Options
@@ -81,13 +222,29 @@ pub struct CopyArgs {
     )]
     pub target_directory: Option<PathBuf>,
 
+    #[command(flatten)]
+    pub filters: FilterArgs,
+
     #[arg(
-        short = 'e',
-        long = "exclude",
-        value_name = "PATTERN",
-        help = "Exclude files matching pattern (can be specified multiple times, supports comma-separated values)"
+        long = "files-from",
+        value_name = "FILE",
+        conflicts_with = "files_from0",
+        help = "copy the paths listed in FILE, one per line ('-' for stdin), relative to the single SOURCE directory"
     )]
-    pub exclude: Vec<String>,
+    pub files_from: Option<PathBuf>,
+
+    #[arg(
+        long = "files-from0",
+        value_name = "FILE",
+        help = "like --files-from, with NUL-separated paths (find -print0)"
+    )]
+    pub files_from0: Option<PathBuf>,
+
+    #[arg(
+        long = "explain-excludes",
+        help = "print each excluded path and the rule that excluded it"
+    )]
+    pub explain_excludes: bool,
 
     // Copy Behavior Options
     #[arg(short, long, help = "Copy directories recursively")]
@@ -103,6 +260,13 @@ pub struct CopyArgs {
     #[arg(long = "resume", help = "resume interrupted transfers")]
     pub resume: bool,
 
+    #[arg(
+        long = "hash-cache",
+        value_name = "MODE",
+        help = "cache resume checksums in extended attributes (none, xattr)"
+    )]
+    pub hash_cache: Option<HashCacheMode>,
+
     #[arg(
         short = 'f',
         long,
@@ -128,6 +292,33 @@ pub struct CopyArgs {
     )]
     pub remove_destination: bool,
 
+    #[arg(
+        long = "special",
+        value_name = "MODE",
+        help = "how to copy FIFOs, sockets and device nodes (recreate, skip, read) [default: recreate]"
+    )]
+    pub special: Option<SpecialMode>,
+
+    #[arg(
+        long = "on-source-change",
+        value_name = "MODE",
+        help = "what to do when a source file changes while it is copied (retry, warn, fail) [default: warn]"
+    )]
+    pub on_source_change: Option<SourceChangeMode>,
+
+    #[arg(
+        long = "source-change-retries",
+        value_name = "N",
+        help = "number of times to recopy a changed file with --on-source-change=retry [default: 3]"
+    )]
+    pub source_change_retries: Option<usize>,
+
+    #[arg(
+        long = "wait-for-space",
+        help = "when the destination is full, wait for free space instead of failing"
+    )]
+    pub wait_for_space: bool,
+
     // Link and Symlink Options
     #[arg(
         short = 's',
@@ -177,6 +368,67 @@ pub struct CopyArgs {
     )]
     pub preserve: Option<String>,
 
+    #[arg(
+        long = "no-preserve",
+        value_name = "ATTR_LIST",
+        help = "don't preserve the specified attributes, applied after --preserve"
+    )]
+    pub no_preserve: Option<String>,
+
+    #[arg(
+        long = "chown",
+        value_name = "USER:GROUP",
+        help = "set the owner and/or group of copies (implies preserving ownership)"
+    )]
+    pub chown: Option<String>,
+
+    #[arg(
+        long = "usermap",
+        value_name = "FROM:TO",
+        help = "map owners when preserving ownership (can be specified multiple times, comma-separated)"
+    )]
+    pub usermap: Vec<String>,
+
+    #[arg(
+        long = "groupmap",
+        value_name = "FROM:TO",
+        help = "map groups when preserving ownership (can be specified multiple times, comma-separated)"
+    )]
+    pub groupmap: Vec<String>,
+
+    #[arg(
+        long = "numeric-ids",
+        help = "match --usermap/--groupmap by numeric id instead of by name"
+    )]
+    pub numeric_ids: bool,
+
+    #[arg(
+        long = "chmod",
+        value_name = "RULES",
+        help = "rewrite permissions of copies, e.g. D755,F644,g-w (can be specified multiple times)"
+    )]
+    pub chmod: Vec<String>,
+
+    #[arg(
+        long = "xattr-include",
+        value_name = "PATTERN",
+        help = "only preserve xattrs whose names match, e.g. 'user.*' (can be specified multiple times)"
+    )]
+    pub xattr_include: Vec<String>,
+
+    #[arg(
+        long = "xattr-exclude",
+        value_name = "PATTERN",
+        help = "don't preserve xattrs whose names match (can be specified multiple times)"
+    )]
+    pub xattr_exclude: Vec<String>,
+
+    #[arg(
+        long = "preserve-strict",
+        help = "fail when a preserved attribute can't be applied, instead of summarizing"
+    )]
+    pub preserve_strict: bool,
+
     // Backup and Reflink Options
     #[arg(
         short = 'b',
@@ -197,6 +449,47 @@ pub struct CopyArgs {
     )]
     pub reflink: Option<ReflinkMode>,
 
+    // Verification Options
+    #[arg(
+        long = "manifest",
+        value_name = "FILE",
+        help = "write a checksum manifest of the copied files (sha256sum/b3sum compatible)"
+    )]
+    pub manifest: Option<PathBuf>,
+
+    #[arg(
+        long = "hash",
+        value_name = "ALGORITHM",
+        requires = "manifest",
+        help = "hash algorithm for --manifest (xxh3, sha256, blake3)"
+    )]
+    pub hash: Option<HashAlgorithm>,
+
+    #[arg(
+        long = "metadata-sidecar",
+        value_name = "FILE",
+        num_args = 0..=1,
+        require_equals = true,
+        help = "record ownership, mode, times, xattrs and ACLs in a sidecar file [default: DEST/.cpx-metadata]"
+    )]
+    pub metadata_sidecar: Option<Option<PathBuf>>,
+
+    // Progress Options
+    #[arg(
+        long = "progress",
+        value_name = "FORMAT",
+        help = "progress output format (bar, json)"
+    )]
+    pub progress: Option<ProgressFormat>,
+
+    #[arg(
+        long = "progress-fd",
+        value_name = "FD",
+        requires = "progress",
+        help = "write --progress=json events to file descriptor FD instead of stderr"
+    )]
+    pub progress_fd: Option<i32>,
+
     // Config Options (Placed last as meta)
     #[arg(long, value_name = "PATH", help = "Use custom config file")]
     pub config: Option<PathBuf>,
@@ -210,12 +503,21 @@ pub struct CopyOptions {
     pub recursive: bool,
     pub parallel: usize,
     pub resume: bool,
+    pub hash_cache: HashCacheMode,
     pub force: bool,
     pub interactive: bool,
     pub parents: bool,
+    /// With `--files-from`, `--parents` paths are taken relative to this directory
+    pub parents_base: Option<PathBuf>,
     pub preserve: PreserveAttr,
+    pub preserve_rules: Arc<PreserveRules>,
+    pub preserve_policy: Arc<PreservePolicy>,
     pub attributes_only: bool,
     pub remove_destination: bool,
+    pub special: SpecialMode,
+    pub on_source_change: SourceChangeMode,
+    pub source_change_retries: usize,
+    pub wait_for_space: bool,
     pub symbolic_link: Option<SymlinkMode>,
     pub hard_link: bool,
     pub follow_symlink: FollowSymlink,
@@ -223,21 +525,48 @@ pub struct CopyOptions {
     pub backup: Option<BackupMode>,
     pub reflink: Option<ReflinkMode>,
     pub exclude_rules: Option<ExcludeRules>,
+    /// `--filter` expression every copied non-directory entry has to match
+    pub filter_expr: Option<FilterExpr>,
+    pub exclude_nodump: bool,
+    pub exclusion_markers: ExclusionMarkers,
+    /// Names of per-directory ignore files honored while walking source directories
+    pub ignore_files: Vec<String>,
+    /// Print why each excluded path is left out
+    pub explain_excludes: bool,
+    pub manifest: Option<Arc<Manifest>>,
+    pub metadata_sidecar: Option<Arc<MetadataSidecar>>,
+    pub events: Option<Arc<EventEmitter>>,
     pub abort: Arc<AtomicBool>,
+    pub out_of_space: Arc<AtomicBool>,
+    /// Read up front: querying it later would race with copy threads creating files
+    pub umask: u32,
 }
 
 impl CopyOptions {
+    /// Whether copies need a pass of `apply_preserve_attrs` once written.
+    pub fn applies_attributes(&self) -> bool {
+        self.preserve != PreserveAttr::none() || self.preserve_rules.has_chmod()
+    }
+
     pub fn none() -> Self {
         Self {
             recursive: false,
             parallel: 4,
             resume: false,
+            hash_cache: HashCacheMode::None,
             force: false,
             interactive: false,
             parents: false,
+            parents_base: None,
             preserve: PreserveAttr::none(),
+            preserve_rules: Arc::new(PreserveRules::default()),
+            preserve_policy: Arc::new(PreservePolicy::default()),
             attributes_only: false,
             remove_destination: false,
+            special: SpecialMode::Recreate,
+            on_source_change: SourceChangeMode::Warn,
+            source_change_retries: 3,
+            wait_for_space: false,
             symbolic_link: None,
             hard_link: false,
             follow_symlink: FollowSymlink::NoDereference,
@@ -245,7 +574,17 @@ impl CopyOptions {
             backup: None,
             reflink: None,
             exclude_rules: None,
+            filter_expr: None,
+            exclude_nodump: false,
+            exclusion_markers: ExclusionMarkers::default(),
+            ignore_files: Vec::new(),
+            explain_excludes: false,
+            manifest: None,
+            metadata_sidecar: None,
+            events: None,
             abort: Arc::new(AtomicBool::new(false)),
+            out_of_space: Arc::new(AtomicBool::new(false)),
+            umask: process_umask(),
         }
     }
 
@@ -254,13 +593,21 @@ impl CopyOptions {
             recursive: config.copy.recursive,
             parallel: config.copy.parallel,
             resume: config.copy.resume,
+            hash_cache: parse_hash_cache_mode(&config.copy.hash_cache),
             force: config.copy.force,
             interactive: config.copy.interactive,
             parents: config.copy.parents,
+            parents_base: None,
             preserve: PreserveAttr::from_string(&config.preserve.mode)
                 .unwrap_or_else(|_| PreserveAttr::default()),
+            preserve_rules: Arc::new(PreserveRules::default()),
+            preserve_policy: Arc::new(PreservePolicy::new(config.preserve.strict)),
             attributes_only: config.copy.attributes_only,
             remove_destination: config.copy.remove_destination,
+            special: parse_special_mode(&config.copy.special),
+            on_source_change: parse_source_change_mode(&config.copy.on_source_change),
+            source_change_retries: config.copy.source_change_retries,
+            wait_for_space: config.copy.wait_for_space,
             symbolic_link: parse_symlink_mode(&config.symlink.mode),
             hard_link: false,
             follow_symlink: parse_follow_symlink(&config.symlink.follow),
@@ -268,7 +615,23 @@ impl CopyOptions {
             backup: parse_backup_mode(&config.backup.mode),
             reflink: parse_reflink_mode(&config.reflink.mode),
             exclude_rules: None,
+            filter_expr: None,
+            exclude_nodump: config.filter.nodump,
+            exclusion_markers: ExclusionMarkers {
+                caches: config.filter.exclude_caches,
+                names: config.filter.exclude_if_present.clone(),
+            },
+            ignore_files: ignore_file_names(
+                config.filter.respect_gitignore,
+                &config.filter.ignore_files,
+            ),
+            explain_excludes: false,
+            manifest: None,
+            metadata_sidecar: None,
+            events: None,
             abort: Arc::new(AtomicBool::new(false)),
+            out_of_space: Arc::new(AtomicBool::new(false)),
+            umask: process_umask(),
         }
     }
 }
@@ -279,17 +642,25 @@ impl From<&CopyArgs> for CopyOptions {
             recursive: cli.recursive,
             parallel: cli.parallel,
             resume: cli.resume,
+            hash_cache: cli.hash_cache.unwrap_or(HashCacheMode::None),
             force: cli.force,
             interactive: cli.interactive,
             parents: cli.parents,
+            parents_base: None,
             preserve: match &cli.preserve {
                 None => PreserveAttr::none(),
                 Some(s) => {
                     PreserveAttr::from_string(s).expect("unable to parse preserve attribute")
                 }
             },
+            preserve_rules: Arc::new(PreserveRules::default()),
+            preserve_policy: Arc::new(PreservePolicy::new(cli.preserve_strict)),
             attributes_only: cli.attributes_only,
             remove_destination: cli.remove_destination,
+            special: cli.special.unwrap_or(SpecialMode::Recreate),
+            on_source_change: cli.on_source_change.unwrap_or(SourceChangeMode::Warn),
+            source_change_retries: cli.source_change_retries.unwrap_or(3),
+            wait_for_space: cli.wait_for_space,
             symbolic_link: cli.symbolic_link,
             hard_link: cli.hard_link,
             follow_symlink: FollowSymlink::NoDereference,
@@ -297,7 +668,23 @@ impl From<&CopyArgs> for CopyOptions {
             backup: cli.backup,
             reflink: cli.reflink,
             exclude_rules: None,
+            filter_expr: None,
+            exclude_nodump: cli.filters.exclude_nodump,
+            exclusion_markers: ExclusionMarkers {
+                caches: cli.filters.exclude_caches,
+                names: cli.filters.exclude_if_present.clone(),
+            },
+            ignore_files: ignore_file_names(
+                cli.filters.respect_gitignore,
+                &cli.filters.ignore_file,
+            ),
+            explain_excludes: cli.explain_excludes,
+            manifest: None,
+            metadata_sidecar: None,
+            events: None,
             abort: Arc::new(AtomicBool::new(false)),
+            out_of_space: Arc::new(AtomicBool::new(false)),
+            umask: process_umask(),
         }
     }
 }
@@ -311,14 +698,51 @@ impl CLIArgs {
             let first_arg = &args[1];
             let is_subcommand = matches!(
                 first_arg.as_str(),
-                "config" | "copy" | "-h" | "--help" | "-V" | "--version"
+                "config"
+                    | "copy"
+                    | "verify"
+                    | "restore-metadata"
+                    | "check-ignore"
+                    | "-h"
+                    | "--help"
+                    | "-V"
+                    | "--version"
             );
             if !is_subcommand {
                 args.insert(1, "copy".to_string());
-                return <Self as clap::Parser>::parse_from(args);
             }
         }
-        <Self as clap::Parser>::parse()
+        Self::parse_from_args(args)
+    }
+
+    fn parse_from_args(args: Vec<String>) -> Self {
+        let matches = <Self as clap::CommandFactory>::command().get_matches_from(args);
+        let mut cli =
+            <Self as clap::FromArgMatches>::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+        // The derived args keep includes and excludes apart; recover their interleaving
+        let filters = match &mut cli.command {
+            Commands::Copy(copy_args) => Some(&mut copy_args.filters),
+            Commands::CheckIgnore(check_args) => Some(&mut check_args.filters),
+            _ => None,
+        };
+        if let Some(filters) = filters
+            && let Some((_, sub_matches)) = matches.subcommand()
+        {
+            let mut order: Vec<(usize, FilterArg)> = Vec::new();
+            for (id, arg) in [
+                ("include", FilterArg::Include),
+                ("exclude", FilterArg::Exclude),
+                ("exclude_from", FilterArg::ExcludeFrom),
+                ("exclude_preset", FilterArg::ExcludePreset),
+            ] {
+                if let Some(indices) = sub_matches.indices_of(id) {
+                    order.extend(indices.map(|index| (index, arg)));
+                }
+            }
+            order.sort_by_key(|(index, _)| *index);
+            filters.filter_order = order.into_iter().map(|(_, arg)| arg).collect();
+        }
+        cli
     }
 
     pub fn validate(self) -> CpxResult<(Vec<PathBuf>, PathBuf, CopyOptions)> {
@@ -330,13 +754,54 @@ impl CLIArgs {
             std::process::exit(0);
         }
 
+        // Handle verify command
+        if let Commands::Verify(verify_args) = &self.command {
+            let verified = verify_args.execute().map_err(|e| {
+                CpxError::Validation(format!(
+                    "Failed to read manifest '{}': {}",
+                    verify_args.manifest.display(),
+                    e
+                ))
+            })?;
+            std::process::exit(if verified { 0 } else { 1 });
+        }
+
+        // Handle restore-metadata command
+        if let Commands::RestoreMetadata(restore_args) = &self.command {
+            let restored = restore_args
+                .execute()
+                .map_err(|e| CpxError::Validation(format!("Failed to restore metadata: {}", e)))?;
+            std::process::exit(if restored { 0 } else { 1 });
+        }
+
+        // Handle check-ignore command
+        if let Commands::CheckIgnore(check_args) = &self.command {
+            let (config, config_path) =
+                load_config_if_needed(check_args.no_config, check_args.config.as_deref())
+                    .map_err(CpxError::Config)?;
+            let mut options = match &config {
+                Some(cfg) => CopyOptions::from_config(cfg),
+                None => CopyOptions::none(),
+            };
+            apply_filter_args(
+                &mut options,
+                &check_args.filters,
+                config.as_ref(),
+                config_path.as_deref(),
+            )?;
+            let any_excluded = check_args.execute(&options).map_err(CpxError::Validation)?;
+            std::process::exit(if any_excluded { 0 } else { 1 });
+        }
+
         // Get copy args from the Copy subcommand
         let copy_args = match self.command {
             Commands::Copy(args) => args,
             _ => unreachable!(),
         };
 
-        let config = load_config_if_needed(&copy_args).map_err(CpxError::Config)?;
+        let (config, config_path) =
+            load_config_if_needed(copy_args.no_config, copy_args.config.as_deref())
+                .map_err(CpxError::Config)?;
 
         // Start with config or defaults
         let mut options = if let Some(ref cfg) = config {
@@ -346,22 +811,56 @@ impl CLIArgs {
         };
 
         // CLI args override config
-        apply_cli_overrides(&mut options, &copy_args).map_err(CpxError::Validation)?;
+        apply_cli_overrides(&mut options, &copy_args, config.as_ref())
+            .map_err(CpxError::Validation)?;
+
+        if let Some((list, _)) = copy_args.files_from()
+            && list == Path::new("-")
+            && copy_args
+                .filters
+                .exclude_from
+                .iter()
+                .any(|file| file == Path::new("-"))
+        {
+            return Err(CpxError::Validation(
+                "--files-from and --exclude-from cannot both read from stdin".to_string(),
+            ));
+        }
 
-        // Build exclude rules
-        let all_patterns =
-            build_all_exclude_patterns(&copy_args, config.as_ref()).map_err(CpxError::Exclude)?;
-        options.exclude_rules = build_exclude_rules(all_patterns).map_err(CpxError::Exclude)?;
+        // Build include/exclude rules
+        apply_filter_args(
+            &mut options,
+            &copy_args.filters,
+            config.as_ref(),
+            config_path.as_deref(),
+        )?;
 
         // Validate conflicts
         validate_conflicts(&options).map_err(CpxError::Validation)?;
-
-        // Handle attributes_only special case
-        if options.attributes_only {
-            options.preserve = PreserveAttr::all();
+        if copy_args.manifest.is_some()
+            && (options.hard_link || options.symbolic_link.is_some() || options.attributes_only)
+        {
+            return Err(CpxError::Validation(
+                "--manifest cannot be used with --link, --symbolic-link or --attributes-only"
+                    .to_string(),
+            ));
+        }
+        // A clone is never read, so there would be nothing to hash it from
+        if copy_args.manifest.is_some() && options.reflink == Some(ReflinkMode::Always) {
+            return Err(CpxError::Validation(
+                "--manifest cannot be used with --reflink=always".to_string(),
+            ));
+        }
+        if copy_args.metadata_sidecar.is_some() && options.hard_link {
+            return Err(CpxError::Validation(
+                "--metadata-sidecar cannot be used with --link".to_string(),
+            ));
         }
 
-        let (sources, destination) = if let Some(target) = copy_args.target_directory {
+        let files_from = copy_args
+            .files_from()
+            .map(|(list, separator)| (list.to_path_buf(), separator));
+        let (mut sources, destination) = if let Some(target) = copy_args.target_directory {
             let mut sources = copy_args.sources;
             sources.push(copy_args.destination);
             (sources, target)
@@ -369,23 +868,84 @@ impl CLIArgs {
             (copy_args.sources, copy_args.destination)
         };
 
+        // The listed paths replace the source directory, keeping their layout under it
+        if let Some((list, separator)) = files_from {
+            if sources.len() != 1 || !sources[0].is_dir() {
+                return Err(CpxError::Validation(
+                    "--files-from needs a single SOURCE directory the listed paths are relative to"
+                        .to_string(),
+                ));
+            }
+            let base = sources.remove(0);
+            let entries = read_list(&list, separator).map_err(|e| {
+                CpxError::Validation(format!(
+                    "Failed to read file list '{}': {}",
+                    list.display(),
+                    e
+                ))
+            })?;
+            sources = resolve_listed(&base, entries).map_err(CpxError::Validation)?;
+            options.parents = true;
+            options.parents_base = Some(base);
+        }
+
+        if let Some(sidecar_path) = copy_args.metadata_sidecar {
+            let base = manifest_base(&sources, &destination);
+            let sidecar_path = sidecar_path.unwrap_or_else(|| base.join(SIDECAR_NAME));
+            options.metadata_sidecar = Some(Arc::new(MetadataSidecar::new(sidecar_path, base)));
+        }
+
+        if let Some(manifest_path) = copy_args.manifest {
+            let base = manifest_base(&sources, &destination);
+            options.manifest = Some(Arc::new(Manifest::new(
+                manifest_path,
+                copy_args.hash.unwrap_or_default(),
+                base,
+            )));
+        }
+
         Ok((sources, destination, options))
     }
 }
 
-fn load_config_if_needed(copy_args: &CopyArgs) -> crate::error::ConfigResult<Option<Config>> {
-    if copy_args.no_config {
-        return Ok(None);
+/// Manifest and sidecar entries are relative to the directory the copies land in.
+fn manifest_base(sources: &[PathBuf], destination: &Path) -> PathBuf {
+    let single_file = sources.len() == 1 && !sources[0].is_dir();
+    if destination.is_dir() || !single_file {
+        destination.to_path_buf()
+    } else {
+        match destination.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+            _ => PathBuf::from("."),
+        }
+    }
+}
+
+/// The config to apply, if any, and the file it was read from.
+fn load_config_if_needed(
+    no_config: bool,
+    custom_path: Option<&Path>,
+) -> crate::error::ConfigResult<(Option<Config>, Option<PathBuf>)> {
+    if no_config {
+        return Ok((None, None));
     }
 
-    if let Some(custom_path) = &copy_args.config {
-        return Ok(Some(load_config_file(custom_path)?));
+    if let Some(custom_path) = custom_path {
+        return Ok((
+            Some(load_config_file(custom_path)?),
+            Some(custom_path.to_path_buf()),
+        ));
     }
 
-    Ok(Some(load_config()))
+    let (config, path) = load_config_with_path();
+    Ok((Some(config), path))
 }
 
-fn apply_cli_overrides(options: &mut CopyOptions, copy_args: &CopyArgs) -> Result<(), String> {
+fn apply_cli_overrides(
+    options: &mut CopyOptions,
+    copy_args: &CopyArgs,
+    config: Option<&Config>,
+) -> Result<(), String> {
     // Boolean flags - when present, they override
     if copy_args.recursive {
         options.recursive = true;
@@ -422,32 +982,213 @@ fn apply_cli_overrides(options: &mut CopyOptions, copy_args: &CopyArgs) -> Resul
     if copy_args.reflink.is_some() {
         options.reflink = copy_args.reflink;
     }
+    if let Some(special) = copy_args.special {
+        options.special = special;
+    }
+
+    if let Some(mode) = copy_args.on_source_change {
+        options.on_source_change = mode;
+    }
+
+    if let Some(retries) = copy_args.source_change_retries {
+        options.source_change_retries = retries;
+    }
+
+    if copy_args.wait_for_space {
+        options.wait_for_space = true;
+    }
+
+    if copy_args.explain_excludes {
+        options.explain_excludes = true;
+    }
+    if let Some(hash_cache) = copy_args.hash_cache {
+        options.hash_cache = hash_cache;
+    }
     if let Some(preserve_str) = &copy_args.preserve {
         options.preserve = PreserveAttr::from_string(preserve_str)
             .map_err(|e| format!("unable to parse preserve attribute: {}", e))?;
     }
+    if options.attributes_only {
+        options.preserve = PreserveAttr::all();
+    }
+
+    // Subtracted last, so it also trims -p all and --attributes-only
+    let no_preserve = copy_args.no_preserve.as_deref().or(config
+        .map(|cfg| cfg.preserve.no_preserve.as_str())
+        .filter(|list| !list.is_empty()));
+    if let Some(list) = no_preserve {
+        if list.trim().is_empty() {
+            return Err("--no-preserve requires at least one attribute".to_string());
+        }
+        let removed = PreserveAttr::from_string(list)
+            .map_err(|e| format!("unable to parse no-preserve attribute: {}", e))?;
+        options.preserve = options.preserve.without(removed);
+    }
+    if copy_args.chown.is_some() {
+        options.preserve.ownership = true;
+    }
+
+    if copy_args.preserve_strict {
+        options.preserve_policy = Arc::new(PreservePolicy::new(true));
+    }
+
+    let mut rules = PreserveRules::default();
+    if copy_args.chown.is_some() || !copy_args.usermap.is_empty() || !copy_args.groupmap.is_empty()
+    {
+        let owners = OwnerMap::parse(
+            &copy_args.usermap,
+            &copy_args.groupmap,
+            copy_args.chown.as_deref(),
+            copy_args.numeric_ids,
+        )?;
+        rules = rules.with_owners(owners);
+    }
+    let chmod = if copy_args.chmod.is_empty() {
+        config.map_or(&[][..], |cfg| &cfg.preserve.chmod.rules)
+    } else {
+        &copy_args.chmod
+    };
+    if !chmod.is_empty() {
+        rules = rules.with_chmod(ChmodRules::parse(chmod)?);
+    }
+    let xattr_config = config.map(|cfg| &cfg.preserve.xattr);
+    let xattr_include = if copy_args.xattr_include.is_empty() {
+        xattr_config.map_or(&[][..], |xattr| &xattr.include)
+    } else {
+        &copy_args.xattr_include
+    };
+    let xattr_exclude = if copy_args.xattr_exclude.is_empty() {
+        xattr_config.map_or(&[][..], |xattr| &xattr.exclude)
+    } else {
+        &copy_args.xattr_exclude
+    };
+    if !xattr_include.is_empty() || !xattr_exclude.is_empty() {
+        rules = rules.with_xattr_filter(XattrFilter::parse(xattr_include, xattr_exclude)?);
+    }
+    options.preserve_rules = Arc::new(rules);
 
     options.parallel = copy_args.parallel;
 
     options.follow_symlink = copy_args.follow_symlink_mode()?;
 
+    if copy_args.progress == Some(ProgressFormat::Json) {
+        let emitter = match copy_args.progress_fd {
+            Some(fd) => EventEmitter::from_fd(fd)
+                .map_err(|e| format!("invalid --progress-fd {}: {}", fd, e))?,
+            None => EventEmitter::stderr(),
+        };
+        options.events = Some(Arc::new(emitter));
+    } else if copy_args.progress_fd.is_some() {
+        return Err("--progress-fd requires --progress=json".to_string());
+    }
+
     Ok(())
 }
 
-fn build_all_exclude_patterns(
-    copy_args: &CopyArgs,
+/// Apply the filter flags over what the config set, and build the filter rules.
+fn apply_filter_args(
+    options: &mut CopyOptions,
+    filters: &FilterArgs,
+    config: Option<&Config>,
+    config_path: Option<&Path>,
+) -> CpxResult<()> {
+    if filters.exclude_nodump {
+        options.exclude_nodump = true;
+    }
+    if filters.exclude_caches {
+        options.exclusion_markers.caches = true;
+    }
+    for name in &filters.exclude_if_present {
+        if !options.exclusion_markers.names.contains(name) {
+            options.exclusion_markers.names.push(name.clone());
+        }
+    }
+    // Added to the ignore files named in config
+    for name in ignore_file_names(filters.respect_gitignore, &filters.ignore_file) {
+        if !options.ignore_files.contains(&name) {
+            options.ignore_files.push(name);
+        }
+    }
+
+    let groups = build_all_filters(filters, config, config_path).map_err(CpxError::Exclude)?;
+    options.exclude_rules = build_filter_rules(groups).map_err(CpxError::Exclude)?;
+    options.filter_expr = build_filter_expr(filters, config).map_err(CpxError::Exclude)?;
+    Ok(())
+}
+
+/// Command-line rules come first so they take precedence, then `[filter]` rules, then
+/// the plain `[filter]` exclude patterns and presets.
+fn build_all_filters(
+    filters: &FilterArgs,
     config: Option<&Config>,
-) -> crate::error::ExcludeResult<Vec<ExcludePattern>> {
-    let mut all_patterns = Vec::new();
+    config_path: Option<&Path>,
+) -> crate::error::ExcludeResult<Vec<FilterGroup>> {
+    let mut groups = filters.parse_filters()?;
 
     if let Some(cfg) = config {
-        for pattern_str in &cfg.exclude.patterns {
-            all_patterns.extend(parse_exclude_pattern_list(pattern_str)?);
+        let origin = |key| RuleOrigin::Config {
+            path: config_path.map(Path::to_path_buf),
+            key,
+        };
+        for rule in &cfg.filter.rules {
+            let (kind, patterns) = parse_filter_rule(rule)?;
+            groups.push(FilterGroup {
+                kind,
+                origin: origin("rules"),
+                patterns,
+            });
+        }
+        let mut patterns = Vec::new();
+        for pattern_str in &cfg.filter.patterns {
+            patterns.extend(parse_exclude_pattern_list(pattern_str)?);
+        }
+        groups.push(FilterGroup {
+            kind: FilterKind::Exclude,
+            origin: origin("patterns"),
+            patterns,
+        });
+        for name in &cfg.filter.presets {
+            groups.push(FilterGroup {
+                kind: FilterKind::Exclude,
+                origin: RuleOrigin::Preset(name.trim().to_string()),
+                patterns: preset_patterns(name)?,
+            });
         }
     }
 
-    all_patterns.extend(copy_args.parse_exclude_patterns()?);
-    Ok(all_patterns)
+    Ok(groups)
+}
+
+/// `--filter` expressions and the `[filter]` expression, all of which have to match.
+fn build_filter_expr(
+    filters: &FilterArgs,
+    config: Option<&Config>,
+) -> crate::error::ExcludeResult<Option<FilterExpr>> {
+    let config_expr = config
+        .map(|cfg| cfg.filter.expression.as_str())
+        .filter(|expr| !expr.trim().is_empty());
+    let mut combined: Option<FilterExpr> = None;
+    for expr in filters.filter.iter().map(String::as_str).chain(config_expr) {
+        let parsed = FilterExpr::parse(expr)?;
+        combined = Some(match combined {
+            Some(previous) => previous.and(parsed),
+            None => parsed,
+        });
+    }
+    Ok(combined)
+}
+
+fn ignore_file_names(respect_gitignore: bool, extra: &[String]) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    if respect_gitignore {
+        names.extend(GITIGNORE_FILES.iter().map(|name| name.to_string()));
+    }
+    for name in extra {
+        if !names.contains(name) {
+            names.push(name.clone());
+        }
+    }
+    names
 }
 
 fn validate_conflicts(options: &CopyOptions) -> Result<(), String> {
@@ -501,14 +1242,86 @@ impl CopyArgs {
         }
     }
 
-    pub fn parse_exclude_patterns(&self) -> crate::error::ExcludeResult<Vec<ExcludePattern>> {
-        let mut patterns = Vec::new();
+    /// The `--files-from`/`--files-from0` list and its separator, if one was given.
+    fn files_from(&self) -> Option<(&Path, u8)> {
+        match (&self.files_from, &self.files_from0) {
+            (Some(file), _) => Some((file, b'\n')),
+            (None, Some(file)) => Some((file, b'\0')),
+            (None, None) => None,
+        }
+    }
+}
 
-        for pattern_str in &self.exclude {
-            patterns.extend(parse_exclude_pattern_list(pattern_str)?);
+impl FilterArgs {
+    /// `--include`/`--exclude`/`--exclude-from`/`--exclude-preset` values in command-line
+    /// order. Without a recorded order, includes go first.
+    pub fn parse_filters(&self) -> crate::error::ExcludeResult<Vec<FilterGroup>> {
+        let mut includes = self.include.iter();
+        let mut excludes = self.exclude.iter();
+        let mut exclude_files = self.exclude_from.iter();
+        let mut presets = self.exclude_preset.iter();
+        let order = if self.filter_order.is_empty() {
+            let mut order = vec![FilterArg::Include; self.include.len()];
+            order.extend(vec![FilterArg::Exclude; self.exclude.len()]);
+            order.extend(vec![FilterArg::ExcludeFrom; self.exclude_from.len()]);
+            order.extend(vec![FilterArg::ExcludePreset; self.exclude_preset.len()]);
+            order
+        } else {
+            self.filter_order.clone()
+        };
+
+        let mut groups = Vec::with_capacity(order.len());
+        for arg in order {
+            match arg {
+                FilterArg::Include | FilterArg::Exclude => {
+                    let (kind, flag, pattern_str) = match arg {
+                        FilterArg::Include => (FilterKind::Include, "--include", includes.next()),
+                        _ => (FilterKind::Exclude, "--exclude", excludes.next()),
+                    };
+                    if let Some(pattern_str) = pattern_str {
+                        groups.push(FilterGroup {
+                            kind,
+                            origin: RuleOrigin::Flag(flag),
+                            patterns: parse_exclude_pattern_list(pattern_str)?,
+                        });
+                    }
+                }
+                FilterArg::ExcludeFrom => {
+                    let Some(file) = exclude_files.next() else {
+                        continue;
+                    };
+                    let lines = read_patterns(file).map_err(|e| {
+                        ExcludeError::InvalidPattern(format!(
+                            "cannot read exclude file '{}': {}",
+                            file.display(),
+                            e
+                        ))
+                    })?;
+                    // One group per line, so each pattern knows where it was written
+                    for (line, pattern) in lines {
+                        groups.push(FilterGroup {
+                            kind: FilterKind::Exclude,
+                            origin: RuleOrigin::PatternFile {
+                                path: file.clone(),
+                                line,
+                            },
+                            patterns: vec![parse_exclude_pattern(&pattern)?],
+                        });
+                    }
+                }
+                FilterArg::ExcludePreset => {
+                    if let Some(name) = presets.next() {
+                        groups.push(FilterGroup {
+                            kind: FilterKind::Exclude,
+                            origin: RuleOrigin::Preset(name.trim().to_string()),
+                            patterns: preset_patterns(name)?,
+                        });
+                    }
+                }
+            }
         }
 
-        Ok(patterns)
+        Ok(groups)
     }
 }
 
@@ -526,12 +1339,26 @@ mod tests {
                 recursive: false,
                 parallel: 4,
                 resume: false,
+                hash_cache: None,
                 force: false,
                 interactive: false,
                 parents: false,
                 preserve: None,
+                no_preserve: None,
+                chown: None,
+                usermap: vec![],
+                groupmap: vec![],
+                numeric_ids: false,
+                chmod: vec![],
+                xattr_include: vec![],
+                xattr_exclude: vec![],
+                preserve_strict: false,
                 attributes_only: false,
                 remove_destination: false,
+                special: None,
+                on_source_change: None,
+                source_change_retries: None,
+                wait_for_space: false,
                 symbolic_link: Some(SymlinkMode::Auto),
                 hard_link: true,
                 dereference: true,
@@ -539,7 +1366,15 @@ mod tests {
                 dereference_command_line: false,
                 backup: None,
                 reflink: None,
-                exclude: Vec::new(),
+                filters: FilterArgs::default(),
+                files_from: None,
+                files_from0: None,
+                explain_excludes: false,
+                manifest: None,
+                metadata_sidecar: None,
+                hash: None,
+                progress: None,
+                progress_fd: None,
                 no_config: false,
                 config: None,
             }),
@@ -560,12 +1395,26 @@ mod tests {
                 recursive: false,
                 parallel: 4,
                 resume: true,
+                hash_cache: None,
                 force: false,
                 interactive: false,
                 parents: false,
                 preserve: None,
+                no_preserve: None,
+                chown: None,
+                usermap: vec![],
+                groupmap: vec![],
+                numeric_ids: false,
+                chmod: vec![],
+                xattr_include: vec![],
+                xattr_exclude: vec![],
+                preserve_strict: false,
                 attributes_only: false,
                 remove_destination: false,
+                special: None,
+                on_source_change: None,
+                source_change_retries: None,
+                wait_for_space: false,
                 symbolic_link: Some(SymlinkMode::Auto),
                 hard_link: false,
                 dereference: true,
@@ -573,7 +1422,15 @@ mod tests {
                 dereference_command_line: false,
                 backup: None,
                 reflink: None,
-                exclude: Vec::new(),
+                filters: FilterArgs::default(),
+                files_from: None,
+                files_from0: None,
+                explain_excludes: false,
+                manifest: None,
+                metadata_sidecar: None,
+                hash: None,
+                progress: None,
+                progress_fd: None,
                 no_config: false,
                 config: None,
             }),
@@ -594,12 +1451,26 @@ mod tests {
                 recursive: false,
                 parallel: 4,
                 resume: true,
+                hash_cache: None,
                 force: false,
                 interactive: false,
                 parents: false,
                 preserve: None,
+                no_preserve: None,
+                chown: None,
+                usermap: vec![],
+                groupmap: vec![],
+                numeric_ids: false,
+                chmod: vec![],
+                xattr_include: vec![],
+                xattr_exclude: vec![],
+                preserve_strict: false,
                 attributes_only: false,
                 remove_destination: false,
+                special: None,
+                on_source_change: None,
+                source_change_retries: None,
+                wait_for_space: false,
                 symbolic_link: None,
                 hard_link: true,
                 dereference: true,
@@ -607,7 +1478,15 @@ mod tests {
                 dereference_command_line: false,
                 backup: None,
                 reflink: None,
-                exclude: Vec::new(),
+                filters: FilterArgs::default(),
+                files_from: None,
+                files_from0: None,
+                explain_excludes: false,
+                manifest: None,
+                metadata_sidecar: None,
+                hash: None,
+                progress: None,
+                progress_fd: None,
                 no_config: false,
                 config: None,
             }),
@@ -628,12 +1507,26 @@ mod tests {
                 recursive: false,
                 parallel: 4,
                 resume: false,
+                hash_cache: None,
                 force: false,
                 interactive: false,
                 parents: false,
                 preserve: None,
+                no_preserve: None,
+                chown: None,
+                usermap: vec![],
+                groupmap: vec![],
+                numeric_ids: false,
+                chmod: vec![],
+                xattr_include: vec![],
+                xattr_exclude: vec![],
+                preserve_strict: false,
                 attributes_only: false,
                 remove_destination: false,
+                special: None,
+                on_source_change: None,
+                source_change_retries: None,
+                wait_for_space: false,
                 symbolic_link: None,
                 hard_link: false,
                 dereference: true,
@@ -641,7 +1534,15 @@ mod tests {
                 dereference_command_line: false,
                 backup: None,
                 reflink: None,
-                exclude: Vec::new(),
+                filters: FilterArgs::default(),
+                files_from: None,
+                files_from0: None,
+                explain_excludes: false,
+                manifest: None,
+                metadata_sidecar: None,
+                hash: None,
+                progress: None,
+                progress_fd: None,
                 no_config: false,
                 config: None,
             }),
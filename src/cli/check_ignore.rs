@@ -0,0 +1,69 @@
+use crate::cli::args::{CopyOptions, FilterArgs};
+use crate::utility::exclude::Verdict;
+use crate::utility::preprocess::explain_exclusion;
+use clap::Args;
+use std::path::{Component, Path, PathBuf};
+
+#[derive(Args, Debug, Clone)]
+pub struct CheckIgnoreArgs {
+    #[arg(
+        help = "Source directory the filter patterns are relative to",
+        required = true
+    )]
+    pub source: PathBuf,
+
+    #[arg(help = "Paths inside SOURCE to check", required = true)]
+    pub paths: Vec<PathBuf>,
+
+    #[command(flatten)]
+    pub filters: FilterArgs,
+
+    #[arg(long, value_name = "PATH", help = "Use custom config file")]
+    pub config: Option<PathBuf>,
+
+    #[arg(long, help = "Ignore all config files")]
+    pub no_config: bool,
+}
+
+impl CheckIgnoreArgs {
+    /// Prints the rule deciding about each path; returns whether any of them is excluded.
+    pub fn execute(&self, options: &CopyOptions) -> Result<bool, String> {
+        let source = normalized(&self.source)?;
+        let mut any_excluded = false;
+        for path in &self.paths {
+            let absolute = normalized(path)?;
+            if !absolute.starts_with(&source) {
+                return Err(format!(
+                    "'{}' is not inside '{}'",
+                    path.display(),
+                    self.source.display()
+                ));
+            }
+            match explain_exclusion(&absolute, &source, options) {
+                Some(reason) => {
+                    any_excluded |= reason.verdict != Verdict::Include;
+                    println!("{}: {}", path.display(), reason);
+                }
+                None => println!("{}: not excluded", path.display()),
+            }
+        }
+        Ok(any_excluded)
+    }
+}
+
+/// `path` made absolute with `.` and `..` resolved, without touching the filesystem:
+/// like `git check-ignore`, paths that don't exist can be checked too.
+fn normalized(path: &Path) -> Result<PathBuf, String> {
+    let absolute = std::path::absolute(path).map_err(|e| e.to_string())?;
+    let mut normalized = PathBuf::new();
+    for component in absolute.components() {
+        match component {
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            Component::CurDir => {}
+            other => normalized.push(other),
+        }
+    }
+    Ok(normalized)
+}
@@ -1 +1,4 @@
 pub mod args;
+pub mod check_ignore;
+pub mod restore;
+pub mod verify;
@@ -0,0 +1,98 @@
+use crate::utility::preserve::{PreservePolicy, PreserveRules};
+use crate::utility::sidecar::{SIDECAR_NAME, read_sidecar, restore_entry};
+use crate::utility::xattr_filter::XattrFilter;
+use clap::Args;
+use colored::Colorize;
+use std::path::PathBuf;
+
+#[derive(Args, Debug, Clone)]
+pub struct RestoreMetadataArgs {
+    #[arg(
+        long = "sidecar",
+        value_name = "FILE",
+        help = "Metadata sidecar to restore from [default: DIR/.cpx-metadata]"
+    )]
+    pub sidecar: Option<PathBuf>,
+
+    #[arg(
+        long = "numeric-ids",
+        help = "restore the recorded uid/gid instead of looking up the recorded names"
+    )]
+    pub numeric_ids: bool,
+
+    #[arg(
+        long = "xattr-include",
+        value_name = "PATTERN",
+        help = "only restore xattrs whose names match, e.g. 'user.*' (can be specified multiple times)"
+    )]
+    pub xattr_include: Vec<String>,
+
+    #[arg(
+        long = "xattr-exclude",
+        value_name = "PATTERN",
+        help = "don't restore xattrs whose names match (can be specified multiple times)"
+    )]
+    pub xattr_exclude: Vec<String>,
+
+    #[arg(
+        long = "preserve-strict",
+        help = "fail when an attribute can't be applied, instead of summarizing"
+    )]
+    pub preserve_strict: bool,
+
+    #[arg(help = "Directory the sidecar paths are relative to", required = true)]
+    pub dir: PathBuf,
+}
+
+impl RestoreMetadataArgs {
+    /// Returns whether every entry of the sidecar was restored.
+    pub fn execute(&self) -> std::io::Result<bool> {
+        let sidecar = self
+            .sidecar
+            .clone()
+            .unwrap_or_else(|| self.dir.join(SIDECAR_NAME));
+        let mut rules = PreserveRules::default();
+        if !self.xattr_include.is_empty() || !self.xattr_exclude.is_empty() {
+            let filter = XattrFilter::parse(&self.xattr_include, &self.xattr_exclude)
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+            rules = rules.with_xattr_filter(filter);
+        }
+        let (mut entries, malformed) = read_sidecar(&sidecar)?;
+        let policy = PreservePolicy::new(self.preserve_strict);
+
+        // Deepest first, so a directory's own metadata is applied after its contents'
+        entries.sort_by(|a, b| b.path.cmp(&a.path));
+
+        let mut restored = 0;
+        let mut failed = 0;
+        for entry in &entries {
+            match restore_entry(&self.dir, entry, self.numeric_ids, &rules, &policy) {
+                Ok(()) => restored += 1,
+                Err(e) => {
+                    failed += 1;
+                    println!("{}: {} ({})", entry.path, "FAILED".red().bold(), e);
+                }
+            }
+        }
+
+        println!("Restored metadata of {} entries", restored);
+        for (attribute, count) in policy.dropped() {
+            eprintln!(
+                "{} {} not restored on {} entries",
+                "Warning:".yellow().bold(),
+                attribute,
+                count
+            );
+        }
+        if malformed > 0 {
+            eprintln!(
+                "{} {} line(s) of {} could not be parsed",
+                "Warning:".yellow().bold(),
+                malformed,
+                sidecar.display()
+            );
+        }
+
+        Ok(failed == 0 && malformed == 0)
+    }
+}
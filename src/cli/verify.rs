@@ -0,0 +1,80 @@
+use crate::utility::manifest::{HashAlgorithm, verify_manifest};
+use clap::Args;
+use colored::Colorize;
+use std::path::PathBuf;
+
+#[derive(Args, Debug, Clone)]
+pub struct VerifyArgs {
+    #[arg(
+        long = "manifest",
+        value_name = "FILE",
+        required = true,
+        help = "Checksum manifest to verify against"
+    )]
+    pub manifest: PathBuf,
+
+    #[arg(
+        long = "hash",
+        value_name = "ALGORITHM",
+        help = "hash algorithm of untagged manifest lines (xxh3, sha256, blake3; default: from the digests)"
+    )]
+    pub hash: Option<HashAlgorithm>,
+
+    #[arg(
+        short = 'q',
+        long = "quiet",
+        help = "don't print OK for each successfully verified file"
+    )]
+    pub quiet: bool,
+
+    #[arg(help = "Directory the manifest paths are relative to", required = true)]
+    pub dir: PathBuf,
+}
+
+impl VerifyArgs {
+    /// Returns whether every entry of the manifest matched.
+    pub fn execute(&self) -> std::io::Result<bool> {
+        let report = verify_manifest(&self.manifest, &self.dir, self.hash)?;
+
+        if !self.quiet {
+            for path in &report.ok {
+                println!("{}: {}", path.display(), "OK".green());
+            }
+        }
+        for path in &report.mismatched {
+            println!("{}: {}", path.display(), "FAILED".red().bold());
+        }
+        for (path, reason) in &report.unreadable {
+            println!(
+                "{}: {} ({})",
+                path.display(),
+                "FAILED open or read".red(),
+                reason
+            );
+        }
+
+        if report.malformed_lines > 0 {
+            eprintln!(
+                "{} {} line(s) are improperly formatted",
+                "Warning:".yellow().bold(),
+                report.malformed_lines
+            );
+        }
+        if !report.mismatched.is_empty() {
+            eprintln!(
+                "{} {} computed checksum(s) did NOT match",
+                "Warning:".yellow().bold(),
+                report.mismatched.len()
+            );
+        }
+        if !report.unreadable.is_empty() {
+            eprintln!(
+                "{} {} listed file(s) could not be read",
+                "Warning:".yellow().bold(),
+                report.unreadable.len()
+            );
+        }
+
+        Ok(report.is_success())
+    }
+}
@@ -1,5 +1,6 @@
 use super::loader::{find_config_files, load_config};
 use super::schema::Config;
+use crate::utility::exclude_presets::EXCLUDE_PRESETS;
 use clap::Subcommand;
 use colored::Colorize;
 use std::fs;
@@ -16,6 +17,8 @@ pub enum ConfigCommand {
     Show,
     /// Show config file locations
     Path,
+    /// List the built-in exclude presets and their patterns
+    Presets,
 }
 
 impl ConfigCommand {
@@ -24,6 +27,7 @@ impl ConfigCommand {
             ConfigCommand::Init { force } => init_config(*force),
             ConfigCommand::Show => show_config(),
             ConfigCommand::Path => show_paths(),
+            ConfigCommand::Presets => show_presets(),
         }
     }
 }
@@ -119,6 +123,24 @@ fn show_config() -> std::io::Result<()> {
     Ok(())
 }
 
+fn show_presets() -> std::io::Result<()> {
+    println!("{}", "Exclude Presets".bold().underline());
+    println!();
+    for (name, patterns) in EXCLUDE_PRESETS {
+        println!("{}", name.bright_blue().bold());
+        for pattern in *patterns {
+            println!("  {}", pattern);
+        }
+    }
+    println!();
+    println!(
+        "Use with {} or {} in [filter]",
+        "--exclude-preset rust,node".green(),
+        "presets = [\"rust\", \"node\"]".green()
+    );
+    Ok(())
+}
+
 fn show_paths() -> std::io::Result<()> {
     use std::path::PathBuf;
 
@@ -179,11 +201,17 @@ fn add_comments_to_config(toml: &str) -> String {
     for line in toml.lines() {
         // Add section comments
         match line {
-            l if l.starts_with("[exclude]") => {
-                result.push_str("# Exclude patterns (glob syntax supported)\n");
+            l if l.starts_with("[filter]") => {
+                result
+                    .push_str("# Include/exclude rules and exclude patterns (gitignore syntax)\n");
+                result.push_str("# Example: rules = [\"+ *.parquet\", \"- *\"]\n");
                 result.push_str(
                     "# Example: patterns = [\"*.tmp\", \"*.log\", \"node_modules\", \".git\"]\n",
                 );
+                result.push_str(
+                    "# Example: presets = [\"rust\", \"macos-junk\"] (cpx config presets)\n",
+                );
+                result.push_str("# Example: expression = \"size > 100M and mtime < 7d\"\n");
             }
             l if l.starts_with("[copy]") => {
                 result.push_str("\n# Copy operation settings\n");
@@ -192,6 +220,16 @@ fn add_comments_to_config(toml: &str) -> String {
                 result.push_str("\n# Preserve file attributes\n");
                 result.push_str("# mode values: \"none\", \"default\", \"all\", or \"mode,timestamps,ownership\"\n");
             }
+            l if l.starts_with("[preserve.chmod]") => {
+                result.push_str(
+                    "# Permission rewrite rules, e.g. rules = [\"D755\", \"F644\", \"g-w\"]\n",
+                );
+            }
+            l if l.starts_with("[preserve.xattr]") => {
+                result.push_str(
+                    "# Which xattrs are preserved, e.g. include = [\"user.*\"], exclude = [\"user.com.dropbox.*\"]\n",
+                );
+            }
             l if l.starts_with("[symlink]") => {
                 result.push_str("\n# Symlink handling\n");
                 result.push_str("# mode: \"auto\", \"absolute\", \"relative\"\n");
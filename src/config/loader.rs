@@ -33,11 +33,16 @@ pub fn load_config_file(path: &Path) -> ConfigResult<Config> {
 
 /// Load and merge all config files (reverse priority: system < user < project)
 pub fn load_config() -> Config {
+    load_config_with_path().0
+}
+
+/// Like [`load_config`], also returning the file the config came from.
+pub fn load_config_with_path() -> (Config, Option<PathBuf>) {
     let project = PathBuf::from("./cpxconfig.toml");
     if project.exists()
         && let Ok(config) = load_config_file(&project)
     {
-        return config;
+        return (config, Some(project));
     }
 
     if let Some(config_dir) = dirs::config_dir() {
@@ -45,7 +50,7 @@ pub fn load_config() -> Config {
         if user.exists()
             && let Ok(config) = load_config_file(&user)
         {
-            return config;
+            return (config, Some(user));
         }
     }
 
@@ -55,9 +60,9 @@ pub fn load_config() -> Config {
         if system.exists()
             && let Ok(config) = load_config_file(&system)
         {
-            return config;
+            return (config, Some(system));
         }
     }
 
-    Config::default()
+    (Config::default(), None)
 }
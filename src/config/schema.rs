@@ -3,8 +3,16 @@ use serde::{Deserialize, Serialize};
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default)]
 #[derive(Default)]
-pub struct ExcludeConfig {
-    pub patterns: Vec<String>,
+pub struct FilterConfig {
+    pub rules: Vec<String>,    // ordered, first match wins: ["+ *.parquet", "- *"]
+    pub patterns: Vec<String>, // excluded after the rules
+    pub presets: Vec<String>,  // built-in pattern sets: "rust", "node", ... (cpx config presets)
+    pub nodump: bool,
+    pub exclude_caches: bool, // skip directories with a CACHEDIR.TAG
+    pub exclude_if_present: Vec<String>, // skip directories containing any of these names
+    pub respect_gitignore: bool, // read .gitignore and .cpxignore in copied directories
+    pub ignore_files: Vec<String>, // more per-directory ignore file names
+    pub expression: String,   // like --filter, "" for none
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -16,14 +24,38 @@ pub struct CopyConfig {
     pub force: bool,
     pub interactive: bool,
     pub resume: bool,
+    pub hash_cache: String, // "none", "xattr"
     pub attributes_only: bool,
     pub remove_destination: bool,
+    pub special: String,          // "recreate", "skip", "read"
+    pub on_source_change: String, // "retry", "warn", "fail"
+    pub source_change_retries: usize,
+    pub wait_for_space: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default)]
 pub struct PreserveConfig {
     pub mode: String,
+    pub no_preserve: String,
+    pub strict: bool,
+    pub chmod: ChmodConfig,
+    pub xattr: XattrConfig,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+#[derive(Default)]
+pub struct ChmodConfig {
+    pub rules: Vec<String>, // rsync-style, e.g. ["D755", "F644", "g-w"]
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+#[derive(Default)]
+pub struct XattrConfig {
+    pub include: Vec<String>, // glob patterns on xattr names, e.g. ["user.*"]
+    pub exclude: Vec<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -72,7 +104,8 @@ pub struct ProgressColorConfig {
 #[serde(default)]
 #[derive(Default)]
 pub struct Config {
-    pub exclude: ExcludeConfig,
+    #[serde(alias = "exclude")]
+    pub filter: FilterConfig,
     pub copy: CopyConfig,
     pub preserve: PreserveConfig,
     pub symlink: SymlinkConfig,
@@ -90,8 +123,13 @@ impl Default for CopyConfig {
             force: false,
             interactive: false,
             resume: false,
+            hash_cache: "none".to_string(),
             attributes_only: false,
             remove_destination: false,
+            special: "recreate".to_string(),
+            on_source_change: "warn".to_string(),
+            source_change_retries: 3,
+            wait_for_space: false,
         }
     }
 }
@@ -100,6 +138,10 @@ impl Default for PreserveConfig {
     fn default() -> Self {
         Self {
             mode: "default".to_string(),
+            no_preserve: String::new(),
+            strict: false,
+            chmod: ChmodConfig::default(),
+            xattr: XattrConfig::default(),
         }
     }
 }
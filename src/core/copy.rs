@@ -1,31 +1,45 @@
-use crate::cli::args::{BackupMode, CopyOptions, FollowSymlink};
+use crate::cli::args::{BackupMode, CopyOptions, FollowSymlink, SourceChangeMode};
 #[cfg(target_os = "linux")]
 use crate::core::fast_copy::fast_copy;
 use crate::error::{CopyError, CopyResult};
 use crate::utility::backup::{create_backup, generate_backup_path};
+use crate::utility::events::{Event, path_string};
 use crate::utility::helper::{
-    create_directories, create_hardlink, create_symlink, prompt_overwrite,
+    available_space, create_destination, create_directories, create_hardlink, create_special,
+    create_symlink, permission_bits, prompt_overwrite,
 };
+use crate::utility::manifest::{StreamHasher, hash_file};
 use crate::utility::preprocess::{
-    CopyPlan, preprocess_directory, preprocess_file, preprocess_multiple,
+    CopyPlan, FileTask, preprocess_directory, preprocess_file, preprocess_multiple,
+    special_file_type,
 };
-use crate::utility::preserve::{self, HardLinkTracker, PreserveAttr};
+use crate::utility::preserve::{self, HardLinkTracker};
 use crate::utility::progress_bar::ProgressBarStyle;
-use indicatif::ProgressBar;
+use indicatif::{HumanBytes, HumanCount, ProgressBar, ProgressDrawTarget};
 use rayon::prelude::*;
+use std::collections::HashMap;
 use std::io::{self, Read, Write};
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use std::{path::Path, path::PathBuf};
 
+/// How often `--wait-for-space` checks the destination's free space.
+const SPACE_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
 pub fn copy(source: &Path, destination: &Path, options: &CopyOptions) -> CopyResult<()> {
+    emit_start(options);
     let source_metadata = match options.follow_symlink {
         FollowSymlink::Dereference | FollowSymlink::CommandLineSymlink => std::fs::metadata(source)
             .map_err(|_e| CopyError::InvalidSource(source.to_path_buf()))?,
         FollowSymlink::NoDereference => std::fs::symlink_metadata(source)
             .map_err(|_e| CopyError::InvalidSource(source.to_path_buf()))?,
     };
-    let source_root = source.parent().unwrap_or(source);
+    // Filter rules see listed paths relative to the --files-from base
+    let source_root = match &options.parents_base {
+        Some(base) => base.as_path(),
+        None => source.parent().unwrap_or(source),
+    };
     let destination_metadata = std::fs::metadata(destination).ok();
 
     let plan = if source_metadata.is_dir() {
@@ -66,10 +80,7 @@ pub fn copy(source: &Path, destination: &Path, options: &CopyOptions) -> CopyRes
         })?
     };
 
-    if plan.skipped_files > 0 {
-        eprintln!("Skipping {} files that already exist", plan.skipped_files);
-    }
-
+    report_skipped(&plan, options);
     execute_copy(plan, options)
 }
 
@@ -78,45 +89,264 @@ pub fn multiple_copy(
     destination: PathBuf,
     options: &CopyOptions,
 ) -> CopyResult<()> {
-    let plan = preprocess_multiple(&sources, &destination, options).map_err(|e| {
+    emit_start(options);
+    let mut plan = preprocess_multiple(&sources, &destination, options).map_err(|e| {
         CopyError::CopyFailed {
-            source: sources[0].clone(),
+            source: sources.first().cloned().unwrap_or_default(),
             destination: destination.clone(),
             reason: e.to_string(),
         }
     })?;
+    report_skipped(&plan, options);
+    let missing = std::mem::take(&mut plan.missing_sources);
+    execute_copy(plan, options)?;
+    if !missing.is_empty() {
+        return Err(CopyError::Io(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("{} listed path(s) do not exist", missing.len()),
+        )));
+    }
+    Ok(())
+}
+
+fn report_skipped(plan: &CopyPlan, options: &CopyOptions) {
+    if let Some(events) = &options.events {
+        // Skipped files and pruned directories are counted in the summary event
+        for missing in &plan.missing_sources {
+            events.emit(Event::Warning {
+                source: Some(path_string(missing)),
+                message: "listed path does not exist".to_string(),
+                file_type: None,
+            });
+        }
+        return;
+    }
     if plan.skipped_files > 0 {
         eprintln!("Skipping {} files that already exist", plan.skipped_files);
     }
-    execute_copy(plan, options)
+    for missing in &plan.missing_sources {
+        eprintln!(
+            "Warning: listed path '{}' does not exist",
+            missing.display()
+        );
+    }
+    if plan.pruned_directories > 0 {
+        eprintln!(
+            "Skipped {} directories marked by an exclusion file",
+            plan.pruned_directories
+        );
+    }
 }
 
 fn execute_copy(plan: CopyPlan, options: &CopyOptions) -> CopyResult<()> {
-    if !options.attributes_only {
-        create_directories(&plan.directories)?;
+    if let Some(events) = &options.events {
+        events.emit(Event::ScanComplete {
+            files: plan.total_files,
+            bytes: plan.total_size,
+            directories: plan.directories.len(),
+            symlinks: plan.total_symlinks,
+            skipped: plan.skipped_files,
+        });
+    }
+
+    let deferred_modes = if options.attributes_only {
+        HashMap::new()
     } else {
-        for dir_task in &plan.directories {
-            if let Some(src) = &dir_task.source
-                && std::fs::symlink_metadata(&dir_task.destination).is_ok()
-            {
-                preserve::apply_preserve_attrs(src, &dir_task.destination, options.preserve)
-                    .map_err(|e| CopyError::CopyFailed {
-                        source: src.clone(),
-                        destination: dir_task.destination.clone(),
-                        reason: e.to_string(),
-                    })?;
-            }
+        create_directories(&plan.directories, options.umask)?
+    };
+
+    let result = copy_entries(&plan, options);
+    // An interrupted or failed copy leaves its directories writable, so it can be resumed
+    let completed = matches!(result, Ok(()) | Err(CopyError::SourceChanged(_)));
+    let finalized = if completed {
+        finalize_directories(&plan, &deferred_modes, options)
+    } else {
+        Ok(())
+    };
+    report_unpreserved(options);
+    let recorded = write_sidecar(&plan, options);
+    #[cfg(target_os = "linux")]
+    let locked = if completed && options.preserve.flags {
+        lock_inode_flags(&plan, options)
+    } else {
+        Ok(())
+    };
+    #[cfg(not(target_os = "linux"))]
+    let locked: CopyResult<()> = Ok(());
+    result?;
+    finalized?;
+    recorded?;
+    locked
+}
+
+/// Set `+i` and `+a` once everything else is written, since they'd stop hard links,
+/// retries and the sidecar from being written.
+#[cfg(target_os = "linux")]
+fn lock_inode_flags(plan: &CopyPlan, options: &CopyOptions) -> CopyResult<()> {
+    let files = plan
+        .files
+        .iter()
+        .map(|file| (&file.source, &file.destination));
+    let directories = plan
+        .directories
+        .iter()
+        .filter_map(|dir| Some((dir.source.as_ref()?, &dir.destination)));
+    for (source, destination) in files.chain(directories) {
+        if std::fs::symlink_metadata(destination).is_ok() {
+            preserve::lock_inode_flags(source, destination, &options.preserve_policy)?;
         }
     }
+    Ok(())
+}
+
+/// Record the metadata of everything that was copied, for `cpx restore-metadata`.
+fn write_sidecar(plan: &CopyPlan, options: &CopyOptions) -> CopyResult<()> {
+    let Some(sidecar) = &options.metadata_sidecar else {
+        return Ok(());
+    };
 
+    let directories = plan
+        .directories
+        .iter()
+        .filter_map(|dir| Some((dir.source.clone()?, dir.destination.clone())));
+    let files = plan
+        .files
+        .iter()
+        .map(|file| (file.source.clone(), file.destination.clone()));
+    // A link made by -s has no original to describe, so it's recorded as created
+    let symlinks = plan.symlinks.iter().map(|link| {
+        let origin = link.origin.as_ref().unwrap_or(&link.destination);
+        (origin.clone(), link.destination.clone())
+    });
+    let specials = plan
+        .specials
+        .iter()
+        .map(|special| (special.source.clone(), special.destination.clone()));
+    let entries: Vec<_> = directories
+        .chain(files)
+        .chain(symlinks)
+        .chain(specials)
+        .filter(|(_, destination)| std::fs::symlink_metadata(destination).is_ok())
+        .collect();
+
+    let skipped = sidecar
+        .write(&entries, &options.preserve_rules)
+        .map_err(|e| CopyError::CopyFailed {
+            source: PathBuf::new(),
+            destination: sidecar.path.clone(),
+            reason: format!("Failed to write metadata sidecar: {}", e),
+        })?;
+    if skipped > 0 {
+        let message = format!(
+            "{} entries with non UTF-8 names left out of {}",
+            skipped,
+            sidecar.path.display()
+        );
+        match &options.events {
+            Some(events) => events.emit(Event::Warning {
+                source: None,
+                message,
+                file_type: None,
+            }),
+            None => eprintln!("Warning: {}", message),
+        }
+    }
+    Ok(())
+}
+
+/// Summarize the attributes that lenient preservation had to drop.
+fn report_unpreserved(options: &CopyOptions) {
+    for (attribute, count) in options.preserve_policy.dropped() {
+        let message = unpreserved_message(attribute, count);
+        match &options.events {
+            Some(events) => events.emit(Event::Warning {
+                source: None,
+                message,
+                file_type: None,
+            }),
+            None => eprintln!("Warning: {}", message),
+        }
+    }
+}
+
+fn unpreserved_message(attribute: &str, count: usize) -> String {
+    format!(
+        "{} not preserved on {} {}",
+        attribute,
+        HumanCount(count as u64),
+        if count == 1 { "file" } else { "files" }
+    )
+}
+
+/// Apply directory metadata once everything inside has been written, deepest first,
+/// so writing the contents can't bump the mtimes and read-only modes can't get in the way.
+fn finalize_directories(
+    plan: &CopyPlan,
+    deferred_modes: &HashMap<PathBuf, u32>,
+    options: &CopyOptions,
+) -> CopyResult<()> {
+    let mut dirs: Vec<_> = plan.directories.iter().collect();
+    dirs.sort_by(|a, b| {
+        b.destination
+            .components()
+            .count()
+            .cmp(&a.destination.components().count())
+            .then_with(|| a.destination.cmp(&b.destination))
+    });
+    dirs.dedup_by(|a, b| a.destination == b.destination);
+
+    for dir_task in dirs {
+        #[cfg(unix)]
+        if let Some(mode) = deferred_modes.get(&dir_task.destination) {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(
+                &dir_task.destination,
+                std::fs::Permissions::from_mode(*mode),
+            )?;
+        }
+
+        if options.applies_attributes()
+            && let Some(src) = &dir_task.source
+            && std::fs::symlink_metadata(&dir_task.destination).is_ok()
+        {
+            preserve::apply_preserve_attrs(
+                src,
+                &dir_task.destination,
+                options.preserve,
+                &options.preserve_rules,
+                &options.preserve_policy,
+            )
+            .map_err(|e| CopyError::CopyFailed {
+                source: src.clone(),
+                destination: dir_task.destination.clone(),
+                reason: e.to_string(),
+            })?;
+        }
+    }
+    Ok(())
+}
+
+fn report_created(options: &CopyOptions, count: usize, what: &str) {
+    let message = format!("Created {} {}", count, what);
+    match &options.events {
+        Some(events) => events.emit(Event::Info {
+            source: None,
+            message,
+        }),
+        None => println!("{}", message),
+    }
+}
+
+fn copy_entries(plan: &CopyPlan, options: &CopyOptions) -> CopyResult<()> {
     if options.hard_link {
         for hardlink_task in &plan.hardlinks {
             create_hardlink(hardlink_task, options)?;
         }
 
         if plan.total_hardlinks > 0 {
-            println!("Created {} hard links", plan.total_hardlinks);
+            report_created(options, plan.total_hardlinks, "hard links");
         }
+        emit_summary(options, plan, 0, 0);
         return Ok(());
     }
 
@@ -126,25 +356,61 @@ fn execute_copy(plan: CopyPlan, options: &CopyOptions) -> CopyResult<()> {
                 source: symlink_task.source.clone(),
                 destination: symlink_task.destination.clone(),
             })?;
+
+            if let Some(origin) = &symlink_task.origin
+                && (options.preserve.timestamps || options.preserve.ownership)
+                && symlink_task.destination.is_symlink()
+            {
+                preserve::apply_symlink_attrs(
+                    origin,
+                    &symlink_task.destination,
+                    options.preserve,
+                    &options.preserve_rules,
+                    &options.preserve_policy,
+                )
+                .map_err(|e| CopyError::CopyFailed {
+                    source: origin.clone(),
+                    destination: symlink_task.destination.clone(),
+                    reason: e.to_string(),
+                })?;
+            }
         }
         if plan.total_symlinks > 0 {
-            println!("Created {} symbolic links", plan.total_symlinks);
+            report_created(options, plan.total_symlinks, "symbolic links");
         }
 
         if options.symbolic_link.is_some() {
+            emit_summary(options, plan, 0, 0);
             return Ok(());
         }
     }
 
+    create_specials(plan, options)?;
+
     let overall_pb = if plan.total_files >= 1 && !options.interactive && !options.attributes_only {
-        let pb = ProgressBar::new(plan.total_size);
-        options.progress_bar.apply(&pb, plan.total_files);
+        let pb = if options.events.is_some() {
+            ProgressBar::with_draw_target(Some(plan.total_size), ProgressDrawTarget::hidden())
+        } else {
+            let pb = ProgressBar::new(plan.total_size);
+            options.progress_bar.apply(&pb, plan.total_files);
+            pb
+        };
         Some(Arc::new(pb))
     } else {
         None
     };
 
     let completed_files = Arc::new(AtomicUsize::new(0));
+    let changed_files = Mutex::new(Vec::new());
+
+    let ticker = match (&options.events, &overall_pb) {
+        (Some(events), Some(pb)) => Some(events.spawn_ticker(
+            ProgressBar::clone(pb),
+            completed_files.clone(),
+            plan.total_files,
+        )),
+        _ => None,
+    };
 
     // Initialize hard link tracker if preserve.links is enabled
     let hardlink_tracker = if options.preserve.links {
@@ -155,16 +421,15 @@ fn execute_copy(plan: CopyPlan, options: &CopyOptions) -> CopyResult<()> {
 
     // For interactive mode, process sequentially
     if options.interactive {
-        for file_task in plan.files {
-            copy_core(
-                &file_task.source,
-                &file_task.destination,
-                file_task.size,
+        for file_task in &plan.files {
+            run_file_task(
+                file_task,
                 overall_pb.as_deref(),
                 &completed_files,
                 plan.total_files,
                 options,
                 hardlink_tracker.as_ref(),
+                &changed_files,
             )?;
         }
     } else {
@@ -181,15 +446,14 @@ fn execute_copy(plan: CopyPlan, options: &CopyOptions) -> CopyResult<()> {
             plan.files
                 .par_iter()
                 .map(|file_task| {
-                    let result = copy_core(
-                        &file_task.source,
-                        &file_task.destination,
-                        file_task.size,
+                    let result = run_file_task(
+                        file_task,
                         overall_pb.as_deref(),
                         &completed_files,
                         plan.total_files,
                         options,
                         hardlink_tracker.as_ref(),
+                        &changed_files,
                     );
 
                     match result {
@@ -202,13 +466,17 @@ fn execute_copy(plan: CopyPlan, options: &CopyOptions) -> CopyResult<()> {
 
         let mut interrupted = false;
         let mut errors: Vec<(PathBuf, PathBuf, CopyError)> = Vec::new();
+        let mut unwritten: Vec<(PathBuf, u64)> = Vec::new();
 
-        for result in results.into_iter() {
+        for (file_task, result) in plan.files.iter().zip(results) {
             if let Err((source, dest, e)) = result {
                 match e {
                     CopyError::Io(ref io_err) if io_err.kind() == io::ErrorKind::Interrupted => {
                         interrupted = true;
                     }
+                    ref e if is_out_of_space(e) => {
+                        unwritten.push((dest, file_task.size));
+                    }
                     _ => {
                         errors.push((source, dest, e));
                     }
@@ -216,11 +484,27 @@ fn execute_copy(plan: CopyPlan, options: &CopyOptions) -> CopyResult<()> {
             }
         }
 
+        if let Some(ticker) = ticker {
+            ticker.stop();
+        }
+
         if interrupted {
             let completed = completed_files.load(Ordering::Relaxed);
 
-            eprintln!("\nCompleted:  {} files", completed);
-            eprintln!("Remaining:  {} files", plan.total_files - completed);
+            match &options.events {
+                Some(events) => events.emit(Event::Warning {
+                    source: None,
+                    message: format!(
+                        "interrupted after {} of {} files",
+                        completed, plan.total_files
+                    ),
+                    file_type: None,
+                }),
+                None => {
+                    eprintln!("\nCompleted:  {} files", completed);
+                    eprintln!("Remaining:  {} files", plan.total_files - completed);
+                }
+            }
 
             return Err(CopyError::Io(io::Error::new(
                 io::ErrorKind::Interrupted,
@@ -228,20 +512,46 @@ fn execute_copy(plan: CopyPlan, options: &CopyOptions) -> CopyResult<()> {
             )));
         }
 
-        if !errors.is_empty() {
+        if !errors.is_empty() || !unwritten.is_empty() {
             if let Some(pb) = overall_pb {
                 pb.abandon_with_message("Completed with errors");
             }
-            eprintln!("\nFailed to copy {} file(s):", errors.len());
-            for (source, _dest, err) in errors.iter().take(3) {
-                eprintln!("  {} - {}", source.display(), err);
+            write_manifest(plan, options)?;
+            let changed = take_changed(changed_files);
+            let failed = errors.len() + unwritten.len();
+            if options.events.is_some() {
+                if !unwritten.is_empty() {
+                    events_out_of_space(options, &unwritten);
+                }
+                emit_summary(options, plan, failed, changed.len());
+            } else {
+                report_changed(&changed);
+                if !errors.is_empty() {
+                    eprintln!("\nFailed to copy {} file(s):", errors.len());
+                    for (source, _dest, err) in errors.iter().take(3) {
+                        eprintln!("  {} - {}", source.display(), err);
+                    }
+                    if errors.len() > 3 {
+                        eprintln!("  ... and {} more", errors.len() - 3);
+                    }
+                }
+                if !unwritten.is_empty() {
+                    eprintln!("\n{}", out_of_space_message(&unwritten));
+                }
             }
-            if errors.len() > 3 {
-                eprintln!("  ... and {} more", errors.len() - 5);
+            // Failing only because sources changed gets its own exit status
+            if unwritten.is_empty()
+                && errors
+                    .iter()
+                    .all(|(_, _, e)| matches!(e, CopyError::SourceModified(_)))
+            {
+                return Err(CopyError::SourceModified(
+                    errors.into_iter().map(|(source, _, _)| source).collect(),
+                ));
             }
             return Err(CopyError::Io(io::Error::other(format!(
                 "{} file(s) failed to copy",
-                errors.len()
+                failed
             ))));
         }
     }
@@ -256,9 +566,435 @@ fn execute_copy(plan: CopyPlan, options: &CopyOptions) -> CopyResult<()> {
         }
     }
 
+    write_manifest(plan, options)?;
+    let changed = take_changed(changed_files);
+    emit_summary(options, plan, 0, changed.len());
+
+    if !changed.is_empty() {
+        if options.events.is_none() {
+            report_changed(&changed);
+        }
+        return Err(CopyError::SourceChanged(changed));
+    }
+
+    Ok(())
+}
+
+fn is_out_of_space(e: &CopyError) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::StorageFull | io::ErrorKind::QuotaExceeded
+    )
+}
+
+/// Describe how much more room the files that didn't fit would have needed.
+fn out_of_space_message(unwritten: &[(PathBuf, u64)]) -> String {
+    let needed: u64 = unwritten.iter().map(|(_, size)| size).sum();
+    let available = unwritten
+        .first()
+        .and_then(|(dest, _)| dest.parent())
+        .and_then(|dir| available_space(dir).ok());
+
+    match available {
+        Some(available) => format!(
+            "Destination is out of space: {} file(s) not copied, {} still needed ({} available)",
+            unwritten.len(),
+            HumanBytes(needed),
+            HumanBytes(available)
+        ),
+        None => format!(
+            "Destination is out of space: {} file(s) not copied, {} still needed",
+            unwritten.len(),
+            HumanBytes(needed)
+        ),
+    }
+}
+
+fn events_out_of_space(options: &CopyOptions, unwritten: &[(PathBuf, u64)]) {
+    if let Some(events) = &options.events {
+        events.emit(Event::Error {
+            source: None,
+            destination: None,
+            message: out_of_space_message(unwritten),
+        });
+    }
+}
+
+fn take_changed(changed_files: Mutex<Vec<PathBuf>>) -> Vec<PathBuf> {
+    let mut changed = changed_files.into_inner().unwrap_or_default();
+    changed.sort();
+    changed
+}
+
+fn report_changed(changed: &[PathBuf]) {
+    if changed.is_empty() {
+        return;
+    }
+    eprintln!(
+        "\nWarning: {} file(s) changed during copy and may be inconsistent:",
+        changed.len()
+    );
+    for source in changed.iter().take(3) {
+        eprintln!("  {}", source.display());
+    }
+    if changed.len() > 3 {
+        eprintln!("  ... and {} more", changed.len() - 3);
+    }
+}
+
+fn create_specials(plan: &CopyPlan, options: &CopyOptions) -> CopyResult<()> {
+    for special_task in &plan.specials {
+        if !options.attributes_only
+            && let Err(e) = create_special(special_task, options)
+        {
+            // Creating device nodes needs CAP_MKNOD; leave them out like a skipped file
+            if e.kind() == io::ErrorKind::PermissionDenied && is_device(special_task.mode) {
+                match &options.events {
+                    Some(events) => events.emit(Event::Warning {
+                        source: Some(path_string(&special_task.source)),
+                        message: format!("skipping device node: {}", e),
+                        file_type: Some(special_file_type(special_task.mode)),
+                    }),
+                    None => eprintln!(
+                        "Skipping device node {}: {}",
+                        special_task.source.display(),
+                        e
+                    ),
+                }
+                continue;
+            }
+            return Err(CopyError::CopyFailed {
+                source: special_task.source.clone(),
+                destination: special_task.destination.clone(),
+                reason: e.to_string(),
+            });
+        }
+
+        if std::fs::symlink_metadata(&special_task.destination).is_ok() {
+            preserve::apply_preserve_attrs(
+                &special_task.source,
+                &special_task.destination,
+                options.preserve,
+                &options.preserve_rules,
+                &options.preserve_policy,
+            )
+            .map_err(|e| CopyError::CopyFailed {
+                source: special_task.source.clone(),
+                destination: special_task.destination.clone(),
+                reason: e.to_string(),
+            })?;
+        }
+    }
+    Ok(())
+}
+
+fn is_device(mode: u32) -> bool {
+    #[cfg(unix)]
+    {
+        let file_type = mode as libc::mode_t & libc::S_IFMT;
+        file_type == libc::S_IFBLK || file_type == libc::S_IFCHR
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = mode;
+        false
+    }
+}
+
+fn write_manifest(plan: &CopyPlan, options: &CopyOptions) -> CopyResult<()> {
+    let Some(manifest) = &options.manifest else {
+        return Ok(());
+    };
+
+    // Files skipped by --resume are part of the delivered tree too
+    for destination in &plan.skipped_destinations {
+        manifest.record_file(destination)?;
+    }
+
+    manifest.write().map_err(|e| CopyError::CopyFailed {
+        source: PathBuf::new(),
+        destination: manifest.path.clone(),
+        reason: format!("Failed to write manifest: {}", e),
+    })
+}
+
+fn emit_start(options: &CopyOptions) {
+    if let Some(events) = &options.events {
+        events.emit(Event::Start {
+            version: env!("CARGO_PKG_VERSION"),
+        });
+    }
+}
+
+fn emit_summary(options: &CopyOptions, plan: &CopyPlan, failed: usize, changed: usize) {
+    if let Some(events) = &options.events {
+        events.emit(Event::Summary {
+            files: plan.total_files - failed,
+            bytes: plan.total_size,
+            failed,
+            skipped: plan.skipped_files,
+            changed,
+            pruned_directories: plan.pruned_directories,
+            elapsed_ms: events.elapsed_ms(),
+        });
+    }
+}
+
+fn run_file_task(
+    file_task: &FileTask,
+    overall_pb: Option<&ProgressBar>,
+    completed_files: &AtomicUsize,
+    total_files: usize,
+    options: &CopyOptions,
+    hardlink_tracker: Option<&Arc<Mutex<HardLinkTracker>>>,
+    changed_files: &Mutex<Vec<PathBuf>>,
+) -> CopyResult<()> {
+    if let Some(events) = &options.events {
+        events.emit(Event::FileStart {
+            source: path_string(&file_task.source),
+            destination: path_string(&file_task.destination),
+            size: file_task.size,
+        });
+    }
+
+    let result = loop {
+        // Once the destination is full, the remaining tasks are not started at all
+        if options.out_of_space.load(Ordering::Relaxed) {
+            if !options.wait_for_space {
+                break Err(CopyError::Io(io::Error::new(
+                    io::ErrorKind::StorageFull,
+                    "not copied, destination is out of space",
+                )));
+            }
+            if let Err(e) = wait_for_space(&file_task.destination, file_task.size, options) {
+                break Err(e);
+            }
+        }
+
+        match copy_checked(
+            file_task,
+            overall_pb,
+            completed_files,
+            total_files,
+            options,
+            hardlink_tracker,
+            changed_files,
+        ) {
+            Err(e) if is_out_of_space(&e) => {
+                remove_partial(&file_task.destination, overall_pb);
+                options.out_of_space.store(true, Ordering::Relaxed);
+                if !options.wait_for_space {
+                    break Err(e);
+                }
+            }
+            result => break result,
+        }
+    };
+
+    if let Some(events) = &options.events {
+        match &result {
+            Ok(()) => events.emit(Event::FileFinish {
+                source: path_string(&file_task.source),
+                destination: path_string(&file_task.destination),
+                size: file_task.size,
+            }),
+            Err(e) => events.emit(Event::Error {
+                source: Some(path_string(&file_task.source)),
+                destination: Some(path_string(&file_task.destination)),
+                message: e.to_string(),
+            }),
+        }
+    }
+
+    result
+}
+
+/// Remove what was written of a file that didn't fit and take it back off the bar.
+fn remove_partial(destination: &Path, overall_pb: Option<&ProgressBar>) {
+    // Only ever delete regular files; the destination may be a device such as /dev/full
+    let Ok(metadata) = std::fs::symlink_metadata(destination) else {
+        return;
+    };
+    if !metadata.is_file() {
+        return;
+    }
+    if std::fs::remove_file(destination).is_ok()
+        && let Some(pb) = overall_pb
+    {
+        pb.dec(metadata.len());
+    }
+}
+
+/// Block until the destination's filesystem has room for `size` bytes.
+fn wait_for_space(destination: &Path, size: u64, options: &CopyOptions) -> CopyResult<()> {
+    let dir = destination.parent().unwrap_or(Path::new("."));
+    let mut announced = false;
+
+    loop {
+        let available = available_space(dir)?;
+        if available >= size {
+            options.out_of_space.store(false, Ordering::Relaxed);
+            return Ok(());
+        }
+
+        if !announced {
+            let message = format!(
+                "destination is full, waiting for {} of free space ({} available)",
+                HumanBytes(size),
+                HumanBytes(available)
+            );
+            match &options.events {
+                Some(events) => events.emit(Event::Warning {
+                    source: Some(path_string(dir)),
+                    message,
+                    file_type: None,
+                }),
+                None => eprintln!("\n{}: {}", dir.display(), message),
+            }
+            announced = true;
+        }
+
+        // Poll in short steps so Ctrl-C is still honoured while waiting
+        for _ in 0..SPACE_POLL_INTERVAL.as_secs() {
+            if options.abort.load(Ordering::Relaxed) {
+                return Err(CopyError::Io(io::Error::new(
+                    io::ErrorKind::Interrupted,
+                    "Operation aborted by user",
+                )));
+            }
+            std::thread::sleep(Duration::from_secs(1));
+        }
+    }
+}
+
+/// The parts of a source's stat that change when someone writes to it.
+#[derive(Debug, PartialEq)]
+struct SourceStamp {
+    size: u64,
+    mtime: (i64, i64),
+    ctime: (i64, i64),
+}
+
+impl SourceStamp {
+    fn of(path: &Path) -> Option<Self> {
+        let metadata = std::fs::metadata(path).ok()?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+            Some(Self {
+                size: metadata.len(),
+                mtime: (metadata.mtime(), metadata.mtime_nsec()),
+                ctime: (metadata.ctime(), metadata.ctime_nsec()),
+            })
+        }
+        #[cfg(not(unix))]
+        {
+            let mtime = metadata
+                .modified()
+                .ok()
+                .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+                .unwrap_or_default();
+            Some(Self {
+                size: metadata.len(),
+                mtime: (mtime.as_secs() as i64, mtime.subsec_nanos() as i64),
+                ctime: (0, 0),
+            })
+        }
+    }
+}
+
+/// Run `copy_core` and compare the source's stat from before and after, so a file
+/// written to while it was copied is retried or flagged instead of silently torn.
+fn copy_checked(
+    file_task: &FileTask,
+    overall_pb: Option<&ProgressBar>,
+    completed_files: &AtomicUsize,
+    total_files: usize,
+    options: &CopyOptions,
+    hardlink_tracker: Option<&Arc<Mutex<HardLinkTracker>>>,
+    changed_files: &Mutex<Vec<PathBuf>>,
+) -> CopyResult<()> {
+    let attempts = match options.on_source_change {
+        SourceChangeMode::Retry => options.source_change_retries + 1,
+        SourceChangeMode::Warn | SourceChangeMode::Fail => 1,
+    };
+    let mut digest = None;
+
+    for attempt in 0..attempts {
+        let before = SourceStamp::of(&file_task.source);
+
+        if attempt == 0 {
+            digest = copy_core(
+                &file_task.source,
+                &file_task.destination,
+                file_task.size,
+                overall_pb,
+                completed_files,
+                total_files,
+                options,
+                hardlink_tracker,
+            )?;
+        } else {
+            // Only the data is copied again: the destination was already prompted for
+            // and backed up, and progress was counted by the first attempt. The torn
+            // copy is truncated and rewritten in place, so links made to it stay intact.
+            let src_metadata = std::fs::metadata(&file_task.source)?;
+            (_, digest) = stream_data(
+                &file_task.source,
+                &src_metadata,
+                &file_task.destination,
+                file_task.size,
+                None,
+                options,
+            )?;
+            if options.applies_attributes() {
+                preserve::apply_preserve_attrs_with_metadata(
+                    &file_task.source,
+                    &src_metadata,
+                    &file_task.destination,
+                    options.preserve,
+                    &options.preserve_rules,
+                    &options.preserve_policy,
+                )
+                .map_err(CopyError::from)?;
+            }
+        }
+
+        if options.attributes_only
+            || before.is_none()
+            || before == SourceStamp::of(&file_task.source)
+        {
+            record_digest(&file_task.destination, digest, options);
+            return Ok(());
+        }
+    }
+
+    if options.on_source_change == SourceChangeMode::Fail {
+        let _ = std::fs::remove_file(&file_task.destination);
+        return Err(CopyError::SourceModified(vec![file_task.source.clone()]));
+    }
+
+    record_digest(&file_task.destination, digest, options);
+    if let Some(events) = &options.events {
+        events.emit(Event::Warning {
+            source: Some(path_string(&file_task.source)),
+            message: format!("changed during copy ({} attempt(s))", attempts),
+            file_type: None,
+        });
+    }
+    if let Ok(mut changed) = changed_files.lock() {
+        changed.push(file_task.source.clone());
+    }
     Ok(())
 }
 
+/// Add the final copy of a file to the manifest, once all attempts are done.
+fn record_digest(destination: &Path, digest: Option<String>, options: &CopyOptions) {
+    if let (Some(manifest), Some(digest)) = (&options.manifest, digest) {
+        manifest.record(destination, digest);
+    }
+}
+
 #[allow(clippy::too_many_arguments)]
 fn copy_core(
     source: &Path,
@@ -269,20 +1005,29 @@ fn copy_core(
     total_files: usize,
     options: &CopyOptions,
     hardlink_tracker: Option<&Arc<Mutex<HardLinkTracker>>>,
-) -> CopyResult<()> {
+) -> CopyResult<Option<String>> {
     if options.attributes_only {
         if std::fs::symlink_metadata(destination).is_err() {
-            return Ok(());
+            return Ok(None);
         }
-        preserve::apply_preserve_attrs(source, destination, options.preserve)?;
-        return Ok(());
+        preserve::apply_preserve_attrs(
+            source,
+            destination,
+            options.preserve,
+            &options.preserve_rules,
+            &options.preserve_policy,
+        )?;
+        return Ok(None);
     }
 
+    // Taken before any data is read, so the source's access time is still the original
+    let src_metadata = std::fs::metadata(source)?;
+
     if options.interactive
         && destination.try_exists().unwrap_or(false)
         && !prompt_overwrite(destination)?
     {
-        return Ok(());
+        return Ok(None);
     }
 
     if let Some(backup_mode) = options.backup
@@ -305,17 +1050,60 @@ fn copy_core(
 
         if tracker_guard.track_and_create_link(source, destination)? {
             // Hard link was created, no need to copy file content
+            let digest = options
+                .manifest
+                .as_ref()
+                .map(|manifest| hash_file(destination, manifest.algorithm))
+                .transpose()?;
             update_progress(overall_pb, completed_files, total_files, options);
-            if options.preserve != PreserveAttr::none() {
-                preserve::apply_preserve_attrs(source, destination, options.preserve)
-                    .map_err(CopyError::from)?;
+            if options.applies_attributes() {
+                preserve::apply_preserve_attrs_with_metadata(
+                    source,
+                    &src_metadata,
+                    destination,
+                    options.preserve,
+                    &options.preserve_rules,
+                    &options.preserve_policy,
+                )
+                .map_err(CopyError::from)?;
             }
-            return Ok(());
+            return Ok(digest);
         }
         // Continue with normal file copy if this is the first file in the inode group
     }
 
-    if let Some(reflink_mode) = options.reflink {
+    transfer_data(
+        source,
+        &src_metadata,
+        destination,
+        file_size,
+        overall_pb,
+        completed_files,
+        total_files,
+        options,
+    )
+}
+
+/// Write the data of `source` to `destination` and apply the preserved attributes.
+/// The destination has already been prompted for, backed up or removed by `copy_core`.
+#[allow(clippy::too_many_arguments)]
+fn transfer_data(
+    source: &Path,
+    src_metadata: &std::fs::Metadata,
+    destination: &Path,
+    file_size: u64,
+    overall_pb: Option<&ProgressBar>,
+    completed_files: &AtomicUsize,
+    total_files: usize,
+    options: &CopyOptions,
+) -> CopyResult<Option<String>> {
+    // In-kernel copies never pass the data through cpx, so files that go into a manifest
+    // take the read loop below and are hashed while they are copied
+    let in_kernel = options.manifest.is_none();
+
+    if let Some(reflink_mode) = options.reflink
+        && in_kernel
+    {
         use crate::cli::args::ReflinkMode;
         if reflink_mode != ReflinkMode::Never {
             if destination.try_exists().unwrap_or(false) {
@@ -327,15 +1115,36 @@ fn copy_core(
 
             match reflink_copy::reflink(source, destination) {
                 Ok(()) => {
+                    // The clone is always a new file, created without the source's mode
+                    #[cfg(unix)]
+                    if !options.preserve.mode {
+                        use std::os::unix::fs::PermissionsExt;
+                        let mode = permission_bits(src_metadata) & !options.umask;
+                        std::fs::set_permissions(
+                            destination,
+                            std::fs::Permissions::from_mode(mode),
+                        )?;
+                    }
+                    let copied = std::fs::metadata(destination)
+                        .map(|m| m.len())
+                        .unwrap_or(file_size);
                     if let Some(pb) = overall_pb {
-                        pb.inc(file_size);
+                        pb.inc(copied);
                     }
+                    reconcile_size(source, file_size, copied, overall_pb, options);
                     update_progress(overall_pb, completed_files, total_files, options);
-                    if options.preserve != PreserveAttr::none() {
-                        preserve::apply_preserve_attrs(source, destination, options.preserve)
-                            .map_err(CopyError::from)?;
+                    if options.applies_attributes() {
+                        preserve::apply_preserve_attrs_with_metadata(
+                            source,
+                            src_metadata,
+                            destination,
+                            options.preserve,
+                            &options.preserve_rules,
+                            &options.preserve_policy,
+                        )
+                        .map_err(CopyError::from)?;
                     }
-                    return Ok(());
+                    return Ok(None);
                 }
                 Err(_e) if reflink_mode == ReflinkMode::Always => {
                     return Err(CopyError::ReflinkFailed {
@@ -349,29 +1158,74 @@ fn copy_core(
     }
 
     #[cfg(target_os = "linux")]
-    {
+    if in_kernel {
         if options.abort.load(Ordering::Relaxed) {
             return Err(CopyError::Io(io::Error::new(
                 io::ErrorKind::Interrupted,
                 "Operation aborted by user",
             )));
         }
-        if let Ok(true) = fast_copy(source, destination, file_size, overall_pb, options) {
+        if let Ok(Some(copied)) = fast_copy(source, destination, file_size, overall_pb, options) {
+            reconcile_size(source, file_size, copied, overall_pb, options);
             update_progress(overall_pb, completed_files, total_files, options);
-            if options.preserve != PreserveAttr::none() {
-                preserve::apply_preserve_attrs(source, destination, options.preserve)
-                    .map_err(CopyError::from)?;
+            if options.applies_attributes() {
+                preserve::apply_preserve_attrs_with_metadata(
+                    source,
+                    src_metadata,
+                    destination,
+                    options.preserve,
+                    &options.preserve_rules,
+                    &options.preserve_policy,
+                )
+                .map_err(CopyError::from)?;
             }
-            return Ok(());
+            return Ok(None);
         }
     }
 
+    let (copied, digest) = stream_data(
+        source,
+        src_metadata,
+        destination,
+        file_size,
+        overall_pb,
+        options,
+    )?;
+    reconcile_size(source, file_size, copied, overall_pb, options);
+    update_progress(overall_pb, completed_files, total_files, options);
+
+    if options.applies_attributes() {
+        preserve::apply_preserve_attrs_with_metadata(
+            source,
+            src_metadata,
+            destination,
+            options.preserve,
+            &options.preserve_rules,
+            &options.preserve_policy,
+        )
+        .map_err(CopyError::from)?;
+    }
+
+    Ok(digest)
+}
+
+/// Copy `source` through a userspace buffer into `destination`, which is opened with
+/// truncation so an existing file keeps its inode. Returns the number of bytes copied
+/// and, with `--manifest`, the digest of the data.
+fn stream_data(
+    source: &Path,
+    src_metadata: &std::fs::Metadata,
+    destination: &Path,
+    file_size: u64,
+    overall_pb: Option<&ProgressBar>,
+    options: &CopyOptions,
+) -> CopyResult<(u64, Option<String>)> {
     let mut src_file = std::fs::File::open(source)?;
-    let dest_file = match std::fs::File::create(destination) {
+    let dest_file = match create_destination(destination, src_metadata) {
         Ok(file) => file,
         Err(_e) if options.force => {
             let _ = std::fs::remove_file(destination);
-            std::fs::File::create(destination)?
+            create_destination(destination, src_metadata)?
         }
         Err(e) => return Err(CopyError::Io(e)),
     };
@@ -399,20 +1253,17 @@ fn copy_core(
     };
 
     let mut accumulated_bytes = 0u64;
+    let mut total_copied = 0u64;
+    let mut hasher = options
+        .manifest
+        .as_ref()
+        .map(|manifest| StreamHasher::new(manifest.algorithm));
 
     loop {
         if options.abort.load(Ordering::Relaxed) {
             dest_file.flush()?;
             drop(dest_file);
-            if let Err(e) = std::fs::remove_file(destination) {
-                eprintln!(
-                    "Could not remove incomplete file {}: {}",
-                    destination.display(),
-                    e
-                );
-            } else {
-                eprintln!("Cleaned up incomplete file: {}", destination.display());
-            }
+            remove_incomplete(destination, options);
 
             return Err(CopyError::Io(io::Error::new(
                 io::ErrorKind::Interrupted,
@@ -425,8 +1276,12 @@ fn copy_core(
             break;
         }
         dest_file.write_all(&buffer[..bytes_read])?;
+        if let Some(hasher) = hasher.as_mut() {
+            hasher.update(&buffer[..bytes_read]);
+        }
 
         accumulated_bytes += bytes_read as u64;
+        total_copied += bytes_read as u64;
         if accumulated_bytes >= update_threshold {
             if let Some(pb) = overall_pb {
                 pb.inc(accumulated_bytes);
@@ -443,14 +1298,64 @@ fn copy_core(
 
     dest_file.flush()?;
 
-    update_progress(overall_pb, completed_files, total_files, options);
+    Ok((total_copied, hasher.map(StreamHasher::finalize_hex)))
+}
+
+/// Sources are copied until EOF, so the planned size can be off for growing files
+/// or pseudo-filesystem entries; keep the bar's total in line with what was copied.
+fn reconcile_size(
+    source: &Path,
+    planned: u64,
+    copied: u64,
+    overall_pb: Option<&ProgressBar>,
+    options: &CopyOptions,
+) {
+    if planned == copied {
+        return;
+    }
+
+    if let Some(pb) = overall_pb {
+        if copied > planned {
+            pb.inc_length(copied - planned);
+        } else {
+            pb.dec_length(planned - copied);
+        }
+    }
 
-    if options.preserve != PreserveAttr::none() {
-        preserve::apply_preserve_attrs(source, destination, options.preserve)
-            .map_err(CopyError::from)?;
+    let message = format!(
+        "size changed during copy (expected {} bytes, copied {} bytes)",
+        planned, copied
+    );
+    match &options.events {
+        Some(events) => events.emit(Event::Warning {
+            source: Some(path_string(source)),
+            message,
+            file_type: None,
+        }),
+        None => eprintln!("Warning: {}: {}", source.display(), message),
     }
+}
 
-    Ok(())
+/// Delete a file whose copy was aborted, saying whether that worked.
+pub(crate) fn remove_incomplete(destination: &Path, options: &CopyOptions) {
+    let removed = std::fs::remove_file(destination);
+    match (&options.events, removed) {
+        (Some(events), Err(e)) => events.emit(Event::Warning {
+            source: Some(path_string(destination)),
+            message: format!("could not remove incomplete file: {}", e),
+            file_type: None,
+        }),
+        (Some(events), Ok(())) => events.emit(Event::Info {
+            source: Some(path_string(destination)),
+            message: "cleaned up incomplete file".to_string(),
+        }),
+        (None, Err(e)) => eprintln!(
+            "Could not remove incomplete file {}: {}",
+            destination.display(),
+            e
+        ),
+        (None, Ok(())) => eprintln!("Cleaned up incomplete file: {}", destination.display()),
+    }
 }
 
 fn update_progress(
@@ -470,6 +1375,8 @@ fn update_progress(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::cli::args::{HashCacheMode, SpecialMode};
+    use crate::utility::preserve::{PreserveAttr, PreservePolicy, PreserveRules};
     use crate::utility::progress_bar::ProgressOptions;
     use std::fs;
     use std::sync::atomic::AtomicBool;
@@ -478,21 +1385,39 @@ mod tests {
         CopyOptions {
             recursive: false,
             resume: false,
+            hash_cache: HashCacheMode::None,
             force: false,
             interactive: false,
             preserve: PreserveAttr::none(),
+            preserve_rules: Arc::new(PreserveRules::default()),
+            preserve_policy: Arc::new(PreservePolicy::default()),
             backup: None,
             symbolic_link: None,
             hard_link: false,
             follow_symlink: FollowSymlink::NoDereference,
             attributes_only: false,
             remove_destination: false,
+            special: SpecialMode::Recreate,
+            on_source_change: SourceChangeMode::Warn,
+            source_change_retries: 3,
+            wait_for_space: false,
             reflink: None,
             parents: false,
+            parents_base: None,
             parallel: 1,
             exclude_rules: None,
+            filter_expr: None,
+            exclude_nodump: false,
+            exclusion_markers: Default::default(),
+            ignore_files: Vec::new(),
+            explain_excludes: false,
+            manifest: None,
+            metadata_sidecar: None,
+            events: None,
             progress_bar: ProgressOptions::default(),
             abort: Arc::new(AtomicBool::new(false)),
+            out_of_space: Arc::new(AtomicBool::new(false)),
+            umask: 0o022,
         }
     }
 
@@ -591,6 +1516,76 @@ mod tests {
         assert!(diff.as_secs() < 1);
     }
 
+    #[test]
+    fn test_copy_core_reads_past_planned_size() {
+        let temp_dir = TempDir::new().unwrap();
+        let source = temp_dir.path().join("growing.log");
+        let dest = temp_dir.path().join("dest.log");
+        fs::write(&source, b"written after the scan").unwrap();
+
+        let pb = ProgressBar::hidden();
+        pb.set_length(7);
+        let completed = AtomicUsize::new(0);
+        let options = default_copy_options();
+
+        copy_core(&source, &dest, 7, Some(&pb), &completed, 1, &options, None).unwrap();
+
+        assert_eq!(fs::read(&dest).unwrap(), b"written after the scan");
+        assert_eq!(pb.length(), Some(22));
+        assert_eq!(pb.position(), 22);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_interrupted_copy_keeps_directories_writable() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = TempDir::new().unwrap();
+        let source_dir = temp_dir.path().join("source");
+        let dest_dir = temp_dir.path().join("dest");
+        fs::create_dir(&source_dir).unwrap();
+        fs::write(source_dir.join("file.txt"), b"content").unwrap();
+        fs::set_permissions(&source_dir, fs::Permissions::from_mode(0o555)).unwrap();
+
+        let mut options = default_copy_options();
+        options.recursive = true;
+        options.preserve.mode = true;
+        options.abort.store(true, Ordering::Relaxed);
+
+        assert!(copy(&source_dir, &dest_dir, &options).is_err());
+        let mode = fs::metadata(dest_dir.join("source"))
+            .unwrap()
+            .permissions()
+            .mode();
+        fs::set_permissions(&source_dir, fs::Permissions::from_mode(0o755)).unwrap();
+        assert_eq!(mode & 0o700, 0o700);
+    }
+
+    #[test]
+    fn test_unpreserved_message() {
+        assert_eq!(
+            unpreserved_message("ownership", 1204),
+            "ownership not preserved on 1,204 files"
+        );
+        assert_eq!(
+            unpreserved_message("xattr", 1),
+            "xattr not preserved on 1 file"
+        );
+    }
+
+    #[test]
+    fn test_source_stamp_detects_writes() {
+        let temp_dir = TempDir::new().unwrap();
+        let source = temp_dir.path().join("source.txt");
+        fs::write(&source, b"before").unwrap();
+
+        let before = SourceStamp::of(&source).unwrap();
+        assert_eq!(Some(&before), SourceStamp::of(&source).as_ref());
+
+        fs::write(&source, b"after, and longer").unwrap();
+        assert_ne!(Some(before), SourceStamp::of(&source));
+    }
+
     #[test]
     fn test_multiple_copy() {
         let temp_dir = TempDir::new().unwrap();
@@ -1,23 +1,30 @@
 use crate::cli::args::CopyOptions;
+use crate::core::copy::remove_incomplete;
 use crate::error::{CopyError, CopyResult};
+use crate::utility::helper::create_destination;
 use indicatif::ProgressBar;
 use nix::fcntl::copy_file_range;
 use std::io;
 use std::path::Path;
 use std::sync::atomic::Ordering;
 
+/// Copy with `copy_file_range` until the source reports EOF.
+///
+/// Returns the number of bytes copied, or `None` when the caller should fall back
+/// to a userspace read loop.
 pub fn fast_copy(
     source: &Path,
     destination: &Path,
     file_size: u64,
     overall_pb: Option<&ProgressBar>,
     options: &CopyOptions,
-) -> CopyResult<bool> {
+) -> CopyResult<Option<u64>> {
     let src_file = std::fs::File::open(source).map_err(|e| CopyError::CopyFailed {
         source: source.to_path_buf(),
         destination: destination.to_path_buf(),
         reason: format!("Failed to open source file: {}", e),
     })?;
+    let src_metadata = src_file.metadata()?;
     if options.remove_destination {
         let exists = std::fs::exists(destination).unwrap_or(false);
 
@@ -29,7 +36,7 @@ pub fn fast_copy(
             })?;
         }
     }
-    let dest_file = match std::fs::File::create(destination) {
+    let dest_file = match create_destination(destination, &src_metadata) {
         Ok(file) => file,
         Err(_e) if options.force => {
             let _ = std::fs::remove_file(destination).map_err(|e| CopyError::CopyFailed {
@@ -37,7 +44,7 @@ pub fn fast_copy(
                 destination: destination.to_path_buf(),
                 reason: format!("Failed to remove destination: {}", e),
             });
-            std::fs::File::create(destination).map_err(|e| CopyError::CopyFailed {
+            create_destination(destination, &src_metadata).map_err(|e| CopyError::CopyFailed {
                 source: source.to_path_buf(),
                 destination: destination.to_path_buf(),
                 reason: format!("Failed to create destination: {}", e),
@@ -52,26 +59,17 @@ pub fn fast_copy(
     loop {
         if options.abort.load(Ordering::Relaxed) {
             drop(dest_file); // Close file
-            if let Err(e) = std::fs::remove_file(destination) {
-                eprintln!(
-                    "Could not remove incomplete file {}: {}",
-                    destination.display(),
-                    e
-                );
-            } else {
-                eprintln!("Cleaned up incomplete file: {}", destination.display());
-            }
+            remove_incomplete(destination, options);
             return Err(CopyError::Io(io::Error::new(
                 io::ErrorKind::Interrupted,
                 "Operation aborted by user",
             )));
         }
 
-        let to_copy = std::cmp::min(chunk_size, (file_size - total_copied) as usize);
-        if to_copy == 0 {
-            break;
-        }
-        match copy_file_range(&src_file, None, &dest_file, None, to_copy) {
+        // The planned size is only a hint: files can grow while being copied
+        match copy_file_range(&src_file, None, &dest_file, None, chunk_size) {
+            // Pseudo-filesystems such as /proc report a size of 0 and return no data here
+            Ok(0) if total_copied == 0 => return Ok(None),
             Ok(0) => break,
             Ok(copied) => {
                 total_copied += copied as u64;
@@ -80,9 +78,13 @@ pub fn fast_copy(
                 }
             }
             Err(_) => {
-                return Ok(false);
+                // The read loop starts over, so take back what was already counted
+                if let Some(pb) = overall_pb {
+                    pb.dec(total_copied);
+                }
+                return Ok(None);
             }
         }
     }
-    Ok(true)
+    Ok(Some(total_copied))
 }
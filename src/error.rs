@@ -46,12 +46,16 @@ pub enum CopyError {
         destination: PathBuf,
     },
     PreserveFailed(PreserveError),
+    SourceChanged(Vec<PathBuf>),
+    /// Sources that changed with `--on-source-change=fail`; their copies were removed
+    SourceModified(Vec<PathBuf>),
 }
 
 #[derive(Debug)]
 pub enum ExcludeError {
     InvalidPattern(String),
     PatternCompilation(globset::Error),
+    InvalidExpression(String),
 }
 
 #[derive(Debug)]
@@ -143,6 +147,22 @@ impl fmt::Display for CopyError {
                 )
             }
             CopyError::PreserveFailed(e) => write!(f, "Preserve failed: {}", e),
+            CopyError::SourceChanged(paths) => match paths.as_slice() {
+                [path] => write!(f, "Source changed during copy: {}", path.display()),
+                _ => write!(f, "{} source files changed during copy", paths.len()),
+            },
+            CopyError::SourceModified(paths) => match paths.as_slice() {
+                [path] => write!(
+                    f,
+                    "Source changed during copy, not copied: {}",
+                    path.display()
+                ),
+                _ => write!(
+                    f,
+                    "{} source files changed during copy and were not copied",
+                    paths.len()
+                ),
+            },
         }
     }
 }
@@ -154,6 +174,9 @@ impl fmt::Display for ExcludeError {
                 write!(f, "Invalid exclude pattern: {}", pattern)
             }
             ExcludeError::PatternCompilation(e) => write!(f, "Pattern compilation error: {}", e),
+            ExcludeError::InvalidExpression(message) => {
+                write!(f, "Invalid filter expression: {}", message)
+            }
         }
     }
 }
@@ -277,6 +300,8 @@ impl CopyError {
             CopyError::HardlinkFailed { .. } => io::ErrorKind::Other,
             CopyError::SymlinkFailed { .. } => io::ErrorKind::Other,
             CopyError::PreserveFailed(_) => io::ErrorKind::Other,
+            CopyError::SourceChanged(_) => io::ErrorKind::Other,
+            CopyError::SourceModified(_) => io::ErrorKind::Other,
         }
     }
 }
@@ -1,6 +1,6 @@
 use cpx::cli::args::CLIArgs;
 use cpx::core::copy::{copy, multiple_copy};
-use cpx::error::CpxError;
+use cpx::error::{CopyError, CpxError};
 use signal_hook::consts::signal::*;
 use signal_hook::iterator::Signals;
 use std::process;
@@ -61,6 +61,12 @@ fn main() {
                 eprintln!("Resume with: cpx --resume [original command]");
                 eprintln!("Completed files will be skipped automatically");
                 process::exit(130); // SIGINT
+            } else if let CopyError::SourceChanged(_) = e {
+                // The copy finished; the changed files were listed in the summary
+                process::exit(3);
+            } else if let CopyError::SourceModified(_) = e {
+                eprintln!("Error copying file: {}", e);
+                process::exit(4);
             } else {
                 eprintln!("Error copying file: {}", e);
                 process::exit(1);
@@ -0,0 +1,185 @@
+/// Which entries a `--chmod` rule applies to: `D` and `F` prefixes narrow it down.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Target {
+    All,
+    Files,
+    Directories,
+}
+
+#[derive(Debug, Clone)]
+enum Action {
+    /// Octal mode, replacing all permission bits
+    Set(u32),
+    /// chmod(1) symbolic clause such as `g-w` or `a+rX`
+    Symbolic { who: u32, ops: Vec<(char, Perms)> },
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Perms {
+    bits: u32,
+    /// `X`: execute only for directories and files that already have an execute bit
+    conditional_x: bool,
+}
+
+#[derive(Debug, Clone)]
+struct ChmodRule {
+    target: Target,
+    action: Action,
+}
+
+/// rsync-style permission rewrite rules, e.g. `D755,F644,g-w`, applied in order.
+#[derive(Debug, Clone)]
+pub struct ChmodRules {
+    rules: Vec<ChmodRule>,
+}
+
+impl ChmodRules {
+    /// Parse rule lists, each of which may hold several comma-separated rules.
+    pub fn parse(specs: &[String]) -> Result<Self, String> {
+        let mut rules = Vec::new();
+        for spec in specs {
+            for rule in spec.split(',').map(str::trim).filter(|r| !r.is_empty()) {
+                rules.push(
+                    parse_rule(rule).ok_or_else(|| format!("invalid chmod rule '{}'", rule))?,
+                );
+            }
+        }
+        Ok(Self { rules })
+    }
+
+    /// The permission bits (`0o7777`) an entry with `mode` ends up with.
+    pub fn apply(&self, mode: u32, is_dir: bool) -> u32 {
+        let mut mode = mode & 0o7777;
+        for rule in &self.rules {
+            let applies = match rule.target {
+                Target::All => true,
+                Target::Files => !is_dir,
+                Target::Directories => is_dir,
+            };
+            if !applies {
+                continue;
+            }
+            match &rule.action {
+                Action::Set(bits) => mode = *bits,
+                Action::Symbolic { who, ops } => {
+                    for (op, perms) in ops {
+                        let mut bits = perms.bits;
+                        if perms.conditional_x && (is_dir || mode & 0o111 != 0) {
+                            bits |= 0o111;
+                        }
+                        let bits = bits & who;
+                        mode = match op {
+                            '+' => mode | bits,
+                            '-' => mode & !bits,
+                            _ => (mode & !who) | bits,
+                        };
+                    }
+                }
+            }
+        }
+        mode
+    }
+}
+
+fn parse_rule(rule: &str) -> Option<ChmodRule> {
+    let (target, rest) = match rule.as_bytes().first()? {
+        b'D' => (Target::Directories, &rule[1..]),
+        b'F' => (Target::Files, &rule[1..]),
+        _ => (Target::All, rule),
+    };
+
+    if !rest.is_empty() && rest.bytes().all(|b| (b'0'..=b'7').contains(&b)) {
+        let bits = u32::from_str_radix(rest, 8)
+            .ok()
+            .filter(|bits| *bits <= 0o7777)?;
+        return Some(ChmodRule {
+            target,
+            action: Action::Set(bits),
+        });
+    }
+
+    let who_len = rest.find(['+', '-', '='])?;
+    let mut who = 0;
+    for c in rest[..who_len].chars() {
+        who |= match c {
+            'u' => 0o4700,
+            'g' => 0o2070,
+            'o' => 0o1007,
+            'a' => 0o7777,
+            _ => return None,
+        };
+    }
+    // Without a who, the rule covers everyone; unlike chmod(1), the umask is not consulted
+    if who == 0 {
+        who = 0o7777;
+    }
+
+    let mut ops = Vec::new();
+    for c in rest[who_len..].chars() {
+        match c {
+            '+' | '-' | '=' => ops.push((c, Perms::default())),
+            _ => {
+                let (_, perms) = ops.last_mut()?;
+                match c {
+                    'r' => perms.bits |= 0o444,
+                    'w' => perms.bits |= 0o222,
+                    'x' => perms.bits |= 0o111,
+                    'X' => perms.conditional_x = true,
+                    's' => perms.bits |= 0o6000,
+                    't' => perms.bits |= 0o1000,
+                    _ => return None,
+                }
+            }
+        }
+    }
+
+    Some(ChmodRule {
+        target,
+        action: Action::Symbolic { who, ops },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(spec: &str) -> ChmodRules {
+        ChmodRules::parse(&[spec.to_string()]).unwrap()
+    }
+
+    #[test]
+    fn test_file_and_directory_selectors() {
+        let rules = rules("D755,F644");
+        assert_eq!(rules.apply(0o700, true), 0o755);
+        assert_eq!(rules.apply(0o777, false), 0o644);
+    }
+
+    #[test]
+    fn test_symbolic_rules() {
+        assert_eq!(rules("g-w").apply(0o775, false), 0o755);
+        assert_eq!(rules("o=r").apply(0o777, false), 0o774);
+        assert_eq!(rules("u+x,go-rwx").apply(0o644, false), 0o700);
+        assert_eq!(rules("+t").apply(0o777, true), 0o1777);
+        assert_eq!(rules("ug+s").apply(0o755, false), 0o6755);
+    }
+
+    #[test]
+    fn test_conditional_execute() {
+        let rules = rules("a+rX");
+        assert_eq!(rules.apply(0o600, false), 0o644);
+        assert_eq!(rules.apply(0o700, false), 0o755);
+        assert_eq!(rules.apply(0o700, true), 0o755);
+    }
+
+    #[test]
+    fn test_rules_apply_in_order() {
+        assert_eq!(rules("F644,g-w,Fg+w").apply(0o600, false), 0o664);
+    }
+
+    #[test]
+    fn test_invalid_rules() {
+        for spec in ["D", "F9", "z+w", "u+q", "g", "77777"] {
+            assert!(ChmodRules::parse(&[spec.to_string()]).is_err(), "{}", spec);
+        }
+    }
+}
@@ -0,0 +1,306 @@
+use indicatif::ProgressBar;
+use serde::Serialize;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// Bumped whenever an event is removed or a field changes meaning.
+/// Adding new events or fields is not a breaking change.
+pub const EVENT_SCHEMA_VERSION: u32 = 1;
+
+/// Interval between `progress` events while files are being copied.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Number of walked entries between two `scan` events.
+pub const SCAN_EVENT_INTERVAL: u64 = 1000;
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    Start {
+        version: &'static str,
+    },
+    Scan {
+        entries: u64,
+        files: usize,
+        bytes: u64,
+    },
+    ScanComplete {
+        files: usize,
+        bytes: u64,
+        directories: usize,
+        symlinks: usize,
+        skipped: usize,
+    },
+    FileStart {
+        source: String,
+        destination: String,
+        size: u64,
+    },
+    FileFinish {
+        source: String,
+        destination: String,
+        size: u64,
+    },
+    Progress {
+        bytes: u64,
+        total_bytes: u64,
+        files: usize,
+        total_files: usize,
+    },
+    Info {
+        source: Option<String>,
+        message: String,
+    },
+    Warning {
+        source: Option<String>,
+        message: String,
+        /// Set for special files that were left out, e.g. `fifo` or `block_device`
+        #[serde(skip_serializing_if = "Option::is_none")]
+        file_type: Option<&'static str>,
+    },
+    Error {
+        source: Option<String>,
+        destination: Option<String>,
+        message: String,
+    },
+    Summary {
+        files: usize,
+        bytes: u64,
+        failed: usize,
+        skipped: usize,
+        changed: usize,
+        pruned_directories: usize,
+        elapsed_ms: u64,
+    },
+}
+
+#[derive(Serialize)]
+struct Envelope<'a> {
+    schema: u32,
+    elapsed_ms: u64,
+    #[serde(flatten)]
+    event: &'a Event,
+}
+
+/// Writes newline-delimited JSON events for `--progress=json`.
+pub struct EventEmitter {
+    writer: Mutex<Box<dyn Write + Send>>,
+    started: Instant,
+}
+
+impl fmt::Debug for EventEmitter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EventEmitter")
+            .field("started", &self.started)
+            .finish_non_exhaustive()
+    }
+}
+
+impl EventEmitter {
+    pub fn new(writer: Box<dyn Write + Send>) -> Self {
+        Self {
+            writer: Mutex::new(writer),
+            started: Instant::now(),
+        }
+    }
+
+    pub fn stderr() -> Self {
+        Self::new(Box::new(io::stderr()))
+    }
+
+    /// Emit events to an already open file descriptor (`--progress-fd N`).
+    #[cfg(unix)]
+    pub fn from_fd(fd: i32) -> io::Result<Self> {
+        use std::os::unix::io::BorrowedFd;
+
+        if fd < 0 || unsafe { libc::fcntl(fd, libc::F_GETFD) } == -1 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("file descriptor {} is not open", fd),
+            ));
+        }
+        // The descriptor belongs to the parent process; write to a duplicate so dropping
+        // the emitter closes only that
+        let borrowed = unsafe { BorrowedFd::borrow_raw(fd) };
+        let file = File::from(borrowed.try_clone_to_owned()?);
+        Ok(Self::new(Box::new(file)))
+    }
+
+    #[cfg(not(unix))]
+    pub fn from_fd(_fd: i32) -> io::Result<Self> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "--progress-fd is only supported on Unix",
+        ))
+    }
+
+    pub fn elapsed_ms(&self) -> u64 {
+        self.started.elapsed().as_millis() as u64
+    }
+
+    pub fn emit(&self, event: Event) {
+        let envelope = Envelope {
+            schema: EVENT_SCHEMA_VERSION,
+            elapsed_ms: self.elapsed_ms(),
+            event: &event,
+        };
+        let Ok(mut line) = serde_json::to_string(&envelope) else {
+            return;
+        };
+        line.push('\n');
+
+        // A closed pipe on the consumer side must never abort the copy
+        if let Ok(mut writer) = self.writer.lock() {
+            let _ = writer.write_all(line.as_bytes());
+            let _ = writer.flush();
+        }
+    }
+
+    /// Periodically report the byte counter of `pb` until the ticker is stopped.
+    pub fn spawn_ticker(
+        self: &Arc<Self>,
+        pb: ProgressBar,
+        completed_files: Arc<AtomicUsize>,
+        total_files: usize,
+    ) -> ProgressTicker {
+        let stop = Arc::new(AtomicBool::new(false));
+        let handle = std::thread::spawn({
+            let emitter = self.clone();
+            let stop = stop.clone();
+            move || {
+                while !stop.load(Ordering::Relaxed) {
+                    std::thread::sleep(PROGRESS_INTERVAL);
+                    emitter.emit(Event::Progress {
+                        bytes: pb.position(),
+                        total_bytes: pb.length().unwrap_or(0),
+                        files: completed_files.load(Ordering::Relaxed),
+                        total_files,
+                    });
+                }
+            }
+        });
+        ProgressTicker {
+            stop,
+            handle: Some(handle),
+        }
+    }
+}
+
+pub struct ProgressTicker {
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl ProgressTicker {
+    pub fn stop(mut self) {
+        self.shutdown();
+    }
+
+    fn shutdown(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Drop for ProgressTicker {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+pub fn path_string(path: &Path) -> String {
+    path.to_string_lossy().into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_emit_writes_versioned_json_lines() {
+        let buffer = SharedBuffer::default();
+        let emitter = EventEmitter::new(Box::new(buffer.clone()));
+
+        emitter.emit(Event::Start { version: "0.0.0" });
+        emitter.emit(Event::FileStart {
+            source: "a.txt".to_string(),
+            destination: "b.txt".to_string(),
+            size: 3,
+        });
+
+        let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 2);
+
+        let first: serde_json::Value = serde_json::from_str(lines[0]).unwrap();
+        assert_eq!(first["schema"], EVENT_SCHEMA_VERSION);
+        assert_eq!(first["event"], "start");
+
+        let second: serde_json::Value = serde_json::from_str(lines[1]).unwrap();
+        assert_eq!(second["event"], "file_start");
+        assert_eq!(second["source"], "a.txt");
+        assert_eq!(second["size"], 3);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_from_fd_leaves_descriptor_open() {
+        use std::io::{Read, Seek};
+        use std::os::unix::io::AsRawFd;
+
+        let mut file = tempfile::tempfile().unwrap();
+        let emitter = EventEmitter::from_fd(file.as_raw_fd()).unwrap();
+        emitter.emit(Event::Start { version: "0.0.0" });
+        drop(emitter);
+
+        // Still usable by its owner after the emitter is gone
+        file.write_all(b"after\n").unwrap();
+        file.rewind().unwrap();
+        let mut contents = String::new();
+        file.read_to_string(&mut contents).unwrap();
+        assert!(contents.contains("\"event\":\"start\""));
+        assert!(contents.ends_with("after\n"));
+    }
+
+    #[test]
+    fn test_ticker_reports_progress() {
+        let buffer = SharedBuffer::default();
+        let emitter = Arc::new(EventEmitter::new(Box::new(buffer.clone())));
+        let pb = ProgressBar::hidden();
+        pb.set_length(10);
+        pb.inc(4);
+
+        let ticker = emitter.spawn_ticker(pb, Arc::new(AtomicUsize::new(1)), 2);
+        std::thread::sleep(PROGRESS_INTERVAL * 2);
+        ticker.stop();
+
+        let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let progress: serde_json::Value =
+            serde_json::from_str(output.lines().next().unwrap()).unwrap();
+        assert_eq!(progress["event"], "progress");
+        assert_eq!(progress["bytes"], 4);
+        assert_eq!(progress["total_bytes"], 10);
+        assert_eq!(progress["total_files"], 2);
+    }
+}
@@ -1,44 +1,152 @@
 use crate::error::{ExcludeError, ExcludeResult};
-use globset::{Glob, GlobSet, GlobSetBuilder};
+use ignore::Match;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use std::fmt;
+use std::io::Read;
 use std::path::Component;
-use std::{
-    borrow::Cow,
-    collections::HashSet,
-    path::{Path, PathBuf},
-};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 #[derive(Debug, Clone)]
 pub struct ExcludeRules {
-    pub absolute_paths: Vec<PathBuf>,
-    pub basenames: HashSet<String>,
-    pub glob_set: Option<GlobSet>,
+    /// Canonical excluded paths, longest first, with the pattern each came from
+    pub absolute_paths: Vec<(PathBuf, RulePattern)>,
+    /// Include and exclude rules in order; the first one matching a path decides
+    pub rules: Vec<FilterRule>,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FilterKind {
+    Include,
+    Exclude,
+}
+
+/// A run of consecutive include or exclude patterns, matched with gitignore semantics
+/// against paths relative to the source root: a later `!pattern` carves out an
+/// exception, and a matching directory also matches everything inside it.
+#[derive(Debug, Clone)]
+pub struct FilterRule {
+    pub kind: FilterKind,
+    matcher: Gitignore,
+    /// The patterns of the run, in the order they were added to `matcher`
+    patterns: Vec<RulePattern>,
+}
+
+/// Include or exclude patterns that came from the same place.
+pub struct FilterGroup {
+    pub kind: FilterKind,
+    pub origin: RuleOrigin,
+    pub patterns: Vec<ExcludePattern>,
+}
+
+/// Where a pattern was given.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RuleOrigin {
+    /// A command-line option, e.g. `--exclude`
+    Flag(&'static str),
+    /// A line of an `--exclude-from` file
+    PatternFile { path: PathBuf, line: usize },
+    /// A `[filter]` key of the config file; `None` when the file isn't known
+    Config {
+        path: Option<PathBuf>,
+        key: &'static str,
+    },
+    /// A built-in exclude preset
+    Preset(String),
+    /// A line of a per-directory ignore file such as `.gitignore`
+    IgnoreFile { path: PathBuf, line: usize },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PatternKind {
+    AbsolutePath,
+    BaseName,
+    Glob,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RulePattern {
+    /// The pattern as written
+    pub text: String,
+    pub kind: PatternKind,
+    pub origin: RuleOrigin,
+}
+
+/// Why a path is or isn't copied, for `--explain-excludes` and `cpx check-ignore`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatchReason {
+    pub verdict: Verdict,
+    pub cause: MatchCause,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MatchCause {
+    Pattern(RulePattern),
+    /// The directory holding this exclusion marker file is skipped
+    Marker(PathBuf),
+    /// This path has the nodump flag
+    Nodump(PathBuf),
+    /// The `--filter` expression doesn't match
+    Expression,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Verdict {
+    Include,
+    Exclude,
+    /// An excluded directory that is walked anyway, because an earlier include rule
+    /// may match something inside; it is only created if something does
+    Traverse,
+}
+
+#[derive(Debug, Clone)]
 pub enum ExcludePattern {
     AbsolutePath(PathBuf),
     BaseName(String),
     GlobPattern(String),
 }
 
+/// Prefix marking a pattern as a filesystem path rather than a gitignore pattern.
+pub const PATH_PREFIX: &str = "path:";
+
 impl ExcludePattern {
+    /// `path:/some/dir` excludes that path as such; anything else, including
+    /// `/build`-style root-anchored patterns, is a gitignore pattern.
     pub fn from_string(pattern: &str) -> Self {
         let trimmed = pattern.trim();
-        if Path::new(trimmed).is_absolute() {
-            return ExcludePattern::AbsolutePath(PathBuf::from(trimmed));
+        if let Some(path) = trimmed.strip_prefix(PATH_PREFIX) {
+            return ExcludePattern::AbsolutePath(PathBuf::from(path));
         }
-        let has_glob_chars = trimmed.contains('*')
-            || trimmed.contains('?')
-            || trimmed.contains('[')
-            || trimmed.contains(']');
-        let has_path_sep = trimmed.contains('/') || trimmed.contains('\\');
-        if has_glob_chars || has_path_sep {
-            ExcludePattern::GlobPattern(trimmed.to_string())
-        } else {
-            ExcludePattern::BaseName(trimmed.to_string())
+        match gitignore_kind(trimmed) {
+            PatternKind::BaseName => ExcludePattern::BaseName(trimmed.to_string()),
+            _ => ExcludePattern::GlobPattern(trimmed.to_string()),
+        }
+    }
+
+    fn kind(&self) -> PatternKind {
+        match self {
+            ExcludePattern::AbsolutePath(_) => PatternKind::AbsolutePath,
+            ExcludePattern::BaseName(_) => PatternKind::BaseName,
+            ExcludePattern::GlobPattern(_) => PatternKind::Glob,
         }
     }
 }
 
+/// A plain name matches at any depth; anything with wildcards, a slash or a leading `!`
+/// is a glob.
+fn gitignore_kind(pattern: &str) -> PatternKind {
+    let has_glob_chars = pattern.contains('*')
+        || pattern.contains('?')
+        || pattern.contains('[')
+        || pattern.contains(']');
+    let has_path_sep = pattern.contains('/') || pattern.contains('\\');
+    if has_glob_chars || has_path_sep || pattern.starts_with('!') {
+        PatternKind::Glob
+    } else {
+        PatternKind::BaseName
+    }
+}
+
 pub fn parse_exclude_pattern_list(input: &str) -> ExcludeResult<Vec<ExcludePattern>> {
     let mut patterns = Vec::new();
 
@@ -47,116 +155,419 @@ pub fn parse_exclude_pattern_list(input: &str) -> ExcludeResult<Vec<ExcludePatte
         if trimmed.is_empty() {
             continue;
         }
+        patterns.push(parse_exclude_pattern(trimmed)?);
+    }
 
-        let path = Path::new(trimmed);
-        for component in path.components() {
-            if matches!(component, Component::ParentDir) {
-                return Err(ExcludeError::InvalidPattern(format!(
-                    "parent directory references (..) are not allowed in pattern '{}'",
-                    trimmed
-                )));
-            }
-        }
+    Ok(patterns)
+}
 
-        patterns.push(ExcludePattern::from_string(trimmed));
+/// Parse one pattern, such as a line of an `--exclude-from` file, where commas are literal.
+pub fn parse_exclude_pattern(pattern: &str) -> ExcludeResult<ExcludePattern> {
+    let trimmed = pattern.trim();
+    let path = Path::new(trimmed);
+    if let Some(path) = trimmed.strip_prefix(PATH_PREFIX)
+        && !Path::new(path).is_absolute()
+    {
+        return Err(ExcludeError::InvalidPattern(format!(
+            "'{}' needs an absolute path after '{}'",
+            trimmed, PATH_PREFIX
+        )));
     }
+    for component in path.components() {
+        if matches!(component, Component::ParentDir) {
+            return Err(ExcludeError::InvalidPattern(format!(
+                "parent directory references (..) are not allowed in pattern '{}'",
+                trimmed
+            )));
+        }
+    }
+    Ok(ExcludePattern::from_string(trimmed))
+}
 
-    Ok(patterns)
+/// Parse a `[filter]` config rule: `+ PATTERNS` includes, `- PATTERNS` excludes.
+pub fn parse_filter_rule(rule: &str) -> ExcludeResult<(FilterKind, Vec<ExcludePattern>)> {
+    let rule = rule.trim();
+    let (kind, patterns) = if let Some(patterns) = rule.strip_prefix("+ ") {
+        (FilterKind::Include, patterns)
+    } else if let Some(patterns) = rule.strip_prefix("- ") {
+        (FilterKind::Exclude, patterns)
+    } else {
+        return Err(ExcludeError::InvalidPattern(format!(
+            "filter rule '{}' must start with '+ ' (include) or '- ' (exclude)",
+            rule
+        )));
+    };
+    Ok((kind, parse_exclude_pattern_list(patterns)?))
 }
 
 pub fn build_exclude_rules(patterns: Vec<ExcludePattern>) -> ExcludeResult<Option<ExcludeRules>> {
-    if patterns.is_empty() {
-        return Ok(None);
-    }
+    build_filter_rules(vec![FilterGroup {
+        kind: FilterKind::Exclude,
+        origin: RuleOrigin::Flag("--exclude"),
+        patterns,
+    }])
+}
+
+/// Build ordered rules; consecutive groups of the same kind are merged into one rule.
+pub fn build_filter_rules(groups: Vec<FilterGroup>) -> ExcludeResult<Option<ExcludeRules>> {
     let mut absolute_paths = Vec::new();
-    let mut basenames = HashSet::new();
-    let mut glob_builder = GlobSetBuilder::new();
-    let mut has_globs = false;
-    for pattern in patterns {
-        match pattern {
-            ExcludePattern::AbsolutePath(path) => {
-                let canonical = path.canonicalize().unwrap_or(path);
-                absolute_paths.push(canonical);
-            }
-            ExcludePattern::BaseName(name) => {
-                basenames.insert(name);
+    let mut runs: Vec<(FilterKind, Vec<RulePattern>)> = Vec::new();
+    for group in groups {
+        let kind = group.kind;
+        for pattern in group.patterns {
+            let pattern_kind = pattern.kind();
+            let text = match pattern {
+                ExcludePattern::AbsolutePath(path) if kind == FilterKind::Exclude => {
+                    let canonical = path.canonicalize().unwrap_or_else(|_| path.clone());
+                    let text = path.display().to_string();
+                    absolute_paths.push((
+                        canonical,
+                        RulePattern {
+                            text,
+                            kind: pattern_kind,
+                            origin: group.origin.clone(),
+                        },
+                    ));
+                    continue;
+                }
+                ExcludePattern::AbsolutePath(path) => {
+                    return Err(ExcludeError::InvalidPattern(format!(
+                        "absolute path '{}' can only be excluded",
+                        path.display()
+                    )));
+                }
+                ExcludePattern::BaseName(text) | ExcludePattern::GlobPattern(text) => text,
+            };
+            // Before `path:`, an absolute pattern naming something on disk excluded that
+            // path; it still does, besides being anchored at the source directory
+            if kind == FilterKind::Exclude
+                && text.starts_with('/')
+                && let Ok(canonical) = Path::new(&text).canonicalize()
+            {
+                absolute_paths.push((
+                    canonical,
+                    RulePattern {
+                        text: text.clone(),
+                        kind: PatternKind::AbsolutePath,
+                        origin: group.origin.clone(),
+                    },
+                ));
             }
-            ExcludePattern::GlobPattern(pattern) => {
-                let glob = Glob::new(&pattern).map_err(|e| {
-                    ExcludeError::InvalidPattern(format!("Invalid glob '{}': {}", pattern, e))
-                })?;
-                glob_builder.add(glob);
-                has_globs = true;
+            let pattern = RulePattern {
+                text,
+                kind: pattern_kind,
+                origin: group.origin.clone(),
+            };
+            match runs.last_mut() {
+                Some((last, patterns)) if *last == kind => patterns.push(pattern),
+                _ => runs.push((kind, vec![pattern])),
             }
         }
     }
-    absolute_paths.sort_unstable_by_key(|b| std::cmp::Reverse(b.as_os_str().len()));
-    let glob_set = if has_globs {
-        Some(glob_builder.build()?)
-    } else {
-        None
-    };
+    if absolute_paths.is_empty() && runs.is_empty() {
+        return Ok(None);
+    }
+    absolute_paths.sort_unstable_by_key(|(path, _)| std::cmp::Reverse(path.as_os_str().len()));
+
+    let mut rules = Vec::with_capacity(runs.len());
+    for (kind, patterns) in runs {
+        // Rooted at ".", so paths are matched as given: relative to the source root
+        let mut builder = GitignoreBuilder::new(".");
+        for pattern in &patterns {
+            builder.add_line(None, &pattern.text).map_err(|e| {
+                ExcludeError::InvalidPattern(format!("Invalid pattern '{}': {}", pattern.text, e))
+            })?;
+        }
+        let matcher = builder
+            .build()
+            .map_err(|e| ExcludeError::InvalidPattern(format!("Invalid patterns: {}", e)))?;
+        rules.push(FilterRule {
+            kind,
+            matcher,
+            patterns,
+        });
+    }
+
     Ok(Some(ExcludeRules {
         absolute_paths,
-        basenames,
-        glob_set,
+        rules,
     }))
 }
 
 pub fn should_exclude(path: &Path, source_root: &Path, rules: &ExcludeRules) -> bool {
-    // Check basename of the path itself
-    if let Some(name) = path.file_name().and_then(|n| n.to_str())
-        && rules.basenames.contains(name)
-    {
-        return true;
-    }
-
-    // Check if any parent directory (between source_root and path) has an excluded basename
-    // This ensures that files inside excluded directories are also excluded
-    let relative = path.strip_prefix(source_root).unwrap_or(path);
-    for component in relative.components() {
-        if let std::path::Component::Normal(os_str) = component
-            && let Some(name) = os_str.to_str()
-            && rules.basenames.contains(name)
-        {
-            return true;
-        }
-    }
+    filter_path(path, source_root, rules) != Verdict::Include
+}
 
+pub fn filter_path(path: &Path, source_root: &Path, rules: &ExcludeRules) -> Verdict {
+    match_path(path, source_root, rules).map_or(Verdict::Include, |(verdict, _)| verdict)
+}
+
+/// The rule deciding about `path`, or `None` when no rule matches and it is copied.
+pub fn explain_path(path: &Path, source_root: &Path, rules: &ExcludeRules) -> Option<MatchReason> {
+    let (verdict, hit) = match_path(path, source_root, rules)?;
+    let pattern = match hit {
+        RuleHit::Absolute(pattern) => pattern.clone(),
+        // The matcher reports the pattern text; the last one with that text is the one
+        // that matched
+        RuleHit::Rule(rule, original) => rule
+            .patterns
+            .iter()
+            .rev()
+            .find(|pattern| pattern.text == original)?
+            .clone(),
+    };
+    Some(MatchReason {
+        verdict,
+        cause: MatchCause::Pattern(pattern),
+    })
+}
+
+enum RuleHit<'a> {
+    Absolute(&'a RulePattern),
+    Rule(&'a FilterRule, &'a str),
+}
+
+fn match_path<'a>(
+    path: &Path,
+    source_root: &Path,
+    rules: &'a ExcludeRules,
+) -> Option<(Verdict, RuleHit<'a>)> {
     // Check absolute paths
     if !rules.absolute_paths.is_empty() {
         let canonical = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
-        for excluded in &rules.absolute_paths {
+        for (excluded, pattern) in &rules.absolute_paths {
             if canonical == *excluded
                 || (canonical.starts_with(excluded)
                     && canonical.components().count() > excluded.components().count())
             {
-                return true;
+                return Some((Verdict::Exclude, RuleHit::Absolute(pattern)));
+            }
+        }
+    }
+
+    if rules.rules.is_empty() {
+        return None;
+    }
+    let relative = match path.strip_prefix(source_root) {
+        Ok(relative) if !relative.as_os_str().is_empty() => relative,
+        _ => Path::new(path.file_name()?),
+    };
+    let is_dir = path.is_dir();
+    for (index, rule) in rules.rules.iter().enumerate() {
+        // A `!pattern` exempts the path from this rule, leaving it to the next ones
+        let Match::Ignore(glob) = matched_after_parents(&rule.matcher, relative, is_dir) else {
+            continue;
+        };
+        let hit = RuleHit::Rule(rule, glob.original());
+        if rule.kind == FilterKind::Include {
+            return Some((Verdict::Include, hit));
+        }
+        // This rule also matches everything inside, so only an earlier include can win there
+        let earlier_include = rules.rules[..index]
+            .iter()
+            .any(|rule| rule.kind == FilterKind::Include);
+        return if is_dir && earlier_include {
+            Some((Verdict::Traverse, hit))
+        } else {
+            Some((Verdict::Exclude, hit))
+        };
+    }
+    None
+}
+
+/// Match `relative`, unless one of its parent directories is excluded already: as in
+/// git, nothing inside an excluded directory can be re-included by a `!pattern`.
+fn matched_after_parents<'a>(
+    matcher: &'a Gitignore,
+    relative: &Path,
+    is_dir: bool,
+) -> Match<&'a ignore::gitignore::Glob> {
+    let parents: Vec<&Path> = relative
+        .ancestors()
+        .skip(1)
+        .filter(|parent| !parent.as_os_str().is_empty())
+        .collect();
+    for parent in parents.into_iter().rev() {
+        if let Match::Ignore(glob) = matcher.matched(parent, true) {
+            return Match::Ignore(glob);
+        }
+    }
+    matcher.matched(relative, is_dir)
+}
+
+/// Names of per-directory ignore files read with `--respect-gitignore`, in order of
+/// increasing precedence.
+pub const GITIGNORE_FILES: [&str; 2] = [".gitignore", ".cpxignore"];
+
+/// The ignore files of the directories from the source root down to the one being read,
+/// deepest last. Cloned into every subdirectory the walk descends into.
+#[derive(Debug, Clone, Default)]
+pub struct IgnoreStack {
+    matchers: Vec<Arc<Gitignore>>,
+}
+
+impl IgnoreStack {
+    /// Add the rules of the ignore files called `names` in `dir`, if there are any.
+    /// Lines that don't parse are skipped, as git does.
+    pub fn load(&mut self, dir: &Path, names: &[String]) {
+        let mut builder = GitignoreBuilder::new(dir);
+        let mut found = false;
+        for name in names {
+            let file = dir.join(name);
+            if file.is_file() {
+                builder.add(file);
+                found = true;
+            }
+        }
+        if found && let Ok(gitignore) = builder.build() {
+            self.matchers.push(Arc::new(gitignore));
+        }
+    }
+
+    /// The deepest ignore file with a matching rule decides, so a nested file can
+    /// re-include what a parent one ignores.
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        for gitignore in self.matchers.iter().rev() {
+            let matched = gitignore.matched(path, is_dir);
+            if !matched.is_none() {
+                return matched.is_ignore();
             }
         }
+        false
     }
 
-    // Check glob patterns
-    if let Some(glob_set) = &rules.glob_set {
-        let relative = path.strip_prefix(source_root).unwrap_or(path);
-        let mut rel_str: Cow<str> = relative.to_string_lossy();
-        if rel_str.contains('\\') {
-            rel_str = Cow::Owned(rel_str.replace('\\', "/"));
+    /// The ignore file rule deciding about `path`, when it ignores it.
+    pub fn explain(&self, path: &Path, is_dir: bool) -> Option<MatchReason> {
+        for gitignore in self.matchers.iter().rev() {
+            match gitignore.matched(path, is_dir) {
+                Match::None => continue,
+                Match::Whitelist(_) => return None,
+                Match::Ignore(glob) => {
+                    let file = glob
+                        .from()
+                        .unwrap_or_else(|| gitignore.path())
+                        .to_path_buf();
+                    let line = ignore_file_line(&file, glob.original());
+                    return Some(MatchReason {
+                        verdict: Verdict::Exclude,
+                        cause: MatchCause::Pattern(RulePattern {
+                            text: glob.original().to_string(),
+                            kind: gitignore_kind(glob.original()),
+                            origin: RuleOrigin::IgnoreFile { path: file, line },
+                        }),
+                    });
+                }
+            }
         }
-        if glob_set.is_match(&*rel_str) {
-            return true;
+        None
+    }
+}
+
+/// Line number of the last line of `file` reading `pattern`, or 0 if it can't be found.
+fn ignore_file_line(file: &Path, pattern: &str) -> usize {
+    std::fs::read_to_string(file)
+        .ok()
+        .and_then(|contents| {
+            contents
+                .lines()
+                .enumerate()
+                .filter(|(_, line)| line.trim_end() == pattern)
+                .last()
+                .map(|(index, _)| index + 1)
+        })
+        .unwrap_or(0)
+}
+
+/// Name of the file marking a cache directory, see <https://bford.info/cachedir/>.
+pub const CACHEDIR_TAG: &str = "CACHEDIR.TAG";
+const CACHEDIR_SIGNATURE: &[u8] = b"Signature: 8a477f597d28d172789f06886806bc55";
+
+/// Files whose presence makes a directory leave itself out of the copy, as with tar's
+/// `--exclude-caches` and `--exclude-if-present`.
+#[derive(Debug, Clone, Default)]
+pub struct ExclusionMarkers {
+    /// Skip directories holding a `CACHEDIR.TAG` with the standard signature
+    pub caches: bool,
+    /// Skip directories holding a file or directory with one of these names
+    pub names: Vec<String>,
+}
+
+impl ExclusionMarkers {
+    pub fn is_empty(&self) -> bool {
+        !self.caches && self.names.is_empty()
+    }
+
+    pub fn marks(&self, dir: &Path) -> bool {
+        self.marker(dir).is_some()
+    }
+
+    /// The marker file that leaves `dir` out, if there is one.
+    pub fn marker(&self, dir: &Path) -> Option<PathBuf> {
+        let tag = dir.join(CACHEDIR_TAG);
+        if self.caches && is_cachedir_tag(&tag) {
+            return Some(tag);
         }
-        if path.is_dir() {
-            let mut with_slash = String::with_capacity(rel_str.len() + 1);
-            with_slash.push_str(&rel_str);
-            with_slash.push('/');
-            if glob_set.is_match(&with_slash) {
-                return true;
+        self.names
+            .iter()
+            .map(|name| dir.join(name))
+            .find(|marker| std::fs::symlink_metadata(marker).is_ok())
+    }
+}
+
+fn is_cachedir_tag(path: &Path) -> bool {
+    let mut header = [0u8; CACHEDIR_SIGNATURE.len()];
+    std::fs::File::open(path)
+        .and_then(|mut file| file.read_exact(&mut header))
+        .is_ok_and(|()| header == CACHEDIR_SIGNATURE)
+}
+
+impl fmt::Display for RuleOrigin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RuleOrigin::Flag(flag) => write!(f, "{}", flag),
+            RuleOrigin::PatternFile { path, line } | RuleOrigin::IgnoreFile { path, line } => {
+                write!(f, "{}:{}", path.display(), line)
             }
+            RuleOrigin::Config {
+                path: Some(path),
+                key,
+            } => write!(f, "{} [filter] {}", path.display(), key),
+            RuleOrigin::Config { path: None, key } => write!(f, "config [filter] {}", key),
+            RuleOrigin::Preset(name) => write!(f, "preset '{}'", name),
         }
     }
+}
 
-    false
+impl fmt::Display for MatchCause {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MatchCause::Pattern(pattern) => {
+                let kind = match pattern.kind {
+                    PatternKind::AbsolutePath => "absolute path",
+                    PatternKind::BaseName => "basename",
+                    PatternKind::Glob => "glob",
+                };
+                write!(f, "{} '{}' from {}", kind, pattern.text, pattern.origin)
+            }
+            MatchCause::Marker(marker) => write!(f, "marker file {}", marker.display()),
+            MatchCause::Nodump(path) => write!(f, "nodump flag on {}", path.display()),
+            MatchCause::Expression => write!(f, "the --filter expression"),
+        }
+    }
+}
+
+impl fmt::Display for MatchReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.verdict {
+            Verdict::Include => write!(f, "included by {}", self.cause),
+            Verdict::Exclude => write!(f, "excluded by {}", self.cause),
+            Verdict::Traverse => write!(
+                f,
+                "excluded by {}, but searched for included entries",
+                self.cause
+            ),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -305,4 +716,267 @@ mod exclude_tests {
 
         assert!(should_exclude(&dir_path, temp_dir.path(), &rules));
     }
+
+    fn gitignore_rules(patterns: &[&str]) -> ExcludeRules {
+        let patterns = patterns
+            .iter()
+            .map(|p| ExcludePattern::from_string(p))
+            .collect();
+        build_exclude_rules(patterns).unwrap().unwrap()
+    }
+
+    #[test]
+    fn test_exclude_negation() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        create_file(&root.join("debug.log"), b"");
+        create_file(&root.join("keep.log"), b"");
+
+        let rules = gitignore_rules(&["*.log", "!keep.log"]);
+        assert!(should_exclude(&root.join("debug.log"), root, &rules));
+        assert!(!should_exclude(&root.join("keep.log"), root, &rules));
+    }
+
+    #[test]
+    fn test_exclude_root_anchored() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("build")).unwrap();
+        fs::create_dir_all(root.join("src/build")).unwrap();
+        create_file(&root.join("build/out.o"), b"");
+
+        let rules = gitignore_rules(&["/build"]);
+        assert!(should_exclude(&root.join("build"), root, &rules));
+        assert!(should_exclude(&root.join("build/out.o"), root, &rules));
+        assert!(!should_exclude(&root.join("src/build"), root, &rules));
+    }
+
+    #[test]
+    fn test_negation_cannot_reinclude_inside_excluded_directory() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        create_file(&root.join("build/o"), b"");
+        create_file(&root.join("build/p"), b"");
+        create_file(&root.join("o"), b"");
+
+        let rules = gitignore_rules(&["build", "!build/o"]);
+        assert!(should_exclude(&root.join("build"), root, &rules));
+        assert!(should_exclude(&root.join("build/o"), root, &rules));
+        assert!(should_exclude(&root.join("build/p"), root, &rules));
+
+        let reason = explain_path(&root.join("build/o"), root, &rules).unwrap();
+        assert_eq!(reason.verdict, Verdict::Exclude);
+        assert_eq!(reason.cause.to_string(), "basename 'build' from --exclude");
+    }
+
+    #[test]
+    fn test_absolute_paths_need_the_path_prefix() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        create_file(&root.join("build/out.o"), b"");
+        let build = root.join("build");
+
+        // Without the prefix it's parsed as a root-anchored gitignore pattern
+        assert!(matches!(
+            ExcludePattern::from_string(&build.display().to_string()),
+            ExcludePattern::GlobPattern(_)
+        ));
+        let pattern = parse_exclude_pattern(&format!("path:{}", build.display())).unwrap();
+        assert!(matches!(&pattern, ExcludePattern::AbsolutePath(path) if *path == build));
+        // ...and the prefix names a path whether it exists or not
+        assert!(matches!(
+            parse_exclude_pattern("path:/does/not/exist").unwrap(),
+            ExcludePattern::AbsolutePath(_)
+        ));
+        assert!(parse_exclude_pattern("path:relative/dir").is_err());
+
+        let rules = build_exclude_rules(vec![pattern]).unwrap().unwrap();
+        assert!(should_exclude(&root.join("build/out.o"), root, &rules));
+
+        // An unprefixed absolute path that exists still excludes itself
+        let unprefixed = ExcludePattern::from_string(&build.display().to_string());
+        let rules = build_exclude_rules(vec![unprefixed]).unwrap().unwrap();
+        assert!(should_exclude(&root.join("build/out.o"), root, &rules));
+        let reason = explain_path(&build, root, &rules).unwrap();
+        assert!(matches!(
+            reason.cause,
+            MatchCause::Pattern(RulePattern {
+                kind: PatternKind::AbsolutePath,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_exclude_directory_only() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("a/cache")).unwrap();
+        create_file(&root.join("b/cache"), b"");
+
+        let rules = gitignore_rules(&["cache/"]);
+        assert!(should_exclude(&root.join("a/cache"), root, &rules));
+        assert!(!should_exclude(&root.join("b/cache"), root, &rules));
+    }
+
+    #[test]
+    fn test_ignore_stack_nested_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let names = vec![".gitignore".to_string(), ".cpxignore".to_string()];
+        create_file(&root.join(".gitignore"), b"*.log\ntarget/\n");
+        create_file(&root.join("sub/.cpxignore"), b"!keep.log\n");
+
+        let mut ignores = IgnoreStack::default();
+        ignores.load(root, &names);
+        assert!(ignores.is_ignored(&root.join("a.log"), false));
+        assert!(ignores.is_ignored(&root.join("target"), true));
+        assert!(!ignores.is_ignored(&root.join("target"), false));
+
+        ignores.load(&root.join("sub"), &names);
+        assert!(!ignores.is_ignored(&root.join("sub/keep.log"), false));
+        assert!(ignores.is_ignored(&root.join("sub/other.log"), false));
+    }
+
+    #[test]
+    fn test_explain_path_reports_origin() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        create_file(&root.join("data/a.parquet"), b"");
+        create_file(&root.join("node_modules/x/index.js"), b"");
+
+        let rules = build_filter_rules(vec![
+            FilterGroup {
+                kind: FilterKind::Include,
+                origin: RuleOrigin::Flag("--include"),
+                patterns: parse_exclude_pattern_list("*.parquet").unwrap(),
+            },
+            FilterGroup {
+                kind: FilterKind::Exclude,
+                origin: RuleOrigin::Config {
+                    path: Some(PathBuf::from("cpxconfig.toml")),
+                    key: "patterns",
+                },
+                patterns: parse_exclude_pattern_list("*.tmp, node_modules").unwrap(),
+            },
+        ])
+        .unwrap()
+        .unwrap();
+
+        let reason = explain_path(&root.join("data/a.parquet"), root, &rules).unwrap();
+        assert_eq!(reason.verdict, Verdict::Include);
+        assert_eq!(
+            reason.to_string(),
+            "included by glob '*.parquet' from --include"
+        );
+
+        let reason = explain_path(&root.join("node_modules/x/index.js"), root, &rules).unwrap();
+        assert_eq!(
+            reason.to_string(),
+            "excluded by basename 'node_modules' from cpxconfig.toml [filter] patterns"
+        );
+        assert!(explain_path(&root.join("data"), root, &rules).is_none());
+    }
+
+    #[test]
+    fn test_ignore_stack_explain_line() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        create_file(&root.join(".gitignore"), b"# logs\n*.log\n!keep.log\n");
+
+        let mut ignores = IgnoreStack::default();
+        ignores.load(root, &[".gitignore".to_string()]);
+        let reason = ignores.explain(&root.join("a.log"), false).unwrap();
+        assert_eq!(
+            reason.cause,
+            MatchCause::Pattern(RulePattern {
+                text: "*.log".to_string(),
+                kind: PatternKind::Glob,
+                origin: RuleOrigin::IgnoreFile {
+                    path: root.join(".gitignore"),
+                    line: 2,
+                },
+            })
+        );
+        assert!(ignores.explain(&root.join("keep.log"), false).is_none());
+    }
+
+    #[test]
+    fn test_exclusion_markers() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        create_file(
+            &root.join("cache/CACHEDIR.TAG"),
+            b"Signature: 8a477f597d28d172789f06886806bc55\n# created by a build tool\n",
+        );
+        create_file(&root.join("fake/CACHEDIR.TAG"), b"not a cache\n");
+        create_file(&root.join("scratch/.nobackup"), b"");
+
+        let markers = ExclusionMarkers {
+            caches: true,
+            names: vec![".nobackup".to_string()],
+        };
+        assert!(markers.marks(&root.join("cache")));
+        assert!(!markers.marks(&root.join("fake")));
+        assert!(markers.marks(&root.join("scratch")));
+        assert!(!markers.marks(root));
+        assert!(ExclusionMarkers::default().is_empty());
+    }
+
+    fn filter_rules(groups: &[(FilterKind, &str)]) -> ExcludeRules {
+        let groups = groups
+            .iter()
+            .map(|(kind, list)| FilterGroup {
+                kind: *kind,
+                origin: RuleOrigin::Flag("--exclude"),
+                patterns: parse_exclude_pattern_list(list).unwrap(),
+            })
+            .collect();
+        build_filter_rules(groups).unwrap().unwrap()
+    }
+
+    #[test]
+    fn test_filter_first_match_wins() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        create_file(&root.join("data/a.parquet"), b"");
+        create_file(&root.join("data/a.csv"), b"");
+        create_file(&root.join("node_modules/x.json"), b"");
+
+        let rules = filter_rules(&[
+            (FilterKind::Exclude, "node_modules"),
+            (FilterKind::Include, "*.parquet,*.json"),
+            (FilterKind::Exclude, "*"),
+        ]);
+        assert_eq!(
+            filter_path(&root.join("data/a.parquet"), root, &rules),
+            Verdict::Include
+        );
+        assert_eq!(
+            filter_path(&root.join("data/a.csv"), root, &rules),
+            Verdict::Exclude
+        );
+        // Walked for the earlier includes, but pruned when nothing can override
+        assert_eq!(
+            filter_path(&root.join("data"), root, &rules),
+            Verdict::Traverse
+        );
+        assert_eq!(
+            filter_path(&root.join("node_modules"), root, &rules),
+            Verdict::Exclude
+        );
+        assert_eq!(
+            filter_path(&root.join("node_modules/x.json"), root, &rules),
+            Verdict::Exclude
+        );
+    }
+
+    #[test]
+    fn test_filter_rule_from_config() {
+        let (kind, patterns) = parse_filter_rule("+ *.parquet, *.json").unwrap();
+        assert_eq!(kind, FilterKind::Include);
+        assert_eq!(patterns.len(), 2);
+        assert_eq!(parse_filter_rule("- *").unwrap().0, FilterKind::Exclude);
+        assert!(parse_filter_rule("*.tmp").is_err());
+    }
 }
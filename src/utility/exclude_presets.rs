@@ -0,0 +1,89 @@
+use super::exclude::{ExcludePattern, parse_exclude_pattern};
+use crate::error::{ExcludeError, ExcludeResult};
+
+/// Exclude patterns shipped with cpx for build output, caches and OS clutter.
+pub const EXCLUDE_PRESETS: &[(&str, &[&str])] = &[
+    ("rust", &["target/"]),
+    (
+        "node",
+        &[
+            "node_modules/",
+            ".npm/",
+            ".pnpm-store/",
+            ".next/",
+            ".nuxt/",
+            ".parcel-cache/",
+        ],
+    ),
+    (
+        "python",
+        &[
+            "__pycache__/",
+            "*.py[cod]",
+            ".venv/",
+            ".tox/",
+            ".mypy_cache/",
+            ".pytest_cache/",
+            ".ruff_cache/",
+            "*.egg-info/",
+        ],
+    ),
+    ("java", &[".gradle/", "*.class"]),
+    (
+        "macos-junk",
+        &[
+            ".DS_Store",
+            "._*",
+            ".Spotlight-V100/",
+            ".Trashes/",
+            ".fseventsd/",
+        ],
+    ),
+    (
+        "windows-junk",
+        &["Thumbs.db", "ehthumbs.db", "desktop.ini", "$RECYCLE.BIN/"],
+    ),
+    ("editor", &["*.swp", "*.swo", "*~", ".idea/"]),
+    ("vcs", &[".git/", ".hg/", ".svn/"]),
+];
+
+/// Patterns of the preset called `name`.
+pub fn preset_patterns(name: &str) -> ExcludeResult<Vec<ExcludePattern>> {
+    let (_, patterns) = EXCLUDE_PRESETS
+        .iter()
+        .find(|(preset, _)| *preset == name.trim())
+        .ok_or_else(|| {
+            let known: Vec<&str> = EXCLUDE_PRESETS.iter().map(|(preset, _)| *preset).collect();
+            ExcludeError::InvalidPattern(format!(
+                "unknown exclude preset '{}' (available: {})",
+                name.trim(),
+                known.join(", ")
+            ))
+        })?;
+    patterns
+        .iter()
+        .map(|pattern| parse_exclude_pattern(pattern))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_presets_parse() {
+        for (name, _) in EXCLUDE_PRESETS {
+            assert!(!preset_patterns(name).unwrap().is_empty(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_unknown_preset() {
+        let Err(err) = preset_patterns("cobol") else {
+            panic!("unknown preset accepted");
+        };
+        let err = err.to_string();
+        assert!(err.contains("unknown exclude preset 'cobol'"));
+        assert!(err.contains("rust"));
+    }
+}
@@ -0,0 +1,131 @@
+use std::collections::HashSet;
+use std::io::{self, Read};
+use std::path::{Component, Path, PathBuf};
+
+/// Read a list from a file, or from stdin for `-`, split on `separator`.
+/// Empty entries are dropped, as is the `\r` of CRLF line endings.
+pub fn read_list(path: &Path, separator: u8) -> io::Result<Vec<Vec<u8>>> {
+    Ok(read_input(path)?
+        .split(|byte| *byte == separator)
+        .map(|entry| match separator {
+            b'\n' => entry.strip_suffix(b"\r").unwrap_or(entry),
+            _ => entry,
+        })
+        .filter(|entry| !entry.is_empty())
+        .map(<[u8]>::to_vec)
+        .collect())
+}
+
+/// Patterns of an `--exclude-from` file with their line numbers: one per line, skipping
+/// blank lines and `#` comments.
+pub fn read_patterns(path: &Path) -> io::Result<Vec<(usize, String)>> {
+    Ok(String::from_utf8_lossy(&read_input(path)?)
+        .lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line.trim().to_string()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .collect())
+}
+
+fn read_input(path: &Path) -> io::Result<Vec<u8>> {
+    let mut data = Vec::new();
+    if path == Path::new("-") {
+        io::stdin().lock().read_to_end(&mut data)?;
+    } else {
+        std::fs::File::open(path)?.read_to_end(&mut data)?;
+    }
+    Ok(data)
+}
+
+/// Turn `--files-from` entries into paths under `base`, in order and without duplicates.
+///
+/// Leading `/` and `./` are dropped so each entry keeps its relative layout in the
+/// destination; entries climbing out with `..` are refused.
+pub fn resolve_listed(base: &Path, entries: Vec<Vec<u8>>) -> Result<Vec<PathBuf>, String> {
+    let mut seen = HashSet::new();
+    let mut paths = Vec::with_capacity(entries.len());
+    for entry in entries {
+        let listed = entry_path(entry);
+        let mut relative = PathBuf::new();
+        for component in listed.components() {
+            match component {
+                Component::Normal(name) => relative.push(name),
+                Component::ParentDir => {
+                    return Err(format!(
+                        "listed path '{}' is outside the source directory",
+                        listed.display()
+                    ));
+                }
+                Component::CurDir | Component::RootDir | Component::Prefix(_) => {}
+            }
+        }
+        if relative.as_os_str().is_empty() || !seen.insert(relative.clone()) {
+            continue;
+        }
+        paths.push(base.join(relative));
+    }
+    Ok(paths)
+}
+
+#[cfg(unix)]
+fn entry_path(entry: Vec<u8>) -> PathBuf {
+    use std::os::unix::ffi::OsStringExt;
+    PathBuf::from(std::ffi::OsString::from_vec(entry))
+}
+
+#[cfg(not(unix))]
+fn entry_path(entry: Vec<u8>) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(&entry).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_read_list_separators() {
+        let temp = TempDir::new().unwrap();
+        let lines = temp.path().join("lines");
+        fs::write(&lines, "a.txt\r\n\nb c.txt\n").unwrap();
+        assert_eq!(
+            read_list(&lines, b'\n').unwrap(),
+            vec![b"a.txt".to_vec(), b"b c.txt".to_vec()]
+        );
+
+        let nul = temp.path().join("nul");
+        fs::write(&nul, "new\nline\0other\0").unwrap();
+        assert_eq!(
+            read_list(&nul, b'\0').unwrap(),
+            vec![b"new\nline".to_vec(), b"other".to_vec()]
+        );
+    }
+
+    #[test]
+    fn test_read_patterns_skips_comments() {
+        let temp = TempDir::new().unwrap();
+        let file = temp.path().join("excludes");
+        fs::write(&file, "# build output\ntarget/\n\n*.log\n").unwrap();
+        assert_eq!(
+            read_patterns(&file).unwrap(),
+            vec![(2, "target/".to_string()), (4, "*.log".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_resolve_listed() {
+        let base = Path::new("src");
+        let entries = vec![
+            b"./a/b.txt".to_vec(),
+            b"/c.txt".to_vec(),
+            b"a/b.txt".to_vec(),
+            b".".to_vec(),
+        ];
+        assert_eq!(
+            resolve_listed(base, entries).unwrap(),
+            vec![PathBuf::from("src/a/b.txt"), PathBuf::from("src/c.txt")]
+        );
+        assert!(resolve_listed(base, vec![b"../etc/passwd".to_vec()]).is_err());
+    }
+}
@@ -0,0 +1,554 @@
+use super::idmap::{group_id, user_id};
+use crate::error::{ExcludeError, ExcludeResult};
+use globset::{Glob, GlobMatcher};
+use std::fs::Metadata;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+/// A compiled `--filter` expression, e.g. `size > 100M and mtime < 7d and type == file`.
+///
+/// Comparisons combine with `and`, `or`, `not` (or `&&`, `||`, `!`) and parentheses.
+/// Fields are `size` (with K, M, G, T suffixes), `mtime`/`atime`/`ctime` (ages with
+/// s, m, h, d, w suffixes, so `mtime < 7d` means modified within the last week),
+/// `type` (file, dir, symlink, fifo, socket, block, char), `user`, `group`, `uid`,
+/// `gid` and `name` (a glob on the file name). `executable` stands on its own.
+#[derive(Debug, Clone)]
+pub struct FilterExpr {
+    root: Expr,
+    now: SystemTime,
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Test(Test),
+}
+
+#[derive(Debug, Clone)]
+enum Test {
+    Size(Op, u64),
+    Age(TimeField, Op, Duration),
+    Type(bool, FileKind),
+    Uid(Op, u32),
+    Gid(Op, u32),
+    Name(bool, GlobMatcher),
+    Executable,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+    Ne,
+}
+
+impl Op {
+    fn compare<T: PartialOrd>(self, left: T, right: T) -> bool {
+        match self {
+            Op::Lt => left < right,
+            Op::Le => left <= right,
+            Op::Gt => left > right,
+            Op::Ge => left >= right,
+            Op::Eq => left == right,
+            Op::Ne => left != right,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TimeField {
+    Modified,
+    Accessed,
+    Changed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FileKind {
+    File,
+    Dir,
+    Symlink,
+    Fifo,
+    Socket,
+    Block,
+    Char,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Op(Op),
+    And,
+    Or,
+    Not,
+    Open,
+    Close,
+}
+
+impl FilterExpr {
+    pub fn parse(input: &str) -> ExcludeResult<Self> {
+        let tokens = tokenize(input)?;
+        let mut parser = Parser { tokens, pos: 0 };
+        let root = parser.or()?;
+        if let Some(token) = parser.tokens.get(parser.pos) {
+            return Err(invalid(format!("unexpected {}", describe(token))));
+        }
+        Ok(Self {
+            root,
+            now: SystemTime::now(),
+        })
+    }
+
+    /// Both expressions have to hold.
+    pub fn and(self, other: FilterExpr) -> Self {
+        Self {
+            root: Expr::And(Box::new(self.root), Box::new(other.root)),
+            now: self.now,
+        }
+    }
+
+    pub fn matches(&self, path: &Path, metadata: &Metadata) -> bool {
+        self.eval(&self.root, path, metadata)
+    }
+
+    fn eval(&self, expr: &Expr, path: &Path, metadata: &Metadata) -> bool {
+        match expr {
+            Expr::And(left, right) => {
+                self.eval(left, path, metadata) && self.eval(right, path, metadata)
+            }
+            Expr::Or(left, right) => {
+                self.eval(left, path, metadata) || self.eval(right, path, metadata)
+            }
+            Expr::Not(inner) => !self.eval(inner, path, metadata),
+            Expr::Test(test) => self.test(test, path, metadata),
+        }
+    }
+
+    fn test(&self, test: &Test, path: &Path, metadata: &Metadata) -> bool {
+        match test {
+            Test::Size(op, size) => op.compare(metadata.len(), *size),
+            Test::Age(field, op, age) => match timestamp(metadata, *field) {
+                // Timestamps in the future count as just written
+                Some(time) => op.compare(self.now.duration_since(time).unwrap_or_default(), *age),
+                None => false,
+            },
+            Test::Type(equal, kind) => (file_kind(metadata) == Some(*kind)) == *equal,
+            Test::Uid(op, uid) => owner(metadata).is_some_and(|(owner, _)| op.compare(owner, *uid)),
+            Test::Gid(op, gid) => owner(metadata).is_some_and(|(_, group)| op.compare(group, *gid)),
+            Test::Name(equal, matcher) => {
+                path.file_name().is_some_and(|name| matcher.is_match(name)) == *equal
+            }
+            Test::Executable => metadata.is_file() && is_executable(metadata),
+        }
+    }
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn eat(&mut self, expected: &Token) -> bool {
+        if self.tokens.get(self.pos) == Some(expected) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn or(&mut self) -> ExcludeResult<Expr> {
+        let mut expr = self.and()?;
+        while self.eat(&Token::Or) {
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> ExcludeResult<Expr> {
+        let mut expr = self.not()?;
+        while self.eat(&Token::And) {
+            expr = Expr::And(Box::new(expr), Box::new(self.not()?));
+        }
+        Ok(expr)
+    }
+
+    fn not(&mut self) -> ExcludeResult<Expr> {
+        if self.eat(&Token::Not) {
+            return Ok(Expr::Not(Box::new(self.not()?)));
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> ExcludeResult<Expr> {
+        match self.next() {
+            Some(Token::Open) => {
+                let expr = self.or()?;
+                if !self.eat(&Token::Close) {
+                    return Err(invalid("missing ')'".to_string()));
+                }
+                Ok(expr)
+            }
+            Some(Token::Word(field)) if field == "executable" => Ok(Expr::Test(Test::Executable)),
+            Some(Token::Word(field)) => {
+                let op = match self.next() {
+                    Some(Token::Op(op)) => op,
+                    _ => {
+                        return Err(invalid(format!("expected a comparison after '{}'", field)));
+                    }
+                };
+                let value = match self.next() {
+                    Some(Token::Word(value)) => value,
+                    _ => return Err(invalid(format!("expected a value after '{}'", field))),
+                };
+                Ok(Expr::Test(comparison(&field, op, &value)?))
+            }
+            Some(token) => Err(invalid(format!("unexpected {}", describe(&token)))),
+            None => Err(invalid("unexpected end of expression".to_string())),
+        }
+    }
+}
+
+fn comparison(field: &str, op: Op, value: &str) -> ExcludeResult<Test> {
+    let equality = |field: &str| match op {
+        Op::Eq => Ok(true),
+        Op::Ne => Ok(false),
+        _ => Err(invalid(format!(
+            "'{}' can only be compared with == or !=",
+            field
+        ))),
+    };
+    Ok(match field {
+        "size" => Test::Size(op, parse_size(value)?),
+        "mtime" => Test::Age(TimeField::Modified, op, parse_age(value)?),
+        "atime" => Test::Age(TimeField::Accessed, op, parse_age(value)?),
+        "ctime" => Test::Age(TimeField::Changed, op, parse_age(value)?),
+        "type" => Test::Type(equality(field)?, parse_kind(value)?),
+        "uid" => Test::Uid(op, parse_id(value)?),
+        "gid" => Test::Gid(op, parse_id(value)?),
+        "user" => {
+            let uid = value.parse().ok().or_else(|| user_id(value));
+            Test::Uid(
+                op,
+                uid.ok_or_else(|| invalid(format!("unknown user '{}'", value)))?,
+            )
+        }
+        "group" => {
+            let gid = value.parse().ok().or_else(|| group_id(value));
+            Test::Gid(
+                op,
+                gid.ok_or_else(|| invalid(format!("unknown group '{}'", value)))?,
+            )
+        }
+        "name" => {
+            let matcher = Glob::new(value)
+                .map_err(|e| invalid(format!("invalid name pattern '{}': {}", value, e)))?
+                .compile_matcher();
+            Test::Name(equality(field)?, matcher)
+        }
+        _ => return Err(invalid(format!("unknown field '{}'", field))),
+    })
+}
+
+fn parse_size(value: &str) -> ExcludeResult<u64> {
+    let split = value
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let multiplier: u64 = match unit.to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" | "KIB" => 1 << 10,
+        "M" | "MB" | "MIB" => 1 << 20,
+        "G" | "GB" | "GIB" => 1 << 30,
+        "T" | "TB" | "TIB" => 1 << 40,
+        _ => return Err(invalid(format!("invalid size '{}'", value))),
+    };
+    let number: f64 = number
+        .parse()
+        .map_err(|_| invalid(format!("invalid size '{}'", value)))?;
+    Ok((number * multiplier as f64) as u64)
+}
+
+fn parse_age(value: &str) -> ExcludeResult<Duration> {
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let seconds: u64 = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "" | "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return Err(invalid(format!("invalid age '{}'", value))),
+    };
+    let number: u64 = number
+        .parse()
+        .map_err(|_| invalid(format!("invalid age '{}'", value)))?;
+    Ok(Duration::from_secs(number.saturating_mul(seconds)))
+}
+
+fn parse_kind(value: &str) -> ExcludeResult<FileKind> {
+    Ok(match value {
+        "file" | "f" => FileKind::File,
+        "dir" | "directory" | "d" => FileKind::Dir,
+        "symlink" | "link" | "l" => FileKind::Symlink,
+        "fifo" | "p" => FileKind::Fifo,
+        "socket" | "s" => FileKind::Socket,
+        "block" | "b" => FileKind::Block,
+        "char" | "c" => FileKind::Char,
+        _ => return Err(invalid(format!("unknown type '{}'", value))),
+    })
+}
+
+fn parse_id(value: &str) -> ExcludeResult<u32> {
+    value
+        .parse()
+        .map_err(|_| invalid(format!("invalid id '{}'", value)))
+}
+
+fn tokenize(input: &str) -> ExcludeResult<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = input.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' | ')' => {
+                chars.next();
+                tokens.push(if c == '(' { Token::Open } else { Token::Close });
+            }
+            '<' | '>' | '=' | '!' => {
+                chars.next();
+                let followed_by_eq = chars.next_if_eq(&'=').is_some();
+                tokens.push(match (c, followed_by_eq) {
+                    ('<', false) => Token::Op(Op::Lt),
+                    ('<', true) => Token::Op(Op::Le),
+                    ('>', false) => Token::Op(Op::Gt),
+                    ('>', true) => Token::Op(Op::Ge),
+                    ('=', _) => Token::Op(Op::Eq),
+                    ('!', true) => Token::Op(Op::Ne),
+                    _ => Token::Not,
+                });
+            }
+            '&' | '|' => {
+                chars.next();
+                if chars.next_if_eq(&c).is_none() {
+                    return Err(invalid(format!("expected '{}{}'", c, c)));
+                }
+                tokens.push(if c == '&' { Token::And } else { Token::Or });
+            }
+            '\'' | '"' => {
+                chars.next();
+                let mut word = String::new();
+                loop {
+                    match chars.next() {
+                        Some(next) if next == c => break,
+                        Some(next) => word.push(next),
+                        None => return Err(invalid("unterminated quote".to_string())),
+                    }
+                }
+                tokens.push(Token::Word(word));
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some(&next) = chars.peek() {
+                    if next.is_whitespace() || "()<>=!&|'\"".contains(next) {
+                        break;
+                    }
+                    word.push(next);
+                    chars.next();
+                }
+                tokens.push(match word.as_str() {
+                    "and" => Token::And,
+                    "or" => Token::Or,
+                    "not" => Token::Not,
+                    _ => Token::Word(word),
+                });
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+fn describe(token: &Token) -> String {
+    match token {
+        Token::Word(word) => format!("'{}'", word),
+        Token::Op(_) => "comparison".to_string(),
+        Token::And => "'and'".to_string(),
+        Token::Or => "'or'".to_string(),
+        Token::Not => "'not'".to_string(),
+        Token::Open => "'('".to_string(),
+        Token::Close => "')'".to_string(),
+    }
+}
+
+fn invalid(message: String) -> ExcludeError {
+    ExcludeError::InvalidExpression(message)
+}
+
+fn timestamp(metadata: &Metadata, field: TimeField) -> Option<SystemTime> {
+    match field {
+        TimeField::Modified => metadata.modified().ok(),
+        TimeField::Accessed => metadata.accessed().ok(),
+        #[cfg(unix)]
+        TimeField::Changed => {
+            use std::os::unix::fs::MetadataExt;
+            let ctime = Duration::new(metadata.ctime().max(0) as u64, metadata.ctime_nsec() as u32);
+            Some(SystemTime::UNIX_EPOCH + ctime)
+        }
+        #[cfg(not(unix))]
+        TimeField::Changed => None,
+    }
+}
+
+fn file_kind(metadata: &Metadata) -> Option<FileKind> {
+    let file_type = metadata.file_type();
+    if file_type.is_file() {
+        return Some(FileKind::File);
+    }
+    if file_type.is_dir() {
+        return Some(FileKind::Dir);
+    }
+    if file_type.is_symlink() {
+        return Some(FileKind::Symlink);
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::FileTypeExt;
+        if file_type.is_fifo() {
+            return Some(FileKind::Fifo);
+        }
+        if file_type.is_socket() {
+            return Some(FileKind::Socket);
+        }
+        if file_type.is_block_device() {
+            return Some(FileKind::Block);
+        }
+        if file_type.is_char_device() {
+            return Some(FileKind::Char);
+        }
+    }
+    None
+}
+
+#[cfg(unix)]
+fn owner(metadata: &Metadata) -> Option<(u32, u32)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((metadata.uid(), metadata.gid()))
+}
+
+#[cfg(not(unix))]
+fn owner(_metadata: &Metadata) -> Option<(u32, u32)> {
+    None
+}
+
+#[cfg(unix)]
+fn is_executable(metadata: &Metadata) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    metadata.permissions().mode() & 0o111 != 0
+}
+
+#[cfg(not(unix))]
+fn is_executable(_metadata: &Metadata) -> bool {
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn matches(expr: &str, path: &Path) -> bool {
+        let metadata = fs::symlink_metadata(path).unwrap();
+        FilterExpr::parse(expr).unwrap().matches(path, &metadata)
+    }
+
+    #[test]
+    fn test_size_and_type() {
+        let temp = TempDir::new().unwrap();
+        let file = temp.path().join("data.bin");
+        fs::write(&file, vec![0u8; 2048]).unwrap();
+
+        assert!(matches("size > 1K and type == file", &file));
+        assert!(matches("size <= 2K", &file));
+        assert!(!matches("size > 1.5K && type != file", &file));
+        assert!(matches("type == dir", temp.path()));
+    }
+
+    #[test]
+    fn test_age_and_precedence() {
+        let temp = TempDir::new().unwrap();
+        let file = temp.path().join("old.log");
+        fs::write(&file, "x").unwrap();
+        let old = filetime::FileTime::from_unix_time(1_000_000_000, 0);
+        filetime::set_file_mtime(&file, old).unwrap();
+
+        assert!(matches("mtime > 7d", &file));
+        assert!(!matches("mtime < 7d", &file));
+        // `and` binds tighter than `or`
+        assert!(matches(
+            "mtime < 1h and size > 1G or name == '*.log'",
+            &file
+        ));
+        assert!(!matches(
+            "mtime < 1h and (size > 1G or name == '*.log')",
+            &file
+        ));
+    }
+
+    #[test]
+    fn test_executable_and_owner() {
+        use std::os::unix::fs::{MetadataExt, PermissionsExt};
+
+        let temp = TempDir::new().unwrap();
+        let script = temp.path().join("run.sh");
+        fs::write(&script, "#!/bin/sh").unwrap();
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+        let uid = fs::metadata(&script).unwrap().uid();
+
+        assert!(matches("executable", &script));
+        assert!(!matches("not executable", &script));
+        assert!(matches(
+            &format!("uid == {} and !(uid != {})", uid, uid),
+            &script
+        ));
+    }
+
+    #[test]
+    fn test_compile_errors() {
+        for expr in [
+            "",
+            "size >",
+            "size > 10Q",
+            "colour == red",
+            "type < file",
+            "(size > 1",
+            "size > 1 1",
+            "name == 'x",
+            "user == no-such-user-cpx",
+        ] {
+            assert!(
+                matches!(
+                    FilterExpr::parse(expr),
+                    Err(ExcludeError::InvalidExpression(_))
+                ),
+                "{}",
+                expr
+            );
+        }
+    }
+}
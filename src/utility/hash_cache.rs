@@ -0,0 +1,257 @@
+use super::preprocess::calculate_checksum;
+use crate::cli::args::HashCacheMode;
+use std::io;
+use std::path::Path;
+
+pub const HASH_CACHE_XATTR: &str = "user.cpx.xxh3";
+const HASH_CACHE_VERSION: &str = "v2";
+
+/// How far a file's ctime may be from the moment its entry was written. Storing the
+/// entry itself moves the ctime, so it can't be recorded exactly; the slack covers
+/// filesystems that keep whole-second timestamps.
+const CTIME_SLACK_NSEC: i128 = 1_000_000_000;
+
+/// The file state a cached hash was computed for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct FileStamp {
+    size: u64,
+    mtime_sec: i64,
+    mtime_nsec: i64,
+    inode: u64,
+    ctime_sec: i64,
+    ctime_nsec: i64,
+}
+
+impl FileStamp {
+    #[cfg(unix)]
+    fn from_metadata(metadata: &std::fs::Metadata) -> Self {
+        use std::os::unix::fs::MetadataExt;
+        Self {
+            size: metadata.len(),
+            mtime_sec: metadata.mtime(),
+            mtime_nsec: metadata.mtime_nsec(),
+            inode: metadata.ino(),
+            ctime_sec: metadata.ctime(),
+            ctime_nsec: metadata.ctime_nsec(),
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn from_metadata(metadata: &std::fs::Metadata) -> Self {
+        Self {
+            size: metadata.len(),
+            mtime_sec: 0,
+            mtime_nsec: 0,
+            inode: 0,
+            ctime_sec: 0,
+            ctime_nsec: 0,
+        }
+    }
+
+    /// The stamp to store once the hash is written, with the ctime the write gives it.
+    fn stored_now(self) -> Self {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default();
+        Self {
+            ctime_sec: now.as_secs() as i64,
+            ctime_nsec: now.subsec_nanos() as i64,
+            ..self
+        }
+    }
+
+    /// Whether a file stamped `current` is still the file this cached stamp describes.
+    /// Any write, truncate or chmod moves the ctime, even when the mtime is put back.
+    fn still_matches(&self, current: &FileStamp) -> bool {
+        let ctime =
+            |stamp: &FileStamp| stamp.ctime_sec as i128 * 1_000_000_000 + stamp.ctime_nsec as i128;
+        self.size == current.size
+            && self.mtime_sec == current.mtime_sec
+            && self.mtime_nsec == current.mtime_nsec
+            && self.inode == current.inode
+            && (ctime(current) - ctime(self)).abs() <= CTIME_SLACK_NSEC
+    }
+}
+
+fn encode_entry(stamp: &FileStamp, hash: u64) -> String {
+    format!(
+        "{}:{}:{}.{:09}:{}:{}.{:09}:{:016x}",
+        HASH_CACHE_VERSION,
+        stamp.size,
+        stamp.mtime_sec,
+        stamp.mtime_nsec,
+        stamp.inode,
+        stamp.ctime_sec,
+        stamp.ctime_nsec,
+        hash
+    )
+}
+
+fn decode_entry(value: &[u8]) -> Option<(FileStamp, u64)> {
+    let value = std::str::from_utf8(value).ok()?;
+    let mut parts = value.split(':');
+    if parts.next()? != HASH_CACHE_VERSION {
+        return None;
+    }
+    let size = parts.next()?.parse().ok()?;
+    let (mtime_sec, mtime_nsec) = parts.next()?.split_once('.')?;
+    let inode = parts.next()?.parse().ok()?;
+    let (ctime_sec, ctime_nsec) = parts.next()?.split_once('.')?;
+    let hash = u64::from_str_radix(parts.next()?, 16).ok()?;
+    if parts.next().is_some() {
+        return None;
+    }
+    Some((
+        FileStamp {
+            size,
+            mtime_sec: mtime_sec.parse().ok()?,
+            mtime_nsec: mtime_nsec.parse().ok()?,
+            inode,
+            ctime_sec: ctime_sec.parse().ok()?,
+            ctime_nsec: ctime_nsec.parse().ok()?,
+        },
+        hash,
+    ))
+}
+
+/// Return the xxh3 checksum of `path`, consulting and refreshing the cache when enabled.
+pub fn cached_checksum(path: &Path, mode: HashCacheMode) -> io::Result<u64> {
+    match mode {
+        HashCacheMode::None => calculate_checksum(path),
+        HashCacheMode::Xattr => xattr_checksum(path),
+    }
+}
+
+#[cfg(unix)]
+fn xattr_checksum(path: &Path) -> io::Result<u64> {
+    if !xattr::SUPPORTED_PLATFORM {
+        return calculate_checksum(path);
+    }
+
+    let stamp = FileStamp::from_metadata(&std::fs::metadata(path)?);
+
+    if let Ok(Some(value)) = xattr::get(path, HASH_CACHE_XATTR)
+        && let Some((cached_stamp, hash)) = decode_entry(&value)
+        && cached_stamp.still_matches(&stamp)
+    {
+        return Ok(hash);
+    }
+
+    let hash = calculate_checksum(path)?;
+
+    // Only store the hash if the file did not change while we were reading it
+    let after = FileStamp::from_metadata(&std::fs::metadata(path)?);
+    if after == stamp {
+        // Read-only files or filesystems without user xattrs simply stay uncached
+        let _ = xattr::set(
+            path,
+            HASH_CACHE_XATTR,
+            encode_entry(&stamp.stored_now(), hash).as_bytes(),
+        );
+    }
+
+    Ok(hash)
+}
+
+#[cfg(not(unix))]
+fn xattr_checksum(path: &Path) -> io::Result<u64> {
+    calculate_checksum(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn xattrs_supported(path: &Path) -> bool {
+        xattr::SUPPORTED_PLATFORM && xattr::set(path, "user.cpx.probe", b"1").is_ok()
+    }
+
+    #[test]
+    fn test_entry_roundtrip() {
+        let stamp = FileStamp {
+            size: 42,
+            mtime_sec: 1_700_000_000,
+            mtime_nsec: 5,
+            inode: 99,
+            ctime_sec: 1_700_000_100,
+            ctime_nsec: 7,
+        };
+        let encoded = encode_entry(&stamp, 0xdead_beef);
+        assert_eq!(decode_entry(encoded.as_bytes()), Some((stamp, 0xdead_beef)));
+    }
+
+    #[test]
+    fn test_decode_rejects_unknown_version() {
+        assert_eq!(decode_entry(b"v0:1:2.000000000:3:ff"), None);
+        assert_eq!(decode_entry(b"v1:1:2.000000000:3:ff"), None);
+        assert_eq!(decode_entry(b"garbage"), None);
+    }
+
+    #[test]
+    fn test_cached_checksum_matches_uncached() {
+        let temp_dir = TempDir::new().unwrap();
+        let file = temp_dir.path().join("file.bin");
+        fs::write(&file, b"some content").unwrap();
+
+        let uncached = cached_checksum(&file, HashCacheMode::None).unwrap();
+        let cached = cached_checksum(&file, HashCacheMode::Xattr).unwrap();
+        assert_eq!(uncached, cached);
+    }
+
+    #[test]
+    fn test_stale_entry_is_recomputed() {
+        let temp_dir = TempDir::new().unwrap();
+        let file = temp_dir.path().join("file.bin");
+        fs::write(&file, b"first").unwrap();
+        if !xattrs_supported(&file) {
+            return;
+        }
+
+        let first = cached_checksum(&file, HashCacheMode::Xattr).unwrap();
+        assert!(xattr::get(&file, HASH_CACHE_XATTR).unwrap().is_some());
+
+        fs::write(&file, b"second, longer").unwrap();
+        let second = cached_checksum(&file, HashCacheMode::Xattr).unwrap();
+
+        assert_ne!(first, second);
+        assert_eq!(second, calculate_checksum(&file).unwrap());
+    }
+
+    #[test]
+    fn test_fresh_entry_is_reused() {
+        let temp_dir = TempDir::new().unwrap();
+        let file = temp_dir.path().join("file.bin");
+        fs::write(&file, b"content").unwrap();
+        if !xattrs_supported(&file) {
+            return;
+        }
+
+        let stamp = FileStamp::from_metadata(&fs::metadata(&file).unwrap()).stored_now();
+        xattr::set(&file, HASH_CACHE_XATTR, encode_entry(&stamp, 7).as_bytes()).unwrap();
+
+        // A matching stamp is trusted without rereading the data
+        assert_eq!(cached_checksum(&file, HashCacheMode::Xattr).unwrap(), 7);
+    }
+
+    #[test]
+    fn test_entry_older_than_ctime_is_recomputed() {
+        let temp_dir = TempDir::new().unwrap();
+        let file = temp_dir.path().join("file.bin");
+        fs::write(&file, b"content").unwrap();
+        if !xattrs_supported(&file) {
+            return;
+        }
+
+        // Same size, mtime and inode, as after a rewrite that put the mtime back
+        let mut stamp = FileStamp::from_metadata(&fs::metadata(&file).unwrap()).stored_now();
+        stamp.ctime_sec -= 60;
+        xattr::set(&file, HASH_CACHE_XATTR, encode_entry(&stamp, 7).as_bytes()).unwrap();
+
+        assert_eq!(
+            cached_checksum(&file, HashCacheMode::Xattr).unwrap(),
+            calculate_checksum(&file).unwrap()
+        );
+    }
+}
@@ -1,28 +1,130 @@
 use super::preprocess::{SymlinkKind, SymlinkTask};
 use super::progress_bar::{ProgressBarStyle, ProgressOptions};
-use crate::cli::args::{BackupMode, CopyOptions, FollowSymlink, ReflinkMode, SymlinkMode};
+use crate::cli::args::{
+    BackupMode, CopyOptions, FollowSymlink, HashCacheMode, ReflinkMode, SourceChangeMode,
+    SpecialMode, SymlinkMode,
+};
 use crate::config::schema::Config;
 use crate::error::{CopyError, CopyResult};
-use crate::utility::preprocess::HardlinkTask;
+use crate::utility::preprocess::{HardlinkTask, SpecialTask};
+use std::collections::HashMap;
 use std::io;
 use std::path::{Path, PathBuf};
 
-pub fn create_directories(dirs: &[crate::utility::preprocess::DirectoryTask]) -> io::Result<()> {
+/// Create the planned directories, parents first.
+///
+/// New directories get their source's permission bits minus the umask, like `cp`.
+/// Owner rwx is kept while contents are copied in; the modes that lack it are
+/// returned so they can be applied once the directories are filled.
+pub fn create_directories(
+    dirs: &[crate::utility::preprocess::DirectoryTask],
+    umask: u32,
+) -> io::Result<HashMap<PathBuf, u32>> {
     let mut dirs: Vec<_> = dirs.iter().collect();
     dirs.sort_unstable_by_key(|d| d.destination.components().count());
     dirs.dedup_by_key(|d| &d.destination);
 
+    let mut deferred_modes = HashMap::new();
     for dir in &dirs {
-        match std::fs::create_dir(&dir.destination) {
-            Ok(()) => {}
-            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {}
-            Err(e) if e.kind() == io::ErrorKind::NotFound => {
-                std::fs::create_dir_all(&dir.destination)?;
+        let source_mode = dir
+            .source
+            .as_ref()
+            .and_then(|source| std::fs::metadata(source).ok())
+            .map(|metadata| permission_bits(&metadata));
+
+        let mut builder = std::fs::DirBuilder::new();
+        #[cfg(unix)]
+        if let Some(mode) = source_mode {
+            use std::os::unix::fs::DirBuilderExt;
+            builder.mode(mode | 0o700);
+        }
+
+        let mut created = builder.create(&dir.destination);
+        if let Err(e) = &created
+            && e.kind() == io::ErrorKind::NotFound
+            && let Some(parent) = dir.destination.parent()
+        {
+            // Parents outside the plan, e.g. a destination that doesn't exist yet
+            std::fs::create_dir_all(parent)?;
+            created = builder.create(&dir.destination);
+        }
+
+        match created {
+            Ok(()) => {
+                if let Some(mode) = source_mode
+                    && mode & 0o700 != 0o700
+                {
+                    deferred_modes.insert(dir.destination.clone(), mode & !umask);
+                }
             }
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {}
             Err(e) => return Err(e),
         }
     }
-    Ok(())
+    Ok(deferred_modes)
+}
+
+/// Read/write/execute bits of a source, without setuid, setgid and sticky.
+pub fn permission_bits(metadata: &std::fs::Metadata) -> u32 {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        metadata.permissions().mode() & 0o777
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = metadata;
+        0o666
+    }
+}
+
+/// The process umask, read once by `CopyOptions` before any copy threads exist.
+///
+/// Linux reports it in /proc/self/status. Elsewhere it can only be read by setting it,
+/// which would briefly change the mask for files other threads create.
+pub fn process_umask() -> u32 {
+    #[cfg(target_os = "linux")]
+    if let Ok(status) = std::fs::read_to_string("/proc/self/status")
+        && let Some(umask) = status
+            .lines()
+            .find_map(|line| line.strip_prefix("Umask:"))
+            .and_then(|value| u32::from_str_radix(value.trim(), 8).ok())
+    {
+        return umask;
+    }
+    #[cfg(unix)]
+    {
+        let umask = unsafe {
+            let umask = libc::umask(0o022);
+            libc::umask(umask);
+            umask
+        };
+        #[allow(clippy::unnecessary_cast)]
+        let umask = umask as u32;
+        umask
+    }
+    #[cfg(not(unix))]
+    {
+        0
+    }
+}
+
+/// Open a destination for writing the way `cp` does: a new file is created with the
+/// source's permission bits (the kernel applies the umask), an existing one keeps its mode.
+pub fn create_destination(
+    destination: &Path,
+    source_metadata: &std::fs::Metadata,
+) -> io::Result<std::fs::File> {
+    let mut open_options = std::fs::OpenOptions::new();
+    open_options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        open_options.mode(permission_bits(source_metadata));
+    }
+    #[cfg(not(unix))]
+    let _ = source_metadata;
+    open_options.open(destination)
 }
 
 pub fn create_symlink(task: &SymlinkTask, options: &CopyOptions) -> io::Result<()> {
@@ -101,6 +203,75 @@ pub fn create_hardlink(task: &HardlinkTask, options: &CopyOptions) -> CopyResult
     Ok(())
 }
 
+pub fn create_special(task: &SpecialTask, options: &CopyOptions) -> io::Result<()> {
+    if task.destination.is_symlink() || task.destination.try_exists().unwrap_or(false) {
+        if options.interactive && !prompt_overwrite(&task.destination)? {
+            return Ok(());
+        }
+        if options.force || options.remove_destination || options.resume {
+            std::fs::remove_file(&task.destination)?;
+        } else {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("destination already exists: {:?}", task.destination),
+            ));
+        }
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+
+        let dest_cstring = std::ffi::CString::new(task.destination.as_os_str().as_bytes())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        // mknod handles FIFOs and sockets as well as device nodes
+        let result = unsafe {
+            libc::mknod(
+                dest_cstring.as_ptr(),
+                task.mode as libc::mode_t,
+                task.rdev as libc::dev_t,
+            )
+        };
+        if result != 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+
+    #[cfg(not(unix))]
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "special files are only supported on Unix",
+        ));
+    }
+
+    #[allow(unreachable_code)]
+    Ok(())
+}
+
+/// Bytes available to unprivileged users on the filesystem holding `path`.
+#[cfg(unix)]
+pub fn available_space(path: &Path) -> io::Result<u64> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let path_cstring = std::ffi::CString::new(path.as_os_str().as_bytes())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(path_cstring.as_ptr(), &mut stat) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    #[allow(clippy::unnecessary_cast)]
+    Ok(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+#[cfg(not(unix))]
+pub fn available_space(_path: &Path) -> io::Result<u64> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "free space can only be queried on Unix",
+    ))
+}
+
 pub fn prompt_overwrite(path: &Path) -> io::Result<bool> {
     use std::io::{Write, stdin, stdout};
 
@@ -125,6 +296,14 @@ pub fn with_parents(dest: &Path, source: &Path) -> PathBuf {
     dest.join(relative)
 }
 
+/// Like [`with_parents`], but relative to `base` for sources under it.
+pub fn with_parents_from(dest: &Path, source: &Path, base: Option<&Path>) -> PathBuf {
+    match base.and_then(|base| source.strip_prefix(base).ok()) {
+        Some(relative) => dest.join(relative),
+        None => with_parents(dest, source),
+    }
+}
+
 pub fn truncate_filename(filename: &str, max_len: usize) -> String {
     if filename.len() <= max_len {
         filename.to_string()
@@ -189,6 +368,29 @@ pub fn parse_reflink_mode(s: &str) -> Option<ReflinkMode> {
     }
 }
 
+pub fn parse_special_mode(s: &str) -> SpecialMode {
+    match s {
+        "skip" => SpecialMode::Skip,
+        "read" => SpecialMode::Read,
+        _ => SpecialMode::Recreate,
+    }
+}
+
+pub fn parse_source_change_mode(s: &str) -> SourceChangeMode {
+    match s {
+        "retry" => SourceChangeMode::Retry,
+        "fail" => SourceChangeMode::Fail,
+        _ => SourceChangeMode::Warn,
+    }
+}
+
+pub fn parse_hash_cache_mode(s: &str) -> HashCacheMode {
+    match s {
+        "xattr" => HashCacheMode::Xattr,
+        _ => HashCacheMode::None,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -317,6 +519,7 @@ mod tests {
             source: source.clone(),
             destination: dest.clone(),
             kind: SymlinkKind::AbsoluteToSource,
+            origin: None,
         };
 
         create_symlink(&task, &options).unwrap();
@@ -344,6 +547,7 @@ mod tests {
             source: source.clone(),
             destination: dest.clone(),
             kind: SymlinkKind::RelativeToSource,
+            origin: None,
         };
 
         create_symlink(&task, &options).unwrap();
@@ -371,6 +575,7 @@ mod tests {
             source: source_dir.clone(),
             destination: dest_link.clone(),
             kind: SymlinkKind::AbsoluteToSource,
+            origin: None,
         };
 
         create_symlink(&task, &options).unwrap();
@@ -396,6 +601,7 @@ mod tests {
             source: source.clone(),
             destination: dest.clone(),
             kind: SymlinkKind::RelativeToSource,
+            origin: None,
         };
 
         create_symlink(&task, &options).unwrap();
@@ -418,6 +624,7 @@ mod tests {
             source: source.clone(),
             destination: dest.clone(),
             kind: SymlinkKind::AbsoluteToSource,
+            origin: None,
         };
 
         let result = create_symlink(&task, &options);
@@ -436,10 +643,24 @@ mod tests {
             source: source.clone(),
             destination: dest.clone(),
             kind: SymlinkKind::RelativeToSource,
+            origin: None,
         };
 
         create_symlink(&task, &options).unwrap();
         assert!(dest.symlink_metadata().unwrap().is_symlink());
         assert!(dest.metadata().is_err());
     }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_process_umask_reads_proc_status() {
+        let current = unsafe {
+            let umask = libc::umask(0o022);
+            libc::umask(umask);
+            umask
+        };
+        #[allow(clippy::unnecessary_cast)]
+        let current = current as u32;
+        assert_eq!(process_umask(), current);
+    }
 }
@@ -0,0 +1,299 @@
+use globset::{Glob, GlobMatcher};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+#[cfg(unix)]
+use nix::unistd::{Gid, Group, Uid, User};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum IdKind {
+    User,
+    Group,
+}
+
+impl IdKind {
+    #[cfg(unix)]
+    fn lookup_id(self, name: &str) -> Option<u32> {
+        match self {
+            IdKind::User => User::from_name(name).ok().flatten().map(|u| u.uid.as_raw()),
+            IdKind::Group => Group::from_name(name)
+                .ok()
+                .flatten()
+                .map(|g| g.gid.as_raw()),
+        }
+    }
+
+    #[cfg(unix)]
+    fn lookup_name(self, id: u32) -> Option<String> {
+        match self {
+            IdKind::User => User::from_uid(Uid::from_raw(id))
+                .ok()
+                .flatten()
+                .map(|u| u.name),
+            IdKind::Group => Group::from_gid(Gid::from_raw(id))
+                .ok()
+                .flatten()
+                .map(|g| g.name),
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn lookup_id(self, _name: &str) -> Option<u32> {
+        None
+    }
+
+    #[cfg(not(unix))]
+    fn lookup_name(self, _id: u32) -> Option<String> {
+        None
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            IdKind::User => "user",
+            IdKind::Group => "group",
+        }
+    }
+}
+
+pub fn user_name(uid: u32) -> Option<String> {
+    IdKind::User.lookup_name(uid)
+}
+
+pub fn group_name(gid: u32) -> Option<String> {
+    IdKind::Group.lookup_name(gid)
+}
+
+pub fn user_id(name: &str) -> Option<u32> {
+    IdKind::User.lookup_id(name)
+}
+
+pub fn group_id(name: &str) -> Option<u32> {
+    IdKind::Group.lookup_id(name)
+}
+
+#[derive(Debug)]
+enum IdPattern {
+    Any,
+    Range(u32, u32),
+    Name(GlobMatcher),
+}
+
+#[derive(Debug)]
+struct IdRule {
+    from: IdPattern,
+    to: u32,
+}
+
+/// Ordered `FROM:TO` rules for one of uid or gid; the first matching rule wins.
+///
+/// `FROM` is `*`, an id, an id range `LOW-HIGH`, or a name that may contain wildcards.
+/// Names are matched against the name the id has on this system, unless ids are
+/// numeric, in which case plain names are resolved to ids up front.
+#[derive(Debug)]
+pub struct IdMap {
+    kind: IdKind,
+    numeric: bool,
+    rules: Vec<IdRule>,
+    names: Mutex<HashMap<u32, Option<String>>>,
+}
+
+impl IdMap {
+    fn new(kind: IdKind, numeric: bool) -> Self {
+        Self {
+            kind,
+            numeric,
+            rules: Vec::new(),
+            names: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn parse(kind: IdKind, specs: &[String], numeric: bool) -> Result<Self, String> {
+        let mut map = Self::new(kind, numeric);
+        for spec in specs {
+            for rule in spec.split(',').map(str::trim).filter(|r| !r.is_empty()) {
+                let (from, to) = rule.rsplit_once(':').ok_or_else(|| {
+                    format!(
+                        "invalid {}map rule '{}': expected FROM:TO",
+                        kind.label(),
+                        rule
+                    )
+                })?;
+                let rule = IdRule {
+                    from: map.parse_pattern(from)?,
+                    to: map.resolve(to)?,
+                };
+                map.rules.push(rule);
+            }
+        }
+        Ok(map)
+    }
+
+    fn parse_pattern(&self, from: &str) -> Result<IdPattern, String> {
+        if from == "*" {
+            return Ok(IdPattern::Any);
+        }
+        if let Ok(id) = from.parse::<u32>() {
+            return Ok(IdPattern::Range(id, id));
+        }
+        if let Some((low, high)) = from.split_once('-')
+            && let (Ok(low), Ok(high)) = (low.parse::<u32>(), high.parse::<u32>())
+        {
+            return Ok(IdPattern::Range(low, high));
+        }
+
+        let has_glob_chars = from.contains(['*', '?', '[']);
+        if self.numeric {
+            if has_glob_chars {
+                return Err(format!(
+                    "{} pattern '{}' needs name-based mapping, drop --numeric-ids",
+                    self.kind.label(),
+                    from
+                ));
+            }
+            let id = self.resolve(from)?;
+            return Ok(IdPattern::Range(id, id));
+        }
+
+        let matcher = Glob::new(from)
+            .map_err(|e| format!("invalid {} pattern '{}': {}", self.kind.label(), from, e))?
+            .compile_matcher();
+        Ok(IdPattern::Name(matcher))
+    }
+
+    fn resolve(&self, name: &str) -> Result<u32, String> {
+        if let Ok(id) = name.parse::<u32>() {
+            return Ok(id);
+        }
+        self.kind
+            .lookup_id(name)
+            .ok_or_else(|| format!("unknown {} '{}'", self.kind.label(), name))
+    }
+
+    fn name_of(&self, id: u32) -> Option<String> {
+        let mut names = self.names.lock().ok()?;
+        names
+            .entry(id)
+            .or_insert_with(|| self.kind.lookup_name(id))
+            .clone()
+    }
+
+    pub fn map(&self, id: u32) -> u32 {
+        for rule in &self.rules {
+            let matched = match &rule.from {
+                IdPattern::Any => true,
+                IdPattern::Range(low, high) => (*low..=*high).contains(&id),
+                IdPattern::Name(matcher) => {
+                    self.name_of(id).is_some_and(|name| matcher.is_match(name))
+                }
+            };
+            if matched {
+                return rule.to;
+            }
+        }
+        id
+    }
+}
+
+/// Owner and group rewriting from `--chown`, `--usermap` and `--groupmap`.
+#[derive(Debug)]
+pub struct OwnerMap {
+    pub users: IdMap,
+    pub groups: IdMap,
+}
+
+impl OwnerMap {
+    pub fn parse(
+        usermap: &[String],
+        groupmap: &[String],
+        chown: Option<&str>,
+        numeric: bool,
+    ) -> Result<Self, String> {
+        let mut users = IdMap::parse(IdKind::User, usermap, numeric)?;
+        let mut groups = IdMap::parse(IdKind::Group, groupmap, numeric)?;
+
+        // --chown is a catch-all rule placed ahead of the maps
+        if let Some(spec) = chown {
+            let (user, group) = spec.split_once(':').unwrap_or((spec, ""));
+            if user.is_empty() && group.is_empty() {
+                return Err(format!("invalid --chown '{}': expected USER:GROUP", spec));
+            }
+            if !user.is_empty() {
+                let to = users.resolve(user)?;
+                users.rules.insert(
+                    0,
+                    IdRule {
+                        from: IdPattern::Any,
+                        to,
+                    },
+                );
+            }
+            if !group.is_empty() {
+                let to = groups.resolve(group)?;
+                groups.rules.insert(
+                    0,
+                    IdRule {
+                        from: IdPattern::Any,
+                        to,
+                    },
+                );
+            }
+        }
+
+        Ok(Self { users, groups })
+    }
+
+    pub fn map(&self, uid: u32, gid: u32) -> (u32, u32) {
+        (self.users.map(uid), self.groups.map(gid))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn specs(rules: &[&str]) -> Vec<String> {
+        rules.iter().map(|r| r.to_string()).collect()
+    }
+
+    #[test]
+    fn test_numeric_and_range_rules() {
+        let map = OwnerMap::parse(&specs(&["1000:2000,1001-1999:3000"]), &[], None, false).unwrap();
+        assert_eq!(map.map(1000, 10), (2000, 10));
+        assert_eq!(map.map(1500, 10), (3000, 10));
+        assert_eq!(map.map(2500, 10), (2500, 10));
+    }
+
+    #[test]
+    fn test_first_matching_rule_wins() {
+        let map = OwnerMap::parse(&[], &specs(&["5:6", "*:7"]), None, false).unwrap();
+        assert_eq!(map.map(0, 5), (0, 6));
+        assert_eq!(map.map(0, 9), (0, 7));
+    }
+
+    #[test]
+    fn test_name_rules() {
+        let map = OwnerMap::parse(&specs(&["ro?t:4242"]), &[], None, false).unwrap();
+        assert_eq!(map.map(0, 0), (4242, 0));
+
+        // With numeric ids, names are resolved to ids, and wildcards can't be
+        let map = OwnerMap::parse(&specs(&["root:4242"]), &[], None, true).unwrap();
+        assert_eq!(map.map(0, 0), (4242, 0));
+        assert!(OwnerMap::parse(&specs(&["ro*:1"]), &[], None, true).is_err());
+    }
+
+    #[test]
+    fn test_chown_overrides_maps() {
+        let map = OwnerMap::parse(&specs(&["0:5"]), &[], Some("root:"), false).unwrap();
+        assert_eq!(map.map(0, 33), (0, 33));
+
+        let map = OwnerMap::parse(&[], &[], Some(":100"), false).unwrap();
+        assert_eq!(map.map(1000, 1000), (1000, 100));
+        assert!(OwnerMap::parse(&[], &[], Some(":"), false).is_err());
+    }
+
+    #[test]
+    fn test_invalid_rules() {
+        assert!(OwnerMap::parse(&specs(&["1000"]), &[], None, false).is_err());
+        assert!(OwnerMap::parse(&specs(&["1000:no-such-user-cpx"]), &[], None, false).is_err());
+    }
+}
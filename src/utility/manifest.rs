@@ -0,0 +1,478 @@
+use clap::ValueEnum;
+use sha2::{Digest, Sha256};
+use std::fmt::Write as _;
+use std::io::{self, BufRead, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use xxhash_rust::xxh3::Xxh3;
+
+#[derive(Debug, Clone, Copy, Default, ValueEnum, PartialEq)]
+pub enum HashAlgorithm {
+    Xxh3,
+    #[default]
+    Sha256,
+    Blake3,
+}
+
+impl HashAlgorithm {
+    /// The tag of BSD-style `ALGO (name) = digest` lines. `b3sum -c` only reads
+    /// untagged lines, so blake3 manifests are recognised by their digests instead.
+    fn tag(&self) -> Option<&'static str> {
+        match self {
+            HashAlgorithm::Xxh3 => Some("XXH3"),
+            HashAlgorithm::Sha256 => Some("SHA256"),
+            HashAlgorithm::Blake3 => None,
+        }
+    }
+
+    fn from_tag(tag: &[u8]) -> Option<Self> {
+        [HashAlgorithm::Xxh3, HashAlgorithm::Sha256]
+            .into_iter()
+            .find(|algorithm| algorithm.tag().map(str::as_bytes) == Some(tag))
+    }
+
+    fn digest_len(&self) -> usize {
+        match self {
+            HashAlgorithm::Xxh3 => 16,
+            HashAlgorithm::Sha256 | HashAlgorithm::Blake3 => 64,
+        }
+    }
+}
+
+/// Incremental hasher fed with the same buffers that are written to the destination.
+pub enum StreamHasher {
+    Xxh3(Box<Xxh3>),
+    Sha256(Sha256),
+    Blake3(Box<blake3::Hasher>),
+}
+
+impl StreamHasher {
+    pub fn new(algorithm: HashAlgorithm) -> Self {
+        match algorithm {
+            HashAlgorithm::Xxh3 => StreamHasher::Xxh3(Box::new(Xxh3::new())),
+            HashAlgorithm::Sha256 => StreamHasher::Sha256(Sha256::new()),
+            HashAlgorithm::Blake3 => StreamHasher::Blake3(Box::new(blake3::Hasher::new())),
+        }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        match self {
+            StreamHasher::Xxh3(h) => h.update(data),
+            StreamHasher::Sha256(h) => h.update(data),
+            StreamHasher::Blake3(h) => {
+                h.update(data);
+            }
+        }
+    }
+
+    pub fn finalize_hex(self) -> String {
+        match self {
+            StreamHasher::Xxh3(h) => format!("{:016x}", h.digest()),
+            StreamHasher::Sha256(h) => to_hex(&h.finalize()),
+            StreamHasher::Blake3(h) => h.finalize().to_hex().to_string(),
+        }
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len() * 2);
+    for byte in bytes {
+        let _ = write!(out, "{:02x}", byte);
+    }
+    out
+}
+
+pub fn hash_file(path: &Path, algorithm: HashAlgorithm) -> io::Result<String> {
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = StreamHasher::new(algorithm);
+    let mut buffer = vec![0u8; 128 * 1024];
+
+    loop {
+        let bytes_read = file.read(&mut buffer)?;
+        if bytes_read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..bytes_read]);
+    }
+
+    Ok(hasher.finalize_hex())
+}
+
+/// Collects digests of copied files and writes them in the format `sha256sum -c` and
+/// `b3sum -c` read.
+#[derive(Debug)]
+pub struct Manifest {
+    pub path: PathBuf,
+    pub algorithm: HashAlgorithm,
+    base: PathBuf,
+    entries: Mutex<Vec<(PathBuf, String)>>,
+}
+
+impl Manifest {
+    /// Entries are written relative to `base`, normally the destination directory.
+    pub fn new(path: PathBuf, algorithm: HashAlgorithm, base: PathBuf) -> Self {
+        Self {
+            path,
+            algorithm,
+            base,
+            entries: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn record(&self, destination: &Path, digest: String) {
+        let relative = destination
+            .strip_prefix(&self.base)
+            .unwrap_or(destination)
+            .to_path_buf();
+        if let Ok(mut entries) = self.entries.lock() {
+            entries.push((relative, digest));
+        }
+    }
+
+    pub fn record_file(&self, destination: &Path) -> io::Result<()> {
+        let digest = hash_file(destination, self.algorithm)?;
+        self.record(destination, digest);
+        Ok(())
+    }
+
+    pub fn write(&self) -> io::Result<()> {
+        let mut entries = self
+            .entries
+            .lock()
+            .map_err(|_| io::Error::other("Failed to acquire manifest lock"))?
+            .clone();
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+        entries.dedup_by(|a, b| a.0 == b.0);
+
+        let mut out = io::BufWriter::new(std::fs::File::create(&self.path)?);
+        for (path, digest) in &entries {
+            out.write_all(&format_line(self.algorithm, digest, path))?;
+        }
+        out.flush()
+    }
+}
+
+/// Format a line the way coreutils does, escaping names containing `\` or newlines.
+fn format_line(algorithm: HashAlgorithm, digest: &str, path: &Path) -> Vec<u8> {
+    let name = path_bytes(path);
+    let escaped = name.contains(&b'\\') || name.contains(&b'\n');
+    let mut line = Vec::with_capacity(name.len() + digest.len() + 16);
+    if escaped {
+        line.push(b'\\');
+    }
+    let push_name = |line: &mut Vec<u8>| {
+        for &byte in name.iter() {
+            match byte {
+                b'\\' => line.extend_from_slice(b"\\\\"),
+                b'\n' => line.extend_from_slice(b"\\n"),
+                _ => line.push(byte),
+            }
+        }
+    };
+    match algorithm.tag() {
+        Some(tag) => {
+            line.extend_from_slice(format!("{} (", tag).as_bytes());
+            push_name(&mut line);
+            line.extend_from_slice(format!(") = {}\n", digest).as_bytes());
+        }
+        None => {
+            line.extend_from_slice(format!("{}  ", digest).as_bytes());
+            push_name(&mut line);
+            line.push(b'\n');
+        }
+    }
+    line
+}
+
+/// A manifest entry: the algorithm its tag names, if any, the digest and the path.
+type ManifestEntry = (Option<HashAlgorithm>, String, PathBuf);
+
+fn parse_line(line: &[u8]) -> Option<ManifestEntry> {
+    let (escaped, line) = match line.strip_prefix(b"\\") {
+        Some(rest) => (true, rest),
+        None => (false, line),
+    };
+
+    let (algorithm, digest, name) = match parse_tagged(line) {
+        Some(tagged) => tagged,
+        None => {
+            let split = line.iter().position(|&byte| byte == b' ')?;
+            let (digest, rest) = (&line[..split], &line[split + 1..]);
+            // GNU tools use ' ' for text mode and '*' for binary mode
+            let name = rest
+                .strip_prefix(b" ")
+                .or_else(|| rest.strip_prefix(b"*"))?;
+            (None, digest, name)
+        }
+    };
+    if digest.is_empty() || name.is_empty() {
+        return None;
+    }
+    let digest = std::str::from_utf8(digest).ok()?.to_ascii_lowercase();
+
+    let name = if escaped {
+        let mut out = Vec::with_capacity(name.len());
+        let mut bytes = name.iter();
+        while let Some(&byte) = bytes.next() {
+            if byte == b'\\' {
+                match bytes.next() {
+                    Some(b'n') => out.push(b'\n'),
+                    Some(b'\\') => out.push(b'\\'),
+                    _ => return None,
+                }
+            } else {
+                out.push(byte);
+            }
+        }
+        out
+    } else {
+        name.to_vec()
+    };
+
+    Some((algorithm, digest, path_from_bytes(name)))
+}
+
+/// Split a BSD-style `ALGO (name) = digest` line.
+fn parse_tagged(line: &[u8]) -> Option<(Option<HashAlgorithm>, &[u8], &[u8])> {
+    let open = line.windows(2).position(|pair| pair == b" (")?;
+    let algorithm = HashAlgorithm::from_tag(&line[..open])?;
+    let rest = &line[open + 2..];
+    let close = rest.windows(4).rposition(|quad| quad == b") = ")?;
+    Some((Some(algorithm), &rest[close + 4..], &rest[..close]))
+}
+
+#[cfg(unix)]
+fn path_bytes(path: &Path) -> std::borrow::Cow<'_, [u8]> {
+    use std::os::unix::ffi::OsStrExt;
+    std::borrow::Cow::Borrowed(path.as_os_str().as_bytes())
+}
+
+#[cfg(not(unix))]
+fn path_bytes(path: &Path) -> std::borrow::Cow<'_, [u8]> {
+    match path.to_string_lossy() {
+        std::borrow::Cow::Borrowed(name) => std::borrow::Cow::Borrowed(name.as_bytes()),
+        std::borrow::Cow::Owned(name) => std::borrow::Cow::Owned(name.into_bytes()),
+    }
+}
+
+#[cfg(unix)]
+fn path_from_bytes(bytes: Vec<u8>) -> PathBuf {
+    use std::os::unix::ffi::OsStringExt;
+    PathBuf::from(std::ffi::OsString::from_vec(bytes))
+}
+
+#[cfg(not(unix))]
+fn path_from_bytes(bytes: Vec<u8>) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+#[derive(Debug, Default)]
+pub struct VerifyReport {
+    pub ok: Vec<PathBuf>,
+    pub mismatched: Vec<PathBuf>,
+    pub unreadable: Vec<(PathBuf, String)>,
+    pub malformed_lines: usize,
+}
+
+impl VerifyReport {
+    pub fn is_success(&self) -> bool {
+        self.mismatched.is_empty() && self.unreadable.is_empty() && self.malformed_lines == 0
+    }
+}
+
+/// Check every entry of `manifest` against the files under `dir`.
+///
+/// Tagged lines name their algorithm; untagged ones use `algorithm`, or when it's not
+/// given, whichever algorithm produces digests of their length.
+pub fn verify_manifest(
+    manifest: &Path,
+    dir: &Path,
+    algorithm: Option<HashAlgorithm>,
+) -> io::Result<VerifyReport> {
+    let reader = io::BufReader::new(std::fs::File::open(manifest)?);
+    let mut report = VerifyReport::default();
+
+    for line in reader.split(b'\n') {
+        let line = line?;
+        let line = line.strip_suffix(b"\r").unwrap_or(&line);
+        if line.iter().all(u8::is_ascii_whitespace) {
+            continue;
+        }
+        let Some((tagged, expected, relative)) = parse_line(line) else {
+            report.malformed_lines += 1;
+            continue;
+        };
+        let candidates: Vec<HashAlgorithm> = match tagged.or(algorithm) {
+            Some(algorithm) => vec![algorithm],
+            None => vec![
+                HashAlgorithm::Sha256,
+                HashAlgorithm::Blake3,
+                HashAlgorithm::Xxh3,
+            ],
+        }
+        .into_iter()
+        .filter(|candidate| candidate.digest_len() == expected.len())
+        .collect();
+        if candidates.is_empty() {
+            report.malformed_lines += 1;
+            continue;
+        }
+
+        let path = dir.join(&relative);
+        let mut result = Ok(false);
+        for candidate in candidates {
+            result = hash_file(&path, candidate).map(|actual| actual == expected);
+            if !matches!(result, Ok(false)) {
+                break;
+            }
+        }
+        match result {
+            Ok(true) => report.ok.push(relative),
+            Ok(false) => report.mismatched.push(relative),
+            Err(e) => report.unreadable.push((relative, e.to_string())),
+        }
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_known_digests() {
+        let temp_dir = TempDir::new().unwrap();
+        let file = temp_dir.path().join("abc.txt");
+        fs::write(&file, b"abc").unwrap();
+
+        assert_eq!(
+            hash_file(&file, HashAlgorithm::Sha256).unwrap(),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(
+            hash_file(&file, HashAlgorithm::Blake3).unwrap(),
+            "6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85"
+        );
+        assert_eq!(hash_file(&file, HashAlgorithm::Xxh3).unwrap().len(), 16);
+    }
+
+    #[test]
+    fn test_stream_hasher_matches_single_update() {
+        let mut chunked = StreamHasher::new(HashAlgorithm::Sha256);
+        chunked.update(b"hello ");
+        chunked.update(b"world");
+
+        let mut whole = StreamHasher::new(HashAlgorithm::Sha256);
+        whole.update(b"hello world");
+
+        assert_eq!(chunked.finalize_hex(), whole.finalize_hex());
+    }
+
+    #[test]
+    fn test_line_roundtrip_with_escapes() {
+        let path = PathBuf::from("dir/odd\\name\nwith newline");
+        for algorithm in [HashAlgorithm::Sha256, HashAlgorithm::Blake3] {
+            let line = format_line(algorithm, "abcd", &path);
+            assert!(line.starts_with(b"\\"));
+
+            let parsed = parse_line(line.strip_suffix(b"\n").unwrap()).unwrap();
+            assert_eq!(parsed.1, "abcd");
+            assert_eq!(parsed.2, path);
+        }
+    }
+
+    #[test]
+    fn test_lines_record_the_algorithm() {
+        let path = Path::new("a (1).txt");
+        let line = format_line(HashAlgorithm::Sha256, "abcd", path);
+        assert_eq!(line, b"SHA256 (a (1).txt) = abcd\n");
+        let parsed = parse_line(line.strip_suffix(b"\n").unwrap()).unwrap();
+        assert_eq!(
+            parsed,
+            (
+                Some(HashAlgorithm::Sha256),
+                "abcd".to_string(),
+                path.to_path_buf()
+            )
+        );
+
+        // b3sum only reads untagged lines
+        let line = format_line(HashAlgorithm::Blake3, "abcd", path);
+        assert_eq!(line, b"abcd  a (1).txt\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_line_roundtrip_non_utf8_name() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let path = PathBuf::from(std::ffi::OsStr::from_bytes(b"caf\xe9.txt"));
+        let line = format_line(HashAlgorithm::Sha256, "abcd", &path);
+        let parsed = parse_line(line.strip_suffix(b"\n").unwrap()).unwrap();
+        assert_eq!(parsed.2, path);
+    }
+
+    #[test]
+    fn test_parse_binary_mode_line() {
+        let parsed = parse_line(b"ABCD *file.bin").unwrap();
+        assert_eq!(
+            parsed,
+            (None, "abcd".to_string(), PathBuf::from("file.bin"))
+        );
+        assert!(parse_line(b"no-separator").is_none());
+    }
+
+    #[test]
+    fn test_write_and_verify_manifest() {
+        let temp_dir = TempDir::new().unwrap();
+        let base = temp_dir.path().join("dest");
+        fs::create_dir_all(base.join("sub")).unwrap();
+        fs::write(base.join("a.txt"), b"alpha").unwrap();
+        fs::write(base.join("sub/b.txt"), b"beta").unwrap();
+
+        let manifest_path = temp_dir.path().join("SHA256SUMS");
+        let manifest = Manifest::new(manifest_path.clone(), HashAlgorithm::Sha256, base.clone());
+        manifest.record_file(&base.join("sub/b.txt")).unwrap();
+        manifest.record_file(&base.join("a.txt")).unwrap();
+        manifest.write().unwrap();
+
+        let content = fs::read_to_string(&manifest_path).unwrap();
+        let lines: Vec<&str> = content.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("SHA256 (a.txt) = "));
+        assert!(lines[1].starts_with("SHA256 (sub/b.txt) = "));
+
+        let report = verify_manifest(&manifest_path, &base, None).unwrap();
+        assert!(report.is_success());
+        assert_eq!(report.ok.len(), 2);
+
+        fs::write(base.join("a.txt"), b"tampered").unwrap();
+        let report = verify_manifest(&manifest_path, &base, None).unwrap();
+        assert!(!report.is_success());
+        assert_eq!(report.mismatched, vec![PathBuf::from("a.txt")]);
+    }
+
+    #[test]
+    fn test_verify_untagged_manifest_by_digest() {
+        let temp_dir = TempDir::new().unwrap();
+        let base = temp_dir.path().join("dest");
+        fs::create_dir_all(&base).unwrap();
+        fs::write(base.join("a.txt"), b"alpha").unwrap();
+
+        let manifest_path = temp_dir.path().join("B3SUMS");
+        let manifest = Manifest::new(manifest_path.clone(), HashAlgorithm::Blake3, base.clone());
+        manifest.record_file(&base.join("a.txt")).unwrap();
+        manifest.write().unwrap();
+
+        assert!(
+            verify_manifest(&manifest_path, &base, None)
+                .unwrap()
+                .is_success()
+        );
+        let report = verify_manifest(&manifest_path, &base, Some(HashAlgorithm::Sha256)).unwrap();
+        assert_eq!(report.mismatched, vec![PathBuf::from("a.txt")]);
+    }
+}
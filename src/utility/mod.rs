@@ -1,6 +1,16 @@
 pub mod backup;
+pub mod chmod;
+pub mod events;
 pub mod exclude;
+pub mod exclude_presets;
+pub mod file_list;
+pub mod filter_expr;
+pub mod hash_cache;
 pub mod helper;
+pub mod idmap;
+pub mod manifest;
 pub mod preprocess;
 pub mod preserve;
 pub mod progress_bar;
+pub mod sidecar;
+pub mod xattr_filter;
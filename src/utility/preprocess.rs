@@ -1,12 +1,19 @@
-use super::exclude::should_exclude;
-use super::helper::with_parents;
-use crate::cli::args::{CopyOptions, FollowSymlink, SymlinkMode};
+use super::events::{Event, EventEmitter, SCAN_EVENT_INTERVAL, path_string};
+use super::exclude::{
+    IgnoreStack, MatchCause, MatchReason, Verdict, explain_path, filter_path, should_exclude,
+};
+use super::hash_cache::cached_checksum;
+use super::helper::with_parents_from;
+use super::preserve::has_nodump_flag;
+use crate::cli::args::{CopyOptions, FollowSymlink, HashCacheMode, SpecialMode, SymlinkMode};
 use crate::error::{CopyError, CopyResult};
-use jwalk::WalkDir;
-use std::collections::HashMap;
+use jwalk::WalkDirGeneric;
+use std::collections::{HashMap, HashSet};
 use std::fs::Metadata;
 use std::io;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use xxhash_rust::xxh3::Xxh3;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -35,6 +42,17 @@ pub struct SymlinkTask {
     pub source: PathBuf,
     pub destination: PathBuf,
     pub kind: SymlinkKind,
+    /// The symlink this one is a copy of, whose own attributes can be preserved.
+    pub origin: Option<PathBuf>,
+}
+
+/// A FIFO, socket or device node that is recreated instead of read.
+#[derive(Debug, Clone)]
+pub struct SpecialTask {
+    pub source: PathBuf,
+    pub destination: PathBuf,
+    pub mode: u32,
+    pub rdev: u64,
 }
 
 #[derive(Debug, Clone)]
@@ -49,12 +67,18 @@ pub struct CopyPlan {
     pub directories: Vec<DirectoryTask>,
     pub symlinks: Vec<SymlinkTask>,
     pub hardlinks: Vec<HardlinkTask>,
+    pub specials: Vec<SpecialTask>,
     pub total_size: u64,
     pub total_files: usize,
     pub total_symlinks: usize,
     pub total_hardlinks: usize,
     pub skipped_files: usize,
     pub skipped_size: u64,
+    pub skipped_destinations: Vec<PathBuf>,
+    /// Directories left out because they hold an exclusion marker file
+    pub pruned_directories: usize,
+    /// `--files-from` entries that don't exist; the rest of the list is still copied
+    pub missing_sources: Vec<PathBuf>,
 }
 
 impl Default for CopyPlan {
@@ -70,12 +94,16 @@ impl CopyPlan {
             directories: Vec::new(),
             symlinks: Vec::new(),
             hardlinks: Vec::new(),
+            specials: Vec::new(),
             total_size: 0,
             total_files: 0,
             total_symlinks: 0,
             total_hardlinks: 0,
             skipped_files: 0,
             skipped_size: 0,
+            pruned_directories: 0,
+            skipped_destinations: Vec::new(),
+            missing_sources: Vec::new(),
         }
     }
 
@@ -87,6 +115,7 @@ impl CopyPlan {
     fn remove_existing_task(&mut self, dest: &Path) {
         self.symlinks.retain(|t| t.destination != dest);
         self.hardlinks.retain(|t| t.destination != dest);
+        self.specials.retain(|t| t.destination != dest);
 
         if let Some(pos) = self.files.iter().position(|t| t.destination == dest) {
             let removed = self.files.remove(pos);
@@ -125,6 +154,19 @@ impl CopyPlan {
             source,
             destination,
             kind,
+            origin: None,
+        });
+        self.total_symlinks += 1;
+    }
+
+    /// Plan an exact copy of the symlink at `origin`, which points to `target`.
+    pub fn add_symlink_copy(&mut self, origin: PathBuf, target: PathBuf, destination: PathBuf) {
+        self.remove_existing_task(&destination);
+        self.symlinks.push(SymlinkTask {
+            source: target,
+            destination,
+            kind: SymlinkKind::PreserveExact,
+            origin: Some(origin),
         });
         self.total_symlinks += 1;
     }
@@ -138,13 +180,56 @@ impl CopyPlan {
         self.total_hardlinks += 1;
     }
 
-    pub fn mark_skipped(&mut self, size: u64) {
+    /// Plan the directories `--parents` creates above `source`, each paired with the
+    /// source directory it mirrors so its attributes can be copied too. Nothing at or
+    /// above `base` is created.
+    pub fn add_parent_directories(
+        &mut self,
+        source: &Path,
+        destination: &Path,
+        base: Option<&Path>,
+    ) {
+        for ancestor in source.ancestors().skip(1) {
+            // Stops at "/", "." and ".." which have no counterpart under `destination`
+            if ancestor.file_name().is_none() || Some(ancestor) == base {
+                break;
+            }
+            self.add_directory(
+                Some(ancestor.to_path_buf()),
+                with_parents_from(destination, ancestor, base),
+            );
+        }
+    }
+
+    pub fn add_special(&mut self, source: PathBuf, destination: PathBuf, metadata: &Metadata) {
+        #[cfg(unix)]
+        let (mode, rdev) = {
+            use std::os::unix::fs::MetadataExt;
+            (metadata.mode(), metadata.rdev())
+        };
+        #[cfg(not(unix))]
+        let (mode, rdev) = {
+            let _ = metadata;
+            (0, 0)
+        };
+
+        self.remove_existing_task(&destination);
+        self.specials.push(SpecialTask {
+            source,
+            destination,
+            mode,
+            rdev,
+        });
+    }
+
+    pub fn mark_skipped(&mut self, destination: PathBuf, size: u64) {
         self.skipped_files += 1;
         self.skipped_size += size;
+        self.skipped_destinations.push(destination);
     }
 
     pub fn sort_files_descending(&mut self) {
-        self.files.sort_by(|a, b| b.size.cmp(&a.size));
+        self.files.sort_by_key(|b| std::cmp::Reverse(b.size));
     }
 
     pub fn merge(&mut self, other: CopyPlan) {
@@ -152,12 +237,16 @@ impl CopyPlan {
         self.directories.extend(other.directories);
         self.symlinks.extend(other.symlinks);
         self.hardlinks.extend(other.hardlinks);
+        self.specials.extend(other.specials);
         self.total_size += other.total_size;
         self.total_files += other.total_files;
         self.total_symlinks += other.total_symlinks;
         self.total_hardlinks += other.total_hardlinks;
         self.skipped_files += other.skipped_files;
         self.skipped_size += other.skipped_size;
+        self.skipped_destinations.extend(other.skipped_destinations);
+        self.pruned_directories += other.pruned_directories;
+        self.missing_sources.extend(other.missing_sources);
     }
 }
 
@@ -175,7 +264,55 @@ fn symlink_kind_from_mode(source: &Path, mode: SymlinkMode) -> SymlinkKind {
     }
 }
 
-fn calculate_checksum(path: &Path) -> io::Result<u64> {
+/// FIFOs, sockets and device nodes; reading them would block or return device contents.
+/// Name of the special file type in `mode`, as reported in warning events.
+pub fn special_file_type(mode: u32) -> &'static str {
+    #[cfg(unix)]
+    {
+        match mode as libc::mode_t & libc::S_IFMT {
+            libc::S_IFIFO => "fifo",
+            libc::S_IFSOCK => "socket",
+            libc::S_IFBLK => "block_device",
+            libc::S_IFCHR => "char_device",
+            _ => "special",
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = mode;
+        "special"
+    }
+}
+
+#[cfg(unix)]
+fn file_mode(metadata: &Metadata) -> u32 {
+    use std::os::unix::fs::MetadataExt;
+    metadata.mode()
+}
+
+#[cfg(not(unix))]
+fn file_mode(_metadata: &Metadata) -> u32 {
+    0
+}
+
+pub fn is_special_file(metadata: &Metadata) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::FileTypeExt;
+        let file_type = metadata.file_type();
+        file_type.is_fifo()
+            || file_type.is_socket()
+            || file_type.is_block_device()
+            || file_type.is_char_device()
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = metadata;
+        false
+    }
+}
+
+pub(crate) fn calculate_checksum(path: &Path) -> io::Result<u64> {
     use std::io::Read;
     let mut file = std::fs::File::open(path)?;
     let mut hasher = Xxh3::new();
@@ -192,7 +329,11 @@ fn calculate_checksum(path: &Path) -> io::Result<u64> {
     Ok(hasher.digest())
 }
 
-pub fn should_skip_file(source: &Path, destination: &Path) -> io::Result<bool> {
+pub fn should_skip_file(
+    source: &Path,
+    destination: &Path,
+    hash_cache: HashCacheMode,
+) -> io::Result<bool> {
     let dest_metadata = match std::fs::metadata(destination) {
         Ok(meta) => meta,
         Err(_) => return Ok(false),
@@ -211,8 +352,8 @@ pub fn should_skip_file(source: &Path, destination: &Path) -> io::Result<bool> {
         return Ok(true);
     }
 
-    let src_checksum = calculate_checksum(source)?;
-    let dest_checksum = calculate_checksum(destination)?;
+    let src_checksum = cached_checksum(source, hash_cache)?;
+    let dest_checksum = cached_checksum(destination, hash_cache)?;
 
     Ok(src_checksum == dest_checksum)
 }
@@ -229,6 +370,23 @@ fn process_entry(
     if let Some(exclude_rules) = &options.exclude_rules
         && should_exclude(source, source_root, exclude_rules)
     {
+        if options.explain_excludes
+            && let Some(reason) = explain_path(source, source_root, exclude_rules)
+        {
+            report_excluded(options.events.as_deref(), source, &reason);
+        }
+        return Ok(());
+    }
+    if let Some(filter) = &options.filter_expr
+        && !filter.matches(source, metadata)
+    {
+        if options.explain_excludes {
+            report_excluded(
+                options.events.as_deref(),
+                source,
+                &excluded_by(MatchCause::Expression),
+            );
+        }
         return Ok(());
     }
 
@@ -272,7 +430,7 @@ fn process_entry(
                 plan.add_symlink(source.to_path_buf(), dest_path, kind);
             } else {
                 let original_target = std::fs::read_link(source)?;
-                plan.add_symlink(original_target, dest_path, SymlinkKind::PreserveExact);
+                plan.add_symlink_copy(source.to_path_buf(), original_target, dest_path);
             }
         }
     } else if options.hard_link {
@@ -280,8 +438,21 @@ fn process_entry(
     } else if let Some(mode) = options.symbolic_link {
         let kind = symlink_kind_from_mode(source, mode);
         plan.add_symlink(source.to_path_buf(), dest_path, kind);
-    } else if options.resume && should_skip_file(source, &dest_path)? {
-        plan.mark_skipped(metadata.len());
+    } else if is_special_file(metadata) && options.special != SpecialMode::Read {
+        if options.special == SpecialMode::Recreate {
+            plan.add_special(source.to_path_buf(), dest_path, metadata);
+        } else {
+            match &options.events {
+                Some(events) => events.emit(Event::Warning {
+                    source: Some(path_string(source)),
+                    message: "skipping special file".to_string(),
+                    file_type: Some(special_file_type(file_mode(metadata))),
+                }),
+                None => eprintln!("Skipping special file: {}", source.display()),
+            }
+        }
+    } else if options.resume && should_skip_file(source, &dest_path, options.hash_cache)? {
+        plan.mark_skipped(dest_path, metadata.len());
     } else {
         plan.add_file_with_inode(source.to_path_buf(), dest_path, metadata.len(), inode_group);
     }
@@ -307,27 +478,35 @@ pub fn preprocess_file(
     let mut plan = CopyPlan::new();
 
     let dest_path = if options.parents {
-        let dest_meta = destination_metadata.ok_or_else(|| CopyError::CopyFailed {
-            source: source.to_path_buf(),
-            destination: destination.to_path_buf(),
-            reason: format!(
-                "Destination '{}' does not exist, with --parents destination must be a directory",
-                destination.display()
-            ),
-        })?;
-
-        if !dest_meta.is_dir() {
-            return Err(CopyError::CopyFailed {
-                source: source.to_path_buf(),
-                destination: destination.to_path_buf(),
-                reason: format!(
-                    "Destination '{}' is not a directory, with --parents destination must be a directory",
-                    destination.display()
-                ),
-            });
+        match destination_metadata {
+            Some(dest_meta) if !dest_meta.is_dir() => {
+                return Err(CopyError::CopyFailed {
+                    source: source.to_path_buf(),
+                    destination: destination.to_path_buf(),
+                    reason: format!(
+                        "Destination '{}' is not a directory, with --parents destination must be a directory",
+                        destination.display()
+                    ),
+                });
+            }
+            Some(_) => {}
+            // --files-from creates the directory its listed paths are copied into
+            None if options.parents_base.is_some() => {
+                plan.add_directory(None, destination.to_path_buf());
+            }
+            None => {
+                return Err(CopyError::CopyFailed {
+                    source: source.to_path_buf(),
+                    destination: destination.to_path_buf(),
+                    reason: format!(
+                        "Destination '{}' does not exist, with --parents destination must be a directory",
+                        destination.display()
+                    ),
+                });
+            }
         }
 
-        with_parents(destination, source)
+        with_parents_from(destination, source, options.parents_base.as_deref())
     } else if let Some(dest_meta) = destination_metadata {
         if dest_meta.is_dir() {
             destination.join(source.file_name().ok_or_else(|| {
@@ -343,12 +522,25 @@ pub fn preprocess_file(
     if let Some(exclude_rules) = &options.exclude_rules
         && should_exclude(source, source_root, exclude_rules)
     {
+        if options.explain_excludes
+            && let Some(reason) = explain_path(source, source_root, exclude_rules)
+        {
+            report_excluded(options.events.as_deref(), source, &reason);
+        }
         return Ok(plan);
     }
-    if options.parents
-        && let Some(parent) = dest_path.parent()
-    {
-        plan.add_directory(None, parent.to_path_buf());
+    if options.exclude_nodump && has_nodump_flag(source) {
+        if options.explain_excludes {
+            report_excluded(
+                options.events.as_deref(),
+                source,
+                &excluded_by(MatchCause::Nodump(source.to_path_buf())),
+            );
+        }
+        return Ok(plan);
+    }
+    if options.parents {
+        plan.add_parent_directories(source, destination, options.parents_base.as_deref());
     }
 
     let mut inode_groups = None;
@@ -376,22 +568,53 @@ pub fn preprocess_directory(
     options: &CopyOptions,
 ) -> CopyResult<CopyPlan> {
     let mut plan = CopyPlan::new();
+    // A directory that is only walked for what an include rule may find inside is
+    // still copied as the root of its tree
     if source != source_root
         && let Some(exclude_rules) = &options.exclude_rules
-        && should_exclude(source, source_root, exclude_rules)
+        && filter_path(source, source_root, exclude_rules) == Verdict::Exclude
     {
+        if options.explain_excludes
+            && let Some(reason) = explain_path(source, source_root, exclude_rules)
+        {
+            report_excluded(options.events.as_deref(), source, &reason);
+        }
+        return Ok(plan);
+    }
+    if options.exclude_nodump && has_nodump_flag(source) {
+        if options.explain_excludes {
+            report_excluded(
+                options.events.as_deref(),
+                source,
+                &excluded_by(MatchCause::Nodump(source.to_path_buf())),
+            );
+        }
+        return Ok(plan);
+    }
+    if let Some(marker) = options.exclusion_markers.marker(source) {
+        if options.explain_excludes {
+            report_excluded(
+                options.events.as_deref(),
+                source,
+                &excluded_by(MatchCause::Marker(marker)),
+            );
+        }
+        plan.pruned_directories += 1;
         return Ok(plan);
     }
 
     let root_destination =
         if options.parents {
-            with_parents(destination, source)
+            with_parents_from(destination, source, options.parents_base.as_deref())
         } else {
             destination.join(source.file_name().ok_or_else(|| {
                 io::Error::new(io::ErrorKind::InvalidInput, "Invalid source path")
             })?)
         };
 
+    if options.parents {
+        plan.add_parent_directories(source, destination, options.parents_base.as_deref());
+    }
     plan.add_directory(Some(source.into()), root_destination.clone());
 
     let num_threads = num_cpus::get().min(8);
@@ -416,13 +639,80 @@ pub fn preprocess_directory(
         _ => source.to_path_buf(),
     };
 
+    // Entries are matched relative to the directory being copied, or to the --files-from
+    // base its listed paths are relative to
+    let filter_root = options.parents_base.as_deref().unwrap_or(source);
+    let entry_root = if walk_root == source {
+        filter_root
+    } else {
+        walk_root.as_path()
+    };
+
     let mut inode_groups = None;
+    let mut scanned_entries = 0u64;
+    let mut traversed: HashMap<PathBuf, PathBuf> = HashMap::new();
+    // Excluded directories already reported by --explain-excludes, so their contents aren't
+    let mut explained: HashSet<PathBuf> = HashSet::new();
 
-    for entry in WalkDir::new(&walk_root)
+    let mut walker = WalkDirGeneric::<(IgnoreStack, ())>::new(&walk_root)
         .skip_hidden(false)
         .parallelism(jwalk::Parallelism::RayonNewPool(num_threads))
-        .follow_links(follow_symlink)
+        .follow_links(follow_symlink);
+    let pruned = Arc::new(AtomicUsize::new(0));
+    if options.exclude_nodump
+        || !options.ignore_files.is_empty()
+        || !options.exclusion_markers.is_empty()
     {
+        let exclude_nodump = options.exclude_nodump;
+        let ignore_files = options.ignore_files.clone();
+        let markers = options.exclusion_markers.clone();
+        let pruned = Arc::clone(&pruned);
+        let explain = options.explain_excludes;
+        let events = options.events.clone();
+        // Dropping a directory here also keeps the walk out of it
+        walker = walker.process_read_dir(move |depth, dir, ignores, children| {
+            // Without a depth, `dir` is the parent of the walk root, outside the source
+            if depth.is_some() && !ignore_files.is_empty() {
+                ignores.load(dir, &ignore_files);
+            }
+            children.retain(|child| {
+                child.as_ref().map_or(true, |entry| {
+                    let path = entry.path();
+                    let is_dir = entry.file_type().is_dir();
+                    if exclude_nodump && has_nodump_flag(&path) {
+                        if explain {
+                            report_excluded(
+                                events.as_deref(),
+                                &path,
+                                &excluded_by(MatchCause::Nodump(path.clone())),
+                            );
+                        }
+                        return false;
+                    }
+                    if ignores.is_ignored(&path, is_dir) {
+                        if explain && let Some(reason) = ignores.explain(&path, is_dir) {
+                            report_excluded(events.as_deref(), &path, &reason);
+                        }
+                        return false;
+                    }
+                    if is_dir && let Some(marker) = markers.marker(&path) {
+                        if explain {
+                            report_excluded(
+                                events.as_deref(),
+                                &path,
+                                &excluded_by(MatchCause::Marker(marker)),
+                            );
+                        }
+                        pruned.fetch_add(1, Ordering::Relaxed);
+                        return false;
+                    }
+                    true
+                })
+            });
+        });
+    }
+
+    for entry in walker {
         let entry = entry.map_err(|e| CopyError::CopyFailed {
             source: source.to_path_buf(),
             destination: destination.to_path_buf(),
@@ -433,6 +723,17 @@ pub fn preprocess_directory(
             continue;
         }
 
+        scanned_entries += 1;
+        if let Some(events) = &options.events
+            && scanned_entries.is_multiple_of(SCAN_EVENT_INTERVAL)
+        {
+            events.emit(Event::Scan {
+                entries: scanned_entries,
+                files: plan.total_files,
+                bytes: plan.total_size,
+            });
+        }
+
         let relative = src_path
             .strip_prefix(&walk_root)
             .map_err(|_| CopyError::CopyFailed {
@@ -447,13 +748,48 @@ pub fn preprocess_directory(
             src_path.to_path_buf()
         };
 
-        if let Some(exclude_rules) = &options.exclude_rules
-            && should_exclude(&full_source_path, source, exclude_rules)
-        {
-            continue;
+        let verdict = match &options.exclude_rules {
+            Some(exclude_rules) => filter_path(&full_source_path, filter_root, exclude_rules),
+            None => Verdict::Include,
+        };
+        let dest_path = root_destination.join(relative);
+        match verdict {
+            Verdict::Exclude => {
+                if options.explain_excludes {
+                    let reported = src_path
+                        .parent()
+                        .is_some_and(|parent| explained.contains(parent));
+                    if !reported
+                        && let Some(exclude_rules) = &options.exclude_rules
+                        && let Some(reason) =
+                            explain_path(&full_source_path, filter_root, exclude_rules)
+                    {
+                        report_excluded(options.events.as_deref(), &full_source_path, &reason);
+                    }
+                    if entry.file_type().is_dir() {
+                        explained.insert(src_path.to_path_buf());
+                    }
+                }
+                continue;
+            }
+            Verdict::Traverse => {
+                traversed.insert(src_path.to_path_buf(), dest_path);
+                continue;
+            }
+            Verdict::Include => {}
+        }
+        // Create the excluded directories walked to reach this entry, outermost first
+        if !traversed.is_empty() {
+            let mut parents: Vec<_> = src_path
+                .ancestors()
+                .skip(1)
+                .filter_map(|parent| traversed.remove_entry(parent))
+                .collect();
+            while let Some((parent_source, parent_destination)) = parents.pop() {
+                plan.add_directory(Some(parent_source), parent_destination);
+            }
         }
 
-        let dest_path = root_destination.join(relative);
         let metadata = entry.metadata().map_err(|e| CopyError::CopyFailed {
             source: src_path.to_path_buf(),
             destination: destination.to_path_buf(),
@@ -466,7 +802,7 @@ pub fn preprocess_directory(
             process_entry(
                 &mut plan,
                 &src_path,
-                &walk_root,
+                entry_root,
                 dest_path,
                 &metadata,
                 options,
@@ -475,52 +811,147 @@ pub fn preprocess_directory(
         }
     }
 
+    plan.pruned_directories += pruned.load(Ordering::Relaxed);
     plan.sort_files_descending();
     Ok(plan)
 }
 
+/// Why `path` inside the source directory `source_root` is or isn't copied, going
+/// through the checks of the directory walk from the top down. `None` means no rule
+/// applies and it is copied.
+pub fn explain_exclusion(
+    path: &Path,
+    source_root: &Path,
+    options: &CopyOptions,
+) -> Option<MatchReason> {
+    let relative = path.strip_prefix(source_root).ok()?;
+    let mut ignores = IgnoreStack::default();
+    let mut current = source_root.to_path_buf();
+    let mut components = relative.components();
+    loop {
+        let is_dir = current.is_dir();
+        if options.exclude_nodump && has_nodump_flag(&current) {
+            return Some(excluded_by(MatchCause::Nodump(current)));
+        }
+        // Ignore files only apply below the directory holding them
+        if current != source_root && ignores.is_ignored(&current, is_dir) {
+            return ignores.explain(&current, is_dir);
+        }
+        if is_dir {
+            if let Some(marker) = options.exclusion_markers.marker(&current) {
+                return Some(excluded_by(MatchCause::Marker(marker)));
+            }
+            if !options.ignore_files.is_empty() {
+                ignores.load(&current, &options.ignore_files);
+            }
+        }
+        match components.next() {
+            Some(component) => current.push(component),
+            None => break,
+        }
+    }
+
+    if path != source_root
+        && let Some(exclude_rules) = &options.exclude_rules
+        && let Some(reason) = explain_path(path, source_root, exclude_rules)
+    {
+        return Some(reason);
+    }
+    if let Some(filter) = &options.filter_expr
+        && let Ok(metadata) = std::fs::symlink_metadata(path)
+        && !metadata.is_dir()
+        && !filter.matches(path, &metadata)
+    {
+        return Some(excluded_by(MatchCause::Expression));
+    }
+    None
+}
+
+fn excluded_by(cause: MatchCause) -> MatchReason {
+    MatchReason {
+        verdict: Verdict::Exclude,
+        cause,
+    }
+}
+
+/// Print why `path` is left out, for `--explain-excludes`.
+fn report_excluded(events: Option<&EventEmitter>, path: &Path, reason: &MatchReason) {
+    match events {
+        Some(events) => events.emit(Event::Info {
+            source: Some(path_string(path)),
+            message: reason.to_string(),
+        }),
+        None => eprintln!("{}: {}", path.display(), reason),
+    }
+}
+
 pub fn preprocess_multiple(
     sources: &[PathBuf],
     destination: &Path,
     options: &CopyOptions,
 ) -> CopyResult<CopyPlan> {
-    let dest_metadata = std::fs::metadata(destination)
-        .map_err(|_e| CopyError::InvalidDestination(destination.to_path_buf()))?;
-    if !dest_metadata.is_dir() {
-        return Err(CopyError::CopyFailed {
-            source: PathBuf::new(),
-            destination: destination.to_path_buf(),
-            reason: format!("Destination '{}' is not a directory", destination.display()),
-        });
+    let mut plan = CopyPlan::new();
+    // An empty --files-from list copies nothing
+    if sources.is_empty() {
+        return Ok(plan);
     }
 
-    let mut plan = CopyPlan::new();
+    match std::fs::metadata(destination) {
+        Ok(dest_metadata) if !dest_metadata.is_dir() => {
+            return Err(CopyError::CopyFailed {
+                source: PathBuf::new(),
+                destination: destination.to_path_buf(),
+                reason: format!("Destination '{}' is not a directory", destination.display()),
+            });
+        }
+        Ok(_) => {}
+        Err(_) if options.parents_base.is_some() => {
+            plan.add_directory(None, destination.to_path_buf());
+        }
+        Err(_) => return Err(CopyError::InvalidDestination(destination.to_path_buf())),
+    }
 
     for source in sources {
         let metadata = match options.follow_symlink {
             FollowSymlink::Dereference | FollowSymlink::CommandLineSymlink => {
                 std::fs::metadata(source)
-                    .map_err(|_e| CopyError::InvalidSource(source.to_path_buf()))?
             }
-            FollowSymlink::NoDereference => std::fs::symlink_metadata(source)
-                .map_err(|_e| CopyError::InvalidSource(source.to_path_buf()))?,
+            FollowSymlink::NoDereference => std::fs::symlink_metadata(source),
         };
+        let metadata = match metadata {
+            Ok(metadata) => metadata,
+            // Like rsync, a listed path that is gone doesn't stop the rest of the list
+            Err(_) if options.parents_base.is_some() => {
+                plan.missing_sources.push(source.clone());
+                continue;
+            }
+            Err(_) => return Err(CopyError::InvalidSource(source.to_path_buf())),
+        };
+        // Filter rules see listed paths relative to the --files-from base
+        let source_root = options.parents_base.as_deref().unwrap_or(source);
 
         if metadata.is_dir() {
-            let dir_plan =
-                preprocess_directory(source, source, destination, options).map_err(|e| {
-                    CopyError::CopyFailed {
-                        source: source.to_path_buf(),
-                        destination: destination.to_path_buf(),
-                        reason: e.to_string(),
-                    }
+            let dir_plan = preprocess_directory(source, source_root, destination, options)
+                .map_err(|e| CopyError::CopyFailed {
+                    source: source.to_path_buf(),
+                    destination: destination.to_path_buf(),
+                    reason: e.to_string(),
                 })?;
             plan.merge(dir_plan);
         } else {
-            let _source_root = source.parent().unwrap_or_else(|| Path::new("."));
+            if options.exclude_nodump && has_nodump_flag(source) {
+                if options.explain_excludes {
+                    report_excluded(
+                        options.events.as_deref(),
+                        source,
+                        &excluded_by(MatchCause::Nodump(source.clone())),
+                    );
+                }
+                continue;
+            }
 
             let dest_path = if options.parents {
-                with_parents(destination, source)
+                with_parents_from(destination, source, options.parents_base.as_deref())
             } else {
                 destination.join(source.file_name().ok_or_else(|| CopyError::CopyFailed {
                     source: source.to_path_buf(),
@@ -529,17 +960,15 @@ pub fn preprocess_multiple(
                 })?)
             };
 
-            if options.parents
-                && let Some(parent) = dest_path.parent()
-            {
-                plan.add_directory(None, parent.to_path_buf());
+            if options.parents {
+                plan.add_parent_directories(source, destination, options.parents_base.as_deref());
             }
 
             let mut inode_groups = None;
             process_entry(
                 &mut plan,
                 source,
-                source,
+                source_root,
                 dest_path.clone(),
                 &metadata,
                 options,
@@ -775,6 +1204,52 @@ mod tests {
         assert!(plan.symlinks.is_empty());
     }
 
+    #[cfg(unix)]
+    #[test]
+    fn test_preprocess_directory_with_fifo() {
+        let temp_dir = TempDir::new().unwrap();
+        let source_dir = temp_dir.path().join("source");
+        let dest_dir = temp_dir.path().join("dest");
+
+        create_test_file(&source_dir.join("file.txt"), b"content").unwrap();
+        let fifo = std::ffi::CString::new(source_dir.join("pipe").to_str().unwrap()).unwrap();
+        assert_eq!(unsafe { libc::mkfifo(fifo.as_ptr(), 0o644) }, 0);
+
+        let mut options = CopyOptions::none();
+        let plan = preprocess_directory(&source_dir, &source_dir, &dest_dir, &options).unwrap();
+        assert_eq!(plan.total_files, 1);
+        assert_eq!(plan.specials.len(), 1);
+
+        options.special = SpecialMode::Skip;
+        let plan = preprocess_directory(&source_dir, &source_dir, &dest_dir, &options).unwrap();
+        assert_eq!(plan.total_files, 1);
+        assert!(plan.specials.is_empty());
+
+        options.special = SpecialMode::Read;
+        let plan = preprocess_directory(&source_dir, &source_dir, &dest_dir, &options).unwrap();
+        assert_eq!(plan.total_files, 2);
+    }
+
+    #[test]
+    fn test_copy_plan_add_parent_directories() {
+        let mut plan = CopyPlan::new();
+        plan.add_parent_directories(Path::new("/src/a/b/file.txt"), Path::new("/dest"), None);
+
+        let dirs: Vec<_> = plan
+            .directories
+            .iter()
+            .map(|d| (d.source.clone().unwrap(), d.destination.clone()))
+            .collect();
+        assert_eq!(
+            dirs,
+            vec![
+                (PathBuf::from("/src/a/b"), PathBuf::from("/dest/src/a/b")),
+                (PathBuf::from("/src/a"), PathBuf::from("/dest/src/a")),
+                (PathBuf::from("/src"), PathBuf::from("/dest/src")),
+            ]
+        );
+    }
+
     #[test]
     fn test_copy_plan_add_symlink() {
         let mut plan = CopyPlan::new();
@@ -1,7 +1,11 @@
+use super::chmod::ChmodRules;
+use super::idmap::OwnerMap;
+use super::xattr_filter::{XattrFilter, namespace_label};
 use crate::error::{PreserveError, PreserveResult};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::io;
 use std::path::{Path, PathBuf};
+use std::sync::Mutex;
 
 #[cfg(unix)]
 use std::os::unix::fs::PermissionsExt;
@@ -14,6 +18,8 @@ pub struct PreserveAttr {
     pub links: bool,
     pub context: bool,
     pub xattr: bool,
+    pub acl: bool,
+    pub flags: bool,
 }
 
 impl Default for PreserveAttr {
@@ -25,6 +31,8 @@ impl Default for PreserveAttr {
             links: false,
             context: false,
             xattr: false,
+            acl: false,
+            flags: false,
         }
     }
 }
@@ -38,6 +46,8 @@ impl PreserveAttr {
             links: false,
             context: false,
             xattr: false,
+            acl: false,
+            flags: false,
         }
     }
 
@@ -49,6 +59,22 @@ impl PreserveAttr {
             links: true,
             context: true,
             xattr: true,
+            acl: true,
+            flags: true,
+        }
+    }
+
+    /// The attributes of `self` that are not in `other`.
+    pub fn without(self, other: Self) -> Self {
+        Self {
+            mode: self.mode && !other.mode,
+            ownership: self.ownership && !other.ownership,
+            timestamps: self.timestamps && !other.timestamps,
+            links: self.links && !other.links,
+            context: self.context && !other.context,
+            xattr: self.xattr && !other.xattr,
+            acl: self.acl && !other.acl,
+            flags: self.flags && !other.flags,
         }
     }
 
@@ -70,6 +96,8 @@ impl PreserveAttr {
                 "ownership" => attr.ownership = true,
                 "timestamps" => attr.timestamps = true,
                 "xattr" => attr.xattr = true,
+                "acl" => attr.acl = true,
+                "flags" => attr.flags = true,
                 "context" => attr.context = true,
                 "links" => attr.links = true,
                 "all" => return Ok(Self::all()),
@@ -86,56 +114,197 @@ impl PreserveAttr {
     }
 }
 
+/// How the preserved attributes are rewritten on the copies: the owner remapping
+/// applied whenever ownership is preserved, the `--chmod` rules applied to every copy,
+/// and which xattrs are copied.
+#[derive(Debug, Default)]
+pub struct PreserveRules {
+    owners: Option<OwnerMap>,
+    chmod: Option<ChmodRules>,
+    xattrs: Option<XattrFilter>,
+}
+
+impl PreserveRules {
+    pub fn with_owners(mut self, owners: OwnerMap) -> Self {
+        self.owners = Some(owners);
+        self
+    }
+
+    pub fn with_chmod(mut self, rules: ChmodRules) -> Self {
+        self.chmod = Some(rules);
+        self
+    }
+
+    pub fn with_xattr_filter(mut self, filter: XattrFilter) -> Self {
+        self.xattrs = Some(filter);
+        self
+    }
+
+    pub(crate) fn copies_xattr(&self, name: &str) -> bool {
+        self.xattrs
+            .as_ref()
+            .is_none_or(|filter| filter.allows(name))
+    }
+
+    pub fn has_chmod(&self) -> bool {
+        self.chmod.is_some()
+    }
+
+    /// The uid and gid a copy of a file owned by `metadata`'s owner should get.
+    #[cfg(unix)]
+    fn owner_for(&self, metadata: &std::fs::Metadata) -> (u32, u32) {
+        use std::os::unix::fs::MetadataExt;
+
+        match &self.owners {
+            Some(owners) => owners.map(metadata.uid(), metadata.gid()),
+            None => (metadata.uid(), metadata.gid()),
+        }
+    }
+}
+
+/// How attributes that can't be preserved are handled: with `strict`, they fail the copy,
+/// otherwise the copy goes on and they are counted for the end-of-run summary.
+#[derive(Debug, Default)]
+pub struct PreservePolicy {
+    strict: bool,
+    dropped: Mutex<BTreeMap<&'static str, usize>>,
+}
+
+impl PreservePolicy {
+    pub fn new(strict: bool) -> Self {
+        Self {
+            strict,
+            dropped: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    pub fn is_strict(&self) -> bool {
+        self.strict
+    }
+
+    /// Fail with `err` in strict mode, otherwise count `attribute` as dropped on one more file.
+    pub(crate) fn tolerate(&self, attribute: &'static str, err: io::Error) -> io::Result<()> {
+        if self.strict {
+            return Err(err);
+        }
+        if let Ok(mut dropped) = self.dropped.lock() {
+            *dropped.entry(attribute).or_default() += 1;
+        }
+        Ok(())
+    }
+
+    /// Attributes that were not preserved, with the number of files each one was dropped on.
+    pub fn dropped(&self) -> Vec<(&'static str, usize)> {
+        self.dropped
+            .lock()
+            .map(|dropped| {
+                dropped
+                    .iter()
+                    .map(|(attr, count)| (*attr, *count))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
 pub fn apply_preserve_attrs(
     source: &Path,
     destination: &Path,
     attrs: PreserveAttr,
+    rules: &PreserveRules,
+    policy: &PreservePolicy,
 ) -> PreserveResult<()> {
     let src_metadata = std::fs::metadata(source).map_err(|_e| PreserveError::FailedToPreserve {
         path: source.to_path_buf(),
         attribute: "metadata".to_string(),
     })?;
-    if attrs.timestamps {
-        preserve_timestamps(destination, &src_metadata).map_err(|_e| {
+    apply_preserve_attrs_with_metadata(source, &src_metadata, destination, attrs, rules, policy)
+}
+
+/// Like [`apply_preserve_attrs`], with the source's metadata taken before its data was
+/// read, so the access time being preserved is not the one the copy itself caused.
+pub fn apply_preserve_attrs_with_metadata(
+    source: &Path,
+    src_metadata: &std::fs::Metadata,
+    destination: &Path,
+    attrs: PreserveAttr,
+    rules: &PreserveRules,
+    policy: &PreservePolicy,
+) -> PreserveResult<()> {
+    // Ownership first: chown clears setuid/setgid bits and file capabilities
+    #[cfg(unix)]
+    if attrs.ownership {
+        preserve_ownership(destination, src_metadata, rules, policy).map_err(|_e| {
             PreserveError::FailedToPreserve {
                 path: destination.to_path_buf(),
-                attribute: "timestamps".to_string(),
+                attribute: "ownership".to_string(),
             }
         })?;
     }
+
     #[cfg(unix)]
     if attrs.mode {
-        preserve_mode(destination, &src_metadata).map_err(|_e| {
+        preserve_mode(destination, src_metadata).map_err(|_e| PreserveError::FailedToPreserve {
+            path: destination.to_path_buf(),
+            attribute: "mode".to_string(),
+        })?;
+    }
+
+    #[cfg(unix)]
+    if attrs.xattr {
+        preserve_xattr(source, destination, rules, policy).map_err(|_e| {
             PreserveError::FailedToPreserve {
                 path: destination.to_path_buf(),
-                attribute: "mode".to_string(),
+                attribute: "xattr".to_string(),
             }
         })?;
     }
 
+    // After mode: chmod rewrites the mask entry of an access ACL
     #[cfg(unix)]
-    if attrs.ownership {
-        preserve_ownership(destination, &src_metadata).map_err(|_e| {
+    if attrs.acl {
+        preserve_acl(source, destination, src_metadata, policy).map_err(|_e| {
             PreserveError::FailedToPreserve {
                 path: destination.to_path_buf(),
-                attribute: "ownership".to_string(),
+                attribute: "acl".to_string(),
             }
         })?;
     }
 
     #[cfg(unix)]
-    if attrs.xattr {
-        preserve_xattr(source, destination).map_err(|_e| PreserveError::FailedToPreserve {
+    if attrs.context {
+        preserve_context(source, destination).map_err(|_e| PreserveError::FailedToPreserve {
             path: destination.to_path_buf(),
-            attribute: "xattr".to_string(),
+            attribute: "context".to_string(),
         })?;
     }
 
+    // Rewrites whatever mode the steps above left, even when mode isn't preserved
     #[cfg(unix)]
-    if attrs.context {
-        preserve_context(source, destination).map_err(|_e| PreserveError::FailedToPreserve {
+    if let Some(chmod) = &rules.chmod {
+        apply_chmod(destination, chmod).map_err(|_e| PreserveError::FailedToPreserve {
             path: destination.to_path_buf(),
-            attribute: "context".to_string(),
+            attribute: "chmod".to_string(),
+        })?;
+    }
+
+    if attrs.timestamps {
+        preserve_timestamps(destination, src_metadata).map_err(|_e| {
+            PreserveError::FailedToPreserve {
+                path: destination.to_path_buf(),
+                attribute: "timestamps".to_string(),
+            }
+        })?;
+    }
+
+    // Last of all; +i and +a come later still, from `lock_inode_flags`
+    #[cfg(target_os = "linux")]
+    if attrs.flags {
+        preserve_flags(source, destination, src_metadata, policy).map_err(|_e| {
+            PreserveError::FailedToPreserve {
+                path: destination.to_path_buf(),
+                attribute: "flags".to_string(),
+            }
         })?;
     }
 
@@ -143,13 +312,79 @@ pub fn apply_preserve_attrs(
 }
 
 fn preserve_timestamps(destination: &Path, src_metadata: &std::fs::Metadata) -> io::Result<()> {
-    use filetime::{FileTime, set_file_mtime};
+    use filetime::{FileTime, set_file_times};
+
+    // utimensat keeps both times at full nanosecond precision
+    let atime = FileTime::from_last_access_time(src_metadata);
+    let mtime = FileTime::from_last_modification_time(src_metadata);
+
+    set_file_times(destination, atime, mtime)
+}
+
+/// Copy timestamps and ownership of a symlink itself, without following it.
+pub fn apply_symlink_attrs(
+    source: &Path,
+    destination: &Path,
+    attrs: PreserveAttr,
+    rules: &PreserveRules,
+    policy: &PreservePolicy,
+) -> PreserveResult<()> {
+    let src_metadata =
+        std::fs::symlink_metadata(source).map_err(|_e| PreserveError::FailedToPreserve {
+            path: source.to_path_buf(),
+            attribute: "metadata".to_string(),
+        })?;
+
+    // Ownership first: changing it updates the link's ctime, not its times
+    #[cfg(unix)]
+    if attrs.ownership {
+        preserve_symlink_ownership(destination, &src_metadata, rules, policy).map_err(|_e| {
+            PreserveError::FailedToPreserve {
+                path: destination.to_path_buf(),
+                attribute: "ownership".to_string(),
+            }
+        })?;
+    }
+
+    if attrs.timestamps {
+        use filetime::{FileTime, set_symlink_file_times};
+
+        set_symlink_file_times(
+            destination,
+            FileTime::from_last_access_time(&src_metadata),
+            FileTime::from_last_modification_time(&src_metadata),
+        )
+        .map_err(|_e| PreserveError::FailedToPreserve {
+            path: destination.to_path_buf(),
+            attribute: "timestamps".to_string(),
+        })?;
+    }
 
-    let modified_time = src_metadata.modified().map_err(io::Error::other)?;
+    Ok(())
+}
 
-    let system_modified_time = FileTime::from_system_time(modified_time);
+#[cfg(unix)]
+fn preserve_symlink_ownership(
+    destination: &Path,
+    src_metadata: &std::fs::Metadata,
+    rules: &PreserveRules,
+    policy: &PreservePolicy,
+) -> io::Result<()> {
+    use std::os::unix::ffi::OsStrExt;
 
-    set_file_mtime(destination, system_modified_time).map_err(io::Error::other)?;
+    let dest_cstring = std::ffi::CString::new(destination.as_os_str().as_bytes())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+
+    let (uid, gid) = rules.owner_for(src_metadata);
+    let result = unsafe { libc::lchown(dest_cstring.as_ptr(), uid, gid) };
+    if result != 0 {
+        let err = io::Error::last_os_error();
+        // Same leniency as preserve_ownership for unprivileged users
+        if err.raw_os_error() != Some(libc::EPERM) && err.raw_os_error() != Some(libc::EACCES) {
+            return Err(err);
+        }
+        policy.tolerate("ownership", err)?;
+    }
 
     Ok(())
 }
@@ -167,14 +402,27 @@ fn preserve_mode(destination: &Path, src_metadata: &std::fs::Metadata) -> io::Re
 }
 
 #[cfg(unix)]
-fn preserve_ownership(destination: &Path, src_metadata: &std::fs::Metadata) -> io::Result<()> {
-    use std::os::unix::fs::MetadataExt;
+fn apply_chmod(destination: &Path, rules: &ChmodRules) -> io::Result<()> {
+    let metadata = std::fs::metadata(destination)?;
+    let mode = metadata.permissions().mode() & 0o7777;
+    let new_mode = rules.apply(mode, metadata.is_dir());
+    if new_mode != mode {
+        std::fs::set_permissions(destination, std::fs::Permissions::from_mode(new_mode))?;
+    }
+    Ok(())
+}
 
-    let uid = src_metadata.uid();
-    let gid = src_metadata.gid();
+#[cfg(unix)]
+fn preserve_ownership(
+    destination: &Path,
+    src_metadata: &std::fs::Metadata,
+    rules: &PreserveRules,
+    policy: &PreservePolicy,
+) -> io::Result<()> {
+    let (uid, gid) = rules.owner_for(src_metadata);
 
     // Note: This requires elevated privileges (root) to work in most cases
-    // We'll attempt it but won't fail if it doesn't work
+    // Unless strict, a permission failure is only counted for the summary
     let dest_cstring = std::ffi::CString::new(destination.to_string_lossy().as_bytes())
         .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
 
@@ -187,6 +435,7 @@ fn preserve_ownership(destination: &Path, src_metadata: &std::fs::Metadata) -> i
             if err.raw_os_error() != Some(1) && err.raw_os_error() != Some(13) {
                 return Err(err);
             }
+            policy.tolerate("ownership", err)?;
         }
     }
 
@@ -194,7 +443,12 @@ fn preserve_ownership(destination: &Path, src_metadata: &std::fs::Metadata) -> i
 }
 
 #[cfg(unix)]
-fn preserve_xattr(source: &Path, destination: &Path) -> io::Result<()> {
+fn preserve_xattr(
+    source: &Path,
+    destination: &Path,
+    rules: &PreserveRules,
+    policy: &PreservePolicy,
+) -> io::Result<()> {
     if !xattr::SUPPORTED_PLATFORM {
         return Ok(());
     }
@@ -208,14 +462,287 @@ fn preserve_xattr(source: &Path, destination: &Path) -> io::Result<()> {
             return Err(e);
         }
     };
+    // Keep going past a name the destination refuses, but count the file only once
+    // per namespace
+    let mut failed = BTreeMap::new();
     for attr_name in xattrs {
-        if let Some(value) = xattr::get(source, &attr_name)? {
-            let _ = xattr::set(destination, &attr_name, &value);
+        let name = attr_name.to_string_lossy();
+        if !rules.copies_xattr(&name) {
+            continue;
         }
+        let value = match xattr::get(source, &attr_name) {
+            Ok(Some(value)) => value,
+            Ok(None) => continue,
+            Err(e) => {
+                failed.entry(namespace_label(&name)).or_insert(e);
+                continue;
+            }
+        };
+        if let Err(e) = xattr::set(destination, &attr_name, &value) {
+            failed.entry(namespace_label(&name)).or_insert(e);
+        }
+    }
+    for (label, e) in failed {
+        policy.tolerate(label, e)?;
     }
     Ok(())
 }
 
+/// Inode flags that `chattr` can set and that are worth carrying over.
+#[cfg(target_os = "linux")]
+mod inode_flags {
+    pub const SECRM: u32 = 0x0000_0001;
+    pub const UNRM: u32 = 0x0000_0002;
+    pub const COMPR: u32 = 0x0000_0004;
+    pub const SYNC: u32 = 0x0000_0008;
+    pub const IMMUTABLE: u32 = 0x0000_0010;
+    pub const APPEND: u32 = 0x0000_0020;
+    pub const NODUMP: u32 = 0x0000_0040;
+    pub const NOATIME: u32 = 0x0000_0080;
+    pub const NOCOMP: u32 = 0x0000_0400;
+    pub const JOURNAL_DATA: u32 = 0x0000_4000;
+    pub const NOTAIL: u32 = 0x0000_8000;
+    pub const DIRSYNC: u32 = 0x0001_0000;
+    pub const TOPDIR: u32 = 0x0002_0000;
+    pub const NOCOW: u32 = 0x0080_0000;
+    pub const PROJINHERIT: u32 = 0x2000_0000;
+
+    pub const COPYABLE: u32 = SECRM
+        | UNRM
+        | COMPR
+        | SYNC
+        | IMMUTABLE
+        | APPEND
+        | NODUMP
+        | NOATIME
+        | NOCOMP
+        | JOURNAL_DATA
+        | NOTAIL
+        | DIRSYNC
+        | TOPDIR
+        | NOCOW
+        | PROJINHERIT;
+
+    /// Left for [`super::lock_inode_flags`]: once set, a copy can't be linked to,
+    /// rewritten or given its remaining attributes.
+    pub const LOCKING: u32 = IMMUTABLE | APPEND;
+}
+
+/// Open a file or directory for the flags ioctls without blocking or following symlinks.
+#[cfg(target_os = "linux")]
+fn open_for_flags(path: &Path) -> io::Result<std::fs::File> {
+    use std::os::unix::fs::OpenOptionsExt;
+
+    std::fs::OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_NONBLOCK | libc::O_NOFOLLOW)
+        .open(path)
+}
+
+/// Read the inode flags (`lsattr`) of a regular file or directory.
+#[cfg(target_os = "linux")]
+pub fn read_inode_flags(path: &Path) -> io::Result<u32> {
+    use std::os::unix::io::AsRawFd;
+
+    let file = open_for_flags(path)?;
+    // The ioctls take an int, despite the long in their definitions
+    let mut flags: libc::c_int = 0;
+    if unsafe { libc::ioctl(file.as_raw_fd(), libc::FS_IOC_GETFLAGS, &mut flags) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(flags as u32)
+}
+
+#[cfg(target_os = "linux")]
+fn write_inode_flags(path: &Path, flags: u32) -> io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    let file = open_for_flags(path)?;
+    let flags = flags as libc::c_int;
+    if unsafe { libc::ioctl(file.as_raw_fd(), libc::FS_IOC_SETFLAGS, &flags) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Whether `path` is marked with `chattr +d`; unreadable flags count as not set.
+pub fn has_nodump_flag(path: &Path) -> bool {
+    #[cfg(target_os = "linux")]
+    {
+        let Ok(metadata) = std::fs::symlink_metadata(path) else {
+            return false;
+        };
+        (metadata.is_file() || metadata.is_dir())
+            && read_inode_flags(path).is_ok_and(|flags| flags & inode_flags::NODUMP != 0)
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = path;
+        false
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn flags_unsupported(e: &io::Error) -> bool {
+    matches!(
+        e.raw_os_error(),
+        Some(libc::ENOTTY) | Some(libc::EOPNOTSUPP) | Some(libc::EINVAL)
+    )
+}
+
+#[cfg(target_os = "linux")]
+fn preserve_flags(
+    source: &Path,
+    destination: &Path,
+    src_metadata: &std::fs::Metadata,
+    policy: &PreservePolicy,
+) -> io::Result<()> {
+    // The ioctls would reach the driver of a device node, so only files and directories
+    if !(src_metadata.is_file() || src_metadata.is_dir()) {
+        return Ok(());
+    }
+
+    let src_flags = match copyable_inode_flags(source) {
+        Ok(flags) => flags,
+        Err(e) if flags_unsupported(&e) => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    set_inode_flags(destination, src_flags & !inode_flags::LOCKING, policy)
+}
+
+/// Give `destination` the `+i` and `+a` flags of `source`, which `--preserve=flags`
+/// leaves out until every copy, link and retry is done.
+#[cfg(target_os = "linux")]
+pub fn lock_inode_flags(
+    source: &Path,
+    destination: &Path,
+    policy: &PreservePolicy,
+) -> PreserveResult<()> {
+    let failed = |_e| PreserveError::FailedToPreserve {
+        path: destination.to_path_buf(),
+        attribute: "flags".to_string(),
+    };
+    let src_metadata = std::fs::metadata(source).map_err(failed)?;
+    if !(src_metadata.is_file() || src_metadata.is_dir()) {
+        return Ok(());
+    }
+    let src_flags = match copyable_inode_flags(source) {
+        Ok(flags) => flags,
+        Err(e) if flags_unsupported(&e) => return Ok(()),
+        Err(e) => return Err(failed(e)),
+    };
+    if src_flags & inode_flags::LOCKING == 0 {
+        return Ok(());
+    }
+    set_inode_flags(destination, src_flags, policy).map_err(failed)
+}
+
+/// The inode flags of `path` that `--preserve=flags` carries over.
+#[cfg(target_os = "linux")]
+pub(crate) fn copyable_inode_flags(path: &Path) -> io::Result<u32> {
+    Ok(read_inode_flags(path)? & inode_flags::COPYABLE)
+}
+
+/// Give `destination` the copyable flags in `src_flags`, keeping its other flags.
+#[cfg(target_os = "linux")]
+pub(crate) fn set_inode_flags(
+    destination: &Path,
+    src_flags: u32,
+    policy: &PreservePolicy,
+) -> io::Result<()> {
+    let src_flags = src_flags & inode_flags::COPYABLE;
+    let dest_flags = match read_inode_flags(destination) {
+        Ok(flags) => flags,
+        Err(e) if flags_unsupported(&e) => return Ok(()),
+        Err(e) => return Err(e),
+    };
+
+    let wanted = (dest_flags & !inode_flags::COPYABLE) | src_flags;
+    if wanted == dest_flags {
+        return Ok(());
+    }
+
+    // Everything else first, so a failing +i or +a still leaves the other flags set
+    let relaxed = wanted & !(inode_flags::IMMUTABLE | inode_flags::APPEND);
+    if relaxed != dest_flags {
+        match write_inode_flags(destination, relaxed) {
+            Err(e) if flags_unsupported(&e) => return Ok(()),
+            result => result?,
+        }
+    }
+    if wanted != relaxed {
+        match write_inode_flags(destination, wanted) {
+            // +i and +a need CAP_LINUX_IMMUTABLE, treated like chown without privileges
+            Err(e) if e.raw_os_error() == Some(libc::EPERM) => policy.tolerate("flags", e)?,
+            Err(e) if flags_unsupported(&e) => {}
+            result => result?,
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(unix)]
+const ACL_ACCESS_XATTR: &str = "system.posix_acl_access";
+#[cfg(unix)]
+const ACL_DEFAULT_XATTR: &str = "system.posix_acl_default";
+
+/// Copy the access ACL, and the default ACL of directories.
+///
+/// When the destination filesystem can't store ACLs, the mode bits are copied
+/// instead, which keeps at least the owner/group/other permissions.
+#[cfg(unix)]
+fn preserve_acl(
+    source: &Path,
+    destination: &Path,
+    src_metadata: &std::fs::Metadata,
+    policy: &PreservePolicy,
+) -> io::Result<()> {
+    if !xattr::SUPPORTED_PLATFORM {
+        return Ok(());
+    }
+
+    let mut names = vec![ACL_ACCESS_XATTR];
+    if src_metadata.is_dir() {
+        names.push(ACL_DEFAULT_XATTR);
+    }
+
+    for name in names {
+        let value = match xattr::get(source, name) {
+            Ok(value) => value,
+            Err(e) if is_unsupported(&e) => None,
+            Err(e) => return Err(e),
+        };
+
+        let result = match value {
+            Some(value) => xattr::set(destination, name, &value),
+            // A default ACL inherited from the destination's parent has no place here
+            None if name == ACL_DEFAULT_XATTR => match xattr::remove(destination, name) {
+                Err(e) if e.raw_os_error() == Some(libc::ENODATA) => Ok(()),
+                result => result,
+            },
+            None => Ok(()),
+        };
+
+        match result {
+            Ok(()) => {}
+            Err(e) if is_unsupported(&e) => {
+                policy.tolerate("acl", e)?;
+                preserve_mode(destination, src_metadata)?;
+                return Ok(());
+            }
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(unix)]
+fn is_unsupported(e: &io::Error) -> bool {
+    e.kind() == io::ErrorKind::Unsupported || e.raw_os_error() == Some(libc::EOPNOTSUPP)
+}
+
 #[cfg(all(unix, feature = "selinux-support"))]
 pub fn preserve_context(source: &Path, destination: &Path) -> io::Result<()> {
     use selinux;
@@ -323,6 +850,80 @@ mod tests {
         assert!(attr.links);
         assert!(attr.context);
         assert!(attr.xattr);
+        assert!(attr.acl);
+    }
+
+    #[test]
+    fn test_preserve_attr_from_string_acl() {
+        let attr = PreserveAttr::from_string("mode,acl").unwrap();
+        assert!(attr.mode);
+        assert!(attr.acl);
+        assert!(!attr.xattr);
+    }
+
+    #[test]
+    fn test_preserve_attr_from_string_flags() {
+        let attr = PreserveAttr::from_string("flags").unwrap();
+        assert!(attr.flags);
+        assert!(!attr.acl);
+        assert!(PreserveAttr::all().flags);
+        assert!(!PreserveAttr::none().flags);
+    }
+
+    #[test]
+    fn test_preserve_policy_strict_and_lenient() {
+        let lenient = PreservePolicy::default();
+        for _ in 0..2 {
+            lenient
+                .tolerate("ownership", io::Error::from_raw_os_error(libc::EPERM))
+                .unwrap();
+        }
+        lenient
+            .tolerate("xattr", io::Error::from_raw_os_error(libc::EOPNOTSUPP))
+            .unwrap();
+        assert_eq!(lenient.dropped(), vec![("ownership", 2), ("xattr", 1)]);
+
+        let strict = PreservePolicy::new(true);
+        assert!(
+            strict
+                .tolerate("ownership", io::Error::from_raw_os_error(libc::EPERM))
+                .is_err()
+        );
+        assert!(strict.dropped().is_empty());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_setuid_survives_ownership() {
+        let temp = TempDir::new().unwrap();
+        let source = temp.path().join("tool");
+        let dest = temp.path().join("tool.copy");
+        fs::write(&source, "#!/bin/sh\n").unwrap();
+        fs::write(&dest, "#!/bin/sh\n").unwrap();
+        fs::set_permissions(&source, fs::Permissions::from_mode(0o4755)).unwrap();
+
+        let attrs = PreserveAttr::from_string("mode,ownership").unwrap();
+        apply_preserve_attrs(
+            &source,
+            &dest,
+            attrs,
+            &PreserveRules::default(),
+            &PreservePolicy::default(),
+        )
+        .unwrap();
+
+        let mode = fs::metadata(&dest).unwrap().permissions().mode();
+        assert_eq!(mode & 0o7777, 0o4755);
+    }
+
+    #[test]
+    fn test_preserve_attr_without() {
+        let removed = PreserveAttr::from_string("ownership,context").unwrap();
+        let attr = PreserveAttr::all().without(removed);
+        assert!(!attr.ownership);
+        assert!(!attr.context);
+        assert!(attr.mode && attr.timestamps && attr.xattr && attr.links);
+        assert_eq!(PreserveAttr::none().without(removed), PreserveAttr::none());
     }
 
     #[test]
@@ -394,6 +995,112 @@ mod tests {
         assert!(diff.as_secs() < 1);
     }
 
+    #[test]
+    fn test_preserve_timestamps_keeps_atime_and_nanoseconds() {
+        use filetime::FileTime;
+
+        let temp_dir = TempDir::new().unwrap();
+        let source = temp_dir.path().join("source.txt");
+        let dest = temp_dir.path().join("dest.txt");
+        fs::write(&source, b"test").unwrap();
+        fs::write(&dest, b"test").unwrap();
+
+        let atime = FileTime::from_unix_time(1_500_000_000, 123_456_789);
+        let mtime = FileTime::from_unix_time(1_600_000_000, 987_654_321);
+        filetime::set_file_times(&source, atime, mtime).unwrap();
+
+        preserve_timestamps(&dest, &fs::metadata(&source).unwrap()).unwrap();
+
+        let dest_metadata = fs::metadata(&dest).unwrap();
+        assert_eq!(FileTime::from_last_access_time(&dest_metadata), atime);
+        assert_eq!(FileTime::from_last_modification_time(&dest_metadata), mtime);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_apply_symlink_attrs_does_not_follow() {
+        use filetime::FileTime;
+
+        let temp_dir = TempDir::new().unwrap();
+        let target = temp_dir.path().join("target.txt");
+        let source_link = temp_dir.path().join("source_link");
+        let dest_link = temp_dir.path().join("dest_link");
+        fs::write(&target, b"test").unwrap();
+        std::os::unix::fs::symlink(&target, &source_link).unwrap();
+        std::os::unix::fs::symlink(&target, &dest_link).unwrap();
+
+        let mtime = FileTime::from_unix_time(1_400_000_000, 42);
+        filetime::set_symlink_file_times(&source_link, mtime, mtime).unwrap();
+        let target_mtime = FileTime::from_last_modification_time(&fs::metadata(&target).unwrap());
+
+        let attrs = PreserveAttr {
+            timestamps: true,
+            ownership: true,
+            ..PreserveAttr::none()
+        };
+        apply_symlink_attrs(
+            &source_link,
+            &dest_link,
+            attrs,
+            &PreserveRules::default(),
+            &PreservePolicy::default(),
+        )
+        .unwrap();
+
+        let link_metadata = fs::symlink_metadata(&dest_link).unwrap();
+        assert_eq!(FileTime::from_last_modification_time(&link_metadata), mtime);
+        assert_eq!(
+            FileTime::from_last_modification_time(&fs::metadata(&target).unwrap()),
+            target_mtime
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_preserve_acl() {
+        let temp_dir = TempDir::new().unwrap();
+        let source = temp_dir.path().join("source.txt");
+        let dest = temp_dir.path().join("dest.txt");
+        fs::write(&source, b"test").unwrap();
+        fs::write(&dest, b"test").unwrap();
+
+        // user::rw- user:1000:r-- group::r-- mask::r-- other::r--
+        let mut acl = 2u32.to_le_bytes().to_vec();
+        for (tag, perm, id) in [
+            (0x01u16, 6u16, u32::MAX),
+            (0x02, 4, 1000),
+            (0x04, 4, u32::MAX),
+            (0x10, 4, u32::MAX),
+            (0x20, 4, u32::MAX),
+        ] {
+            acl.extend_from_slice(&tag.to_le_bytes());
+            acl.extend_from_slice(&perm.to_le_bytes());
+            acl.extend_from_slice(&id.to_le_bytes());
+        }
+        if xattr::set(&source, ACL_ACCESS_XATTR, &acl).is_err() {
+            // Filesystem without ACL support
+            return;
+        }
+
+        let attrs = PreserveAttr {
+            acl: true,
+            ..PreserveAttr::none()
+        };
+        apply_preserve_attrs(
+            &source,
+            &dest,
+            attrs,
+            &PreserveRules::default(),
+            &PreservePolicy::default(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            xattr::get(&dest, ACL_ACCESS_XATTR).unwrap(),
+            xattr::get(&source, ACL_ACCESS_XATTR).unwrap()
+        );
+    }
+
     #[cfg(unix)]
     #[test]
     fn test_preserve_mode() {
@@ -455,7 +1162,14 @@ mod tests {
         let mut attrs = PreserveAttr::none();
         attrs.timestamps = true;
 
-        apply_preserve_attrs(&source, &dest, attrs).unwrap();
+        apply_preserve_attrs(
+            &source,
+            &dest,
+            attrs,
+            &PreserveRules::default(),
+            &PreservePolicy::default(),
+        )
+        .unwrap();
 
         let src_mtime = fs::metadata(&source).unwrap().modified().unwrap();
         let dest_mtime = fs::metadata(&dest).unwrap().modified().unwrap();
@@ -485,7 +1199,14 @@ mod tests {
         fs::set_permissions(&source, perms).unwrap();
 
         let attrs = PreserveAttr::all();
-        apply_preserve_attrs(&source, &dest, attrs).unwrap();
+        apply_preserve_attrs(
+            &source,
+            &dest,
+            attrs,
+            &PreserveRules::default(),
+            &PreservePolicy::default(),
+        )
+        .unwrap();
 
         let dest_mode = fs::metadata(&dest).unwrap().permissions().mode() & 0o777;
         assert_eq!(dest_mode, 0o600);
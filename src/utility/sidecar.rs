@@ -0,0 +1,461 @@
+use super::idmap::{group_name, user_name};
+use super::preserve::{PreservePolicy, PreserveRules};
+use super::xattr_filter::namespace_label;
+use filetime::FileTime;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write as _;
+use std::fs::Metadata;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+
+#[cfg(unix)]
+use super::idmap::{group_id, user_id};
+#[cfg(unix)]
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+
+/// File name of the sidecar when `--metadata-sidecar` is given without a path.
+pub const SIDECAR_NAME: &str = ".cpx-metadata";
+
+/// Everything `--preserve=all` would have applied to one copied entry.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EntryMetadata {
+    pub path: String,
+    pub mode: u32,
+    pub uid: u32,
+    pub gid: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+    pub atime: (i64, u32),
+    pub mtime: (i64, u32),
+    /// Values hex-encoded; ACLs and capabilities are stored here as the xattrs they are
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub xattrs: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub flags: u32,
+}
+
+fn is_zero(flags: &u32) -> bool {
+    *flags == 0
+}
+
+/// Writes the metadata of copied entries as JSON lines, relative to the destination.
+#[derive(Debug)]
+pub struct MetadataSidecar {
+    pub path: PathBuf,
+    base: PathBuf,
+}
+
+impl MetadataSidecar {
+    pub fn new(path: PathBuf, base: PathBuf) -> Self {
+        Self { path, base }
+    }
+
+    /// Record `(source, destination)` pairs, with the xattrs `rules` would copy.
+    /// Returns how many entries were left out because their names aren't valid UTF-8.
+    pub fn write(
+        &self,
+        entries: &[(PathBuf, PathBuf)],
+        rules: &PreserveRules,
+    ) -> io::Result<usize> {
+        let mut names = NameCache::default();
+        let mut records = Vec::with_capacity(entries.len());
+        let mut skipped = 0;
+
+        for (source, destination) in entries {
+            let relative = destination.strip_prefix(&self.base).unwrap_or(destination);
+            let Some(path) = relative.to_str() else {
+                skipped += 1;
+                continue;
+            };
+            // A symlink copy describes the link itself, anything else what was read
+            let is_link = std::fs::symlink_metadata(destination)
+                .map(|m| m.file_type().is_symlink())
+                .unwrap_or(false);
+            let metadata = if is_link {
+                std::fs::symlink_metadata(source)?
+            } else {
+                std::fs::metadata(source)?
+            };
+            let (mode, uid, gid) = mode_and_owner(&metadata);
+            let atime = FileTime::from_last_access_time(&metadata);
+            let mtime = FileTime::from_last_modification_time(&metadata);
+            records.push(EntryMetadata {
+                path: path.to_string(),
+                mode,
+                uid,
+                gid,
+                user: names.user(uid),
+                group: names.group(gid),
+                atime: (atime.unix_seconds(), atime.nanoseconds()),
+                mtime: (mtime.unix_seconds(), mtime.nanoseconds()),
+                xattrs: read_xattrs(source, is_link, rules),
+                flags: read_flags(source, &metadata),
+            });
+        }
+
+        records.sort_by(|a, b| a.path.cmp(&b.path));
+        records.dedup_by(|a, b| a.path == b.path);
+
+        let mut out = io::BufWriter::new(std::fs::File::create(&self.path)?);
+        for record in &records {
+            serde_json::to_writer(&mut out, record)?;
+            out.write_all(b"\n")?;
+        }
+        out.flush()?;
+        Ok(skipped)
+    }
+}
+
+#[derive(Default)]
+struct NameCache {
+    users: HashMap<u32, Option<String>>,
+    groups: HashMap<u32, Option<String>>,
+}
+
+impl NameCache {
+    fn user(&mut self, uid: u32) -> Option<String> {
+        self.users
+            .entry(uid)
+            .or_insert_with(|| user_name(uid))
+            .clone()
+    }
+
+    fn group(&mut self, gid: u32) -> Option<String> {
+        self.groups
+            .entry(gid)
+            .or_insert_with(|| group_name(gid))
+            .clone()
+    }
+}
+
+#[cfg(unix)]
+fn mode_and_owner(metadata: &Metadata) -> (u32, u32, u32) {
+    (metadata.mode() & 0o7777, metadata.uid(), metadata.gid())
+}
+
+#[cfg(not(unix))]
+fn mode_and_owner(metadata: &Metadata) -> (u32, u32, u32) {
+    let mode = if metadata.permissions().readonly() {
+        0o444
+    } else {
+        0o644
+    };
+    (mode, 0, 0)
+}
+
+fn read_xattrs(source: &Path, is_link: bool, rules: &PreserveRules) -> BTreeMap<String, String> {
+    let mut xattrs = BTreeMap::new();
+    if !xattr::SUPPORTED_PLATFORM {
+        return xattrs;
+    }
+    let names = if is_link {
+        xattr::list(source)
+    } else {
+        xattr::list_deref(source)
+    };
+    for name in names.into_iter().flatten() {
+        let Some(key) = name.to_str().filter(|key| rules.copies_xattr(key)) else {
+            continue;
+        };
+        let value = if is_link {
+            xattr::get(source, &name)
+        } else {
+            xattr::get_deref(source, &name)
+        };
+        if let Ok(Some(value)) = value {
+            xattrs.insert(key.to_string(), to_hex(&value));
+        }
+    }
+    xattrs
+}
+
+#[cfg(target_os = "linux")]
+fn read_flags(source: &Path, metadata: &Metadata) -> u32 {
+    if !(metadata.is_file() || metadata.is_dir()) {
+        return 0;
+    }
+    super::preserve::copyable_inode_flags(source).unwrap_or(0)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn read_flags(_source: &Path, _metadata: &Metadata) -> u32 {
+    0
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len() * 2);
+    for byte in bytes {
+        let _ = write!(out, "{:02x}", byte);
+    }
+    out
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// Read a sidecar, returning its entries and the number of lines that couldn't be parsed.
+pub fn read_sidecar(path: &Path) -> io::Result<(Vec<EntryMetadata>, usize)> {
+    let reader = io::BufReader::new(std::fs::File::open(path)?);
+    let mut entries = Vec::new();
+    let mut malformed = 0;
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str(&line) {
+            Ok(entry) => entries.push(entry),
+            Err(_) => malformed += 1,
+        }
+    }
+    Ok((entries, malformed))
+}
+
+/// Reapply a recorded entry to its counterpart under `dir`.
+///
+/// Names are preferred over the recorded ids when they exist here, unless `numeric_ids`.
+/// Only the xattrs `rules` would copy are restored.
+pub fn restore_entry(
+    dir: &Path,
+    entry: &EntryMetadata,
+    numeric_ids: bool,
+    rules: &PreserveRules,
+    policy: &PreservePolicy,
+) -> io::Result<()> {
+    let path = dir.join(&entry.path);
+    let metadata = std::fs::symlink_metadata(&path)?;
+    let is_link = metadata.file_type().is_symlink();
+
+    // Same order as apply_preserve_attrs: chown would clear setuid bits and capabilities
+    #[cfg(unix)]
+    {
+        let (uid, gid) = if numeric_ids {
+            (entry.uid, entry.gid)
+        } else {
+            (
+                entry.user.as_deref().and_then(user_id).unwrap_or(entry.uid),
+                entry
+                    .group
+                    .as_deref()
+                    .and_then(group_id)
+                    .unwrap_or(entry.gid),
+            )
+        };
+        if let Err(e) = std::os::unix::fs::lchown(&path, Some(uid), Some(gid)) {
+            if !matches!(e.raw_os_error(), Some(libc::EPERM) | Some(libc::EACCES)) {
+                return Err(e);
+            }
+            policy.tolerate("ownership", e)?;
+        }
+
+        if !is_link {
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(entry.mode))?;
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = numeric_ids;
+        if !is_link {
+            let mut permissions = metadata.permissions();
+            permissions.set_readonly(entry.mode & 0o222 == 0);
+            std::fs::set_permissions(&path, permissions)?;
+        }
+    }
+
+    let mut failed = BTreeMap::new();
+    for (name, value) in entry
+        .xattrs
+        .iter()
+        .filter(|(name, _)| rules.copies_xattr(name))
+    {
+        let Some(value) = from_hex(value) else {
+            failed
+                .entry(namespace_label(name))
+                .or_insert(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("malformed value for xattr {}", name),
+                ));
+            continue;
+        };
+        if let Err(e) = xattr::set(&path, name, &value) {
+            failed.entry(namespace_label(name)).or_insert(e);
+        }
+    }
+    for (label, e) in failed {
+        policy.tolerate(label, e)?;
+    }
+
+    filetime::set_symlink_file_times(
+        &path,
+        FileTime::from_unix_time(entry.atime.0, entry.atime.1),
+        FileTime::from_unix_time(entry.mtime.0, entry.mtime.1),
+    )?;
+
+    #[cfg(target_os = "linux")]
+    if entry.flags != 0 && (metadata.is_file() || metadata.is_dir()) {
+        super::preserve::set_inode_flags(&path, entry.flags, policy)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utility::xattr_filter::XattrFilter;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_hex_roundtrip() {
+        let bytes = [0u8, 1, 0x7f, 0xff, 0x10];
+        assert_eq!(from_hex(&to_hex(&bytes)).unwrap(), bytes);
+        assert!(from_hex("abc").is_none());
+        assert!(from_hex("zz").is_none());
+    }
+
+    #[test]
+    fn test_write_read_and_restore() {
+        let temp = TempDir::new().unwrap();
+        let source = temp.path().join("source.txt");
+        let dest_dir = temp.path().join("dest");
+        fs::create_dir(&dest_dir).unwrap();
+        let dest = dest_dir.join("source.txt");
+        fs::write(&source, "data").unwrap();
+        fs::write(&dest, "data").unwrap();
+        fs::set_permissions(&source, fs::Permissions::from_mode(0o640)).unwrap();
+        let mtime = FileTime::from_unix_time(1_500_000_000, 123_456_789);
+        filetime::set_file_times(&source, mtime, mtime).unwrap();
+        let has_xattr = xattr::set(&source, "user.cpx.test", b"\x00value").is_ok();
+
+        let sidecar_path = dest_dir.join(SIDECAR_NAME);
+        let sidecar = MetadataSidecar::new(sidecar_path.clone(), dest_dir.clone());
+        let skipped = sidecar
+            .write(&[(source.clone(), dest.clone())], &PreserveRules::default())
+            .unwrap();
+        assert_eq!(skipped, 0);
+
+        let (entries, malformed) = read_sidecar(&sidecar_path).unwrap();
+        assert_eq!(malformed, 0);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].path, "source.txt");
+        assert_eq!(entries[0].mode, 0o640);
+
+        let policy = PreservePolicy::default();
+        restore_entry(
+            &dest_dir,
+            &entries[0],
+            false,
+            &PreserveRules::default(),
+            &policy,
+        )
+        .unwrap();
+        let restored = fs::metadata(&dest).unwrap();
+        assert_eq!(restored.mode() & 0o7777, 0o640);
+        assert_eq!(FileTime::from_last_modification_time(&restored), mtime);
+        if has_xattr {
+            assert_eq!(
+                xattr::get(&dest, "user.cpx.test").unwrap().unwrap(),
+                b"\x00value"
+            );
+        }
+    }
+
+    #[test]
+    fn test_xattr_filter_applies_to_record_and_restore() {
+        let temp = TempDir::new().unwrap();
+        let source = temp.path().join("source.txt");
+        fs::write(&source, "data").unwrap();
+        if xattr::set(&source, "user.keep", b"1").is_err() {
+            return;
+        }
+        xattr::set(&source, "user.com.dropbox.attrs", b"2").unwrap();
+        let rules = PreserveRules::default().with_xattr_filter(
+            XattrFilter::parse(&[], &["user.com.dropbox.*".to_string()]).unwrap(),
+        );
+
+        let sidecar_path = temp.path().join(SIDECAR_NAME);
+        let sidecar = MetadataSidecar::new(sidecar_path.clone(), temp.path().to_path_buf());
+        sidecar
+            .write(&[(source.clone(), source.clone())], &rules)
+            .unwrap();
+        let (mut entries, _) = read_sidecar(&sidecar_path).unwrap();
+        assert_eq!(
+            entries[0].xattrs.keys().collect::<Vec<_>>(),
+            vec!["user.keep"]
+        );
+
+        // A sidecar written without the filter is still filtered on restore
+        xattr::remove(&source, "user.keep").unwrap();
+        xattr::remove(&source, "user.com.dropbox.attrs").unwrap();
+        entries[0]
+            .xattrs
+            .insert("user.com.dropbox.attrs".to_string(), to_hex(b"2"));
+        restore_entry(
+            temp.path(),
+            &entries[0],
+            false,
+            &rules,
+            &PreservePolicy::default(),
+        )
+        .unwrap();
+        assert_eq!(xattr::get(&source, "user.keep").unwrap().unwrap(), b"1");
+        assert!(
+            xattr::get(&source, "user.com.dropbox.attrs")
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_restore_summarizes_failed_xattrs_by_namespace() {
+        let temp = TempDir::new().unwrap();
+        let file = temp.path().join("file.txt");
+        fs::write(&file, "data").unwrap();
+        let sidecar_path = temp.path().join(SIDECAR_NAME);
+        MetadataSidecar::new(sidecar_path.clone(), temp.path().to_path_buf())
+            .write(&[(file.clone(), file.clone())], &PreserveRules::default())
+            .unwrap();
+        let (mut entries, _) = read_sidecar(&sidecar_path).unwrap();
+        let entry = &mut entries[0];
+        entry.xattrs.insert("user.a".to_string(), "zz".to_string());
+        entry.xattrs.insert("user.b".to_string(), "0".to_string());
+        entry
+            .xattrs
+            .insert("nonsense.c".to_string(), "00".to_string());
+
+        let policy = PreservePolicy::default();
+        restore_entry(
+            temp.path(),
+            entry,
+            false,
+            &PreserveRules::default(),
+            &policy,
+        )
+        .unwrap();
+        // Each namespace counts once per entry, however many of its xattrs failed
+        assert_eq!(policy.dropped(), vec![("user.* xattrs", 1), ("xattr", 1)]);
+
+        let strict = PreservePolicy::new(true);
+        assert!(
+            restore_entry(
+                temp.path(),
+                entry,
+                false,
+                &PreserveRules::default(),
+                &strict
+            )
+            .is_err()
+        );
+    }
+}
@@ -0,0 +1,91 @@
+use globset::{Glob, GlobSet, GlobSetBuilder};
+
+/// Which extended attributes `--preserve=xattr` copies, by name pattern.
+///
+/// Without include patterns every attribute is a candidate; exclude patterns are
+/// checked afterwards, so `--xattr-include='user.*' --xattr-exclude='user.com.dropbox.*'`
+/// keeps all user attributes but Dropbox's.
+#[derive(Debug, Clone)]
+pub struct XattrFilter {
+    include: Option<GlobSet>,
+    exclude: GlobSet,
+}
+
+impl XattrFilter {
+    pub fn parse(include: &[String], exclude: &[String]) -> Result<Self, String> {
+        let include = if include.is_empty() {
+            None
+        } else {
+            Some(build_set(include)?)
+        };
+        Ok(Self {
+            include,
+            exclude: build_set(exclude)?,
+        })
+    }
+
+    pub fn allows(&self, name: &str) -> bool {
+        self.include.as_ref().is_none_or(|set| set.is_match(name)) && !self.exclude.is_match(name)
+    }
+}
+
+fn build_set(patterns: &[String]) -> Result<GlobSet, String> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        let glob = Glob::new(pattern)
+            .map_err(|e| format!("invalid xattr pattern '{}': {}", pattern, e))?;
+        builder.add(glob);
+    }
+    builder
+        .build()
+        .map_err(|e| format!("invalid xattr patterns: {}", e))
+}
+
+/// Label an attribute's namespace for the end-of-copy summary, e.g. `security.* xattrs`.
+pub fn namespace_label(name: &str) -> &'static str {
+    match name.split_once('.').map(|(namespace, _)| namespace) {
+        Some("user") => "user.* xattrs",
+        Some("trusted") => "trusted.* xattrs",
+        Some("security") => "security.* xattrs",
+        Some("system") => "system.* xattrs",
+        _ => "xattr",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn patterns(list: &[&str]) -> Vec<String> {
+        list.iter().map(|p| p.to_string()).collect()
+    }
+
+    #[test]
+    fn test_include_and_exclude() {
+        let filter =
+            XattrFilter::parse(&patterns(&["user.*"]), &patterns(&["user.com.dropbox.*"])).unwrap();
+        assert!(filter.allows("user.mime_type"));
+        assert!(!filter.allows("user.com.dropbox.attrs"));
+        assert!(!filter.allows("security.selinux"));
+    }
+
+    #[test]
+    fn test_exclude_only() {
+        let filter = XattrFilter::parse(&[], &patterns(&["security.*", "trusted.*"])).unwrap();
+        assert!(filter.allows("user.foo"));
+        assert!(filter.allows("system.posix_acl_access"));
+        assert!(!filter.allows("trusted.overlay.opaque"));
+    }
+
+    #[test]
+    fn test_invalid_pattern() {
+        assert!(XattrFilter::parse(&patterns(&["user.[a"]), &[]).is_err());
+    }
+
+    #[test]
+    fn test_namespace_label() {
+        assert_eq!(namespace_label("security.capability"), "security.* xattrs");
+        assert_eq!(namespace_label("user.a.b"), "user.* xattrs");
+        assert_eq!(namespace_label("odd"), "xattr");
+    }
+}
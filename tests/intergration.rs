@@ -1054,7 +1054,7 @@ fn test_config_init() {
     assert!(config_path.exists());
 
     let contents = fs::read_to_string(&config_path).unwrap();
-    assert!(contents.contains("[exclude]"));
+    assert!(contents.contains("[filter]"));
     assert!(contents.contains("[copy]"));
     assert!(contents.contains("[preserve]"));
 }
@@ -1498,3 +1498,1802 @@ fn test_copy_empty_directory() {
     assert!(dest_dir.child("empty_source").path().exists());
     assert!(dest_dir.child("empty_source").path().is_dir());
 }
+
+#[test]
+fn test_progress_json_events() {
+    let temp = assert_fs::TempDir::new().unwrap();
+    let source_dir = temp.child("source");
+    let dest_dir = temp.child("dest");
+
+    source_dir.child("a.txt").write_str("alpha").unwrap();
+    source_dir.child("b.txt").write_str("beta").unwrap();
+
+    let output = Command::new(cargo::cargo_bin!("cpx"))
+        .arg("--no-config")
+        .arg("-r")
+        .arg("--progress=json")
+        .arg(source_dir.path())
+        .arg(dest_dir.path())
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let events: Vec<serde_json::Value> = stderr
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+
+    assert!(events.iter().all(|e| e["schema"] == 1));
+    assert_eq!(events.first().unwrap()["event"], "start");
+    assert_eq!(events.last().unwrap()["event"], "summary");
+    assert_eq!(events.last().unwrap()["files"], 2);
+    let finished = events
+        .iter()
+        .filter(|e| e["event"] == "file_finish")
+        .count();
+    assert_eq!(finished, 2);
+
+    dest_dir.child("source/a.txt").assert("alpha");
+}
+
+#[test]
+fn test_progress_json_keeps_messages_in_the_stream() {
+    let temp = assert_fs::TempDir::new().unwrap();
+    let source_dir = temp.child("source");
+    source_dir.child("a.txt").write_str("alpha").unwrap();
+    source_dir.child("skip.log").write_str("log").unwrap();
+
+    let output = Command::new(cargo::cargo_bin!("cpx"))
+        .arg("--no-config")
+        .arg("-r")
+        .arg("-s")
+        .arg("absolute")
+        .arg("--progress=json")
+        .arg("--explain-excludes")
+        .arg("-e")
+        .arg("*.log")
+        .arg(source_dir.path())
+        .arg(temp.child("dest").path())
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    // Nothing but JSON lines on either stream
+    assert!(output.stdout.is_empty(), "{:?}", output.stdout);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let events: Vec<serde_json::Value> = stderr
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    let infos: Vec<_> = events.iter().filter(|e| e["event"] == "info").collect();
+    assert!(infos.iter().any(|e| {
+        e["source"].as_str().unwrap().ends_with("skip.log")
+            && e["message"].as_str().unwrap().contains("'*.log'")
+    }));
+    assert!(
+        infos
+            .iter()
+            .any(|e| e["message"] == "Created 1 symbolic links")
+    );
+}
+
+#[test]
+fn test_progress_fd_requires_json() {
+    let temp = assert_fs::TempDir::new().unwrap();
+    let source = temp.child("source.txt");
+    source.write_str("content").unwrap();
+
+    Command::new(cargo::cargo_bin!("cpx"))
+        .arg("--no-config")
+        .arg("--progress=bar")
+        .arg("--progress-fd")
+        .arg("2")
+        .arg(source.path())
+        .arg(temp.child("dest.txt").path())
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--progress=json"));
+}
+
+#[test]
+fn test_progress_json_no_start_on_invalid_args() {
+    let temp = assert_fs::TempDir::new().unwrap();
+    let source = temp.child("source.txt");
+    source.write_str("content").unwrap();
+
+    Command::new(cargo::cargo_bin!("cpx"))
+        .arg("--no-config")
+        .arg("--progress=json")
+        .arg("--reflink")
+        .arg("--link")
+        .arg(source.path())
+        .arg(temp.child("dest.txt").path())
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("\"event\"").not());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_resume_with_xattr_hash_cache() {
+    let temp = assert_fs::TempDir::new().unwrap();
+    let source = temp.child("source.txt");
+    let dest_dir = temp.child("dest");
+    let dest = dest_dir.child("source.txt");
+
+    dest_dir.create_dir_all().unwrap();
+    dest.write_str("Same content").unwrap();
+    source.write_str("Same content").unwrap();
+
+    // Destination older than source forces the checksum comparison
+    filetime::set_file_mtime(
+        dest.path(),
+        filetime::FileTime::from_unix_time(1_000_000, 0),
+    )
+    .unwrap();
+
+    Command::new(cargo::cargo_bin!("cpx"))
+        .arg("--no-config")
+        .arg("--resume")
+        .arg("--hash-cache=xattr")
+        .arg(source.path())
+        .arg(dest_dir.path())
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Skipping"));
+
+    if xattr::set(source.path(), "user.cpx.probe", b"1").is_ok() {
+        assert!(xattr::get(dest.path(), "user.cpx.xxh3").unwrap().is_some());
+        assert!(
+            xattr::get(source.path(), "user.cpx.xxh3")
+                .unwrap()
+                .is_some()
+        );
+    }
+}
+
+#[test]
+fn test_manifest_and_verify() {
+    let temp = assert_fs::TempDir::new().unwrap();
+    let source_dir = temp.child("source");
+    let dest_dir = temp.child("dest");
+    let manifest = temp.child("SHA256SUMS");
+
+    source_dir.child("abc.txt").write_str("abc").unwrap();
+    source_dir
+        .child("sub/other.txt")
+        .write_str("other")
+        .unwrap();
+    dest_dir.create_dir_all().unwrap();
+
+    Command::new(cargo::cargo_bin!("cpx"))
+        .arg("--no-config")
+        .arg("-r")
+        .arg("--manifest")
+        .arg(manifest.path())
+        .arg("--hash=sha256")
+        .arg(source_dir.path())
+        .arg(dest_dir.path())
+        .assert()
+        .success();
+
+    let content = fs::read_to_string(manifest.path()).unwrap();
+    assert!(content.contains(
+        "SHA256 (source/abc.txt) = ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+    ));
+    assert!(content.contains("SHA256 (source/sub/other.txt) = "));
+
+    Command::new(cargo::cargo_bin!("cpx"))
+        .arg("verify")
+        .arg("--manifest")
+        .arg(manifest.path())
+        .arg(dest_dir.path())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("source/abc.txt: OK"));
+
+    dest_dir.child("source/abc.txt").write_str("abd").unwrap();
+
+    Command::new(cargo::cargo_bin!("cpx"))
+        .arg("verify")
+        .arg("--manifest")
+        .arg(manifest.path())
+        .arg(dest_dir.path())
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("source/abc.txt: FAILED"));
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_manifest_with_reflink_and_non_utf8_names() {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+
+    let temp = assert_fs::TempDir::new().unwrap();
+    let source_dir = temp.child("source");
+    let dest_dir = temp.child("dest");
+    let manifest = temp.child("B3SUMS");
+
+    source_dir.create_dir_all().unwrap();
+    fs::write(
+        source_dir.path().join(OsStr::from_bytes(b"caf\xe9.txt")),
+        "latin-1 name",
+    )
+    .unwrap();
+    source_dir.child("plain.txt").write_str("plain").unwrap();
+
+    Command::new(cargo::cargo_bin!("cpx"))
+        .arg("--no-config")
+        .arg("-r")
+        .arg("--reflink=auto")
+        .arg("--manifest")
+        .arg(manifest.path())
+        .arg("--hash=blake3")
+        .arg(source_dir.path())
+        .arg(dest_dir.path())
+        .assert()
+        .success();
+
+    assert_eq!(
+        fs::read(manifest.path())
+            .unwrap()
+            .split(|&b| b == b'\n')
+            .count(),
+        3
+    );
+
+    // The algorithm comes from the manifest, not from a --hash default
+    Command::new(cargo::cargo_bin!("cpx"))
+        .arg("verify")
+        .arg("--manifest")
+        .arg(manifest.path())
+        .arg(dest_dir.path())
+        .assert()
+        .success();
+}
+
+#[test]
+fn test_manifest_conflicts_with_link() {
+    let temp = assert_fs::TempDir::new().unwrap();
+    let source = temp.child("source.txt");
+    source.write_str("content").unwrap();
+
+    Command::new(cargo::cargo_bin!("cpx"))
+        .arg("--no-config")
+        .arg("-l")
+        .arg("--manifest")
+        .arg(temp.child("SUMS").path())
+        .arg(source.path())
+        .arg(temp.child("dest.txt").path())
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--manifest"));
+}
+
+#[test]
+fn test_manifest_conflicts_with_reflink_always() {
+    let temp = assert_fs::TempDir::new().unwrap();
+    let source = temp.child("source.txt");
+    source.write_str("content").unwrap();
+
+    Command::new(cargo::cargo_bin!("cpx"))
+        .arg("--no-config")
+        .arg("--reflink=always")
+        .arg("--manifest")
+        .arg(temp.child("SUMS").path())
+        .arg(source.path())
+        .arg(temp.child("dest.txt").path())
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--reflink=always"));
+    temp.child("dest.txt").assert(predicate::path::missing());
+}
+
+#[cfg(unix)]
+fn make_fifo(path: &std::path::Path) {
+    use std::os::unix::ffi::OsStrExt;
+    let path = std::ffi::CString::new(path.as_os_str().as_bytes()).unwrap();
+    assert_eq!(unsafe { libc::mkfifo(path.as_ptr(), 0o640) }, 0);
+}
+
+#[test]
+#[cfg(unix)]
+fn test_fifo_is_recreated_not_read() {
+    use std::os::unix::fs::FileTypeExt;
+
+    let source_dir = assert_fs::TempDir::new().unwrap();
+    let dest_dir = assert_fs::TempDir::new().unwrap();
+    source_dir.child("file.txt").write_str("content").unwrap();
+    make_fifo(source_dir.child("pipe").path());
+
+    assert_cmd::Command::new(cargo::cargo_bin!("cpx"))
+        .arg("--no-config")
+        .arg("-r")
+        .arg("-p")
+        .arg("mode")
+        .arg(source_dir.path())
+        .arg(dest_dir.path())
+        .timeout(std::time::Duration::from_secs(30))
+        .assert()
+        .success();
+
+    let copied = dest_dir.path().join(source_dir.path().file_name().unwrap());
+    let metadata = fs::symlink_metadata(copied.join("pipe")).unwrap();
+    assert!(metadata.file_type().is_fifo());
+    assert_eq!(metadata.mode() & 0o777, 0o640);
+    assert_eq!(
+        fs::read_to_string(copied.join("file.txt")).unwrap(),
+        "content"
+    );
+}
+
+#[test]
+#[cfg(unix)]
+fn test_special_skip_leaves_fifo_out() {
+    let source_dir = assert_fs::TempDir::new().unwrap();
+    let dest_dir = assert_fs::TempDir::new().unwrap();
+    source_dir.child("file.txt").write_str("content").unwrap();
+    make_fifo(source_dir.child("pipe").path());
+
+    assert_cmd::Command::new(cargo::cargo_bin!("cpx"))
+        .arg("--no-config")
+        .arg("-r")
+        .arg("--special=skip")
+        .arg(source_dir.path())
+        .arg(dest_dir.path())
+        .timeout(std::time::Duration::from_secs(30))
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Skipping special file"));
+
+    let copied = dest_dir.path().join(source_dir.path().file_name().unwrap());
+    assert!(copied.join("file.txt").exists());
+    assert!(fs::symlink_metadata(copied.join("pipe")).is_err());
+
+    let output = assert_cmd::Command::new(cargo::cargo_bin!("cpx"))
+        .arg("--no-config")
+        .arg("-r")
+        .arg("--special=skip")
+        .arg("--progress=json")
+        .arg(source_dir.path())
+        .arg(dest_dir.path().join("json"))
+        .timeout(std::time::Duration::from_secs(30))
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let events: Vec<serde_json::Value> = String::from_utf8(output.stderr)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    let warning = events
+        .iter()
+        .find(|e| e["event"] == "warning")
+        .expect("a warning event");
+    assert!(warning["source"].as_str().unwrap().ends_with("pipe"));
+    assert_eq!(warning["file_type"], "fifo");
+}
+
+/// Mark `path` with `chattr +d`, returning false where the filesystem has no inode flags.
+#[cfg(target_os = "linux")]
+fn set_nodump(path: &std::path::Path) -> bool {
+    std::process::Command::new("chattr")
+        .arg("+d")
+        .arg(path)
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn test_exclude_nodump_prunes_flagged_entries() {
+    let source_dir = assert_fs::TempDir::new().unwrap();
+    let dest_dir = assert_fs::TempDir::new().unwrap();
+    source_dir.child("keep.txt").write_str("keep").unwrap();
+    source_dir
+        .child("scratch.bin")
+        .write_str("scratch")
+        .unwrap();
+    source_dir
+        .child("cache/entry.txt")
+        .write_str("cached")
+        .unwrap();
+    if !set_nodump(source_dir.child("scratch.bin").path())
+        || !set_nodump(source_dir.child("cache").path())
+    {
+        eprintln!("Skipping: inode flags not supported here");
+        return;
+    }
+
+    assert_cmd::Command::new(cargo::cargo_bin!("cpx"))
+        .arg("--no-config")
+        .arg("-r")
+        .arg("--exclude-nodump")
+        .arg(source_dir.path())
+        .arg(dest_dir.path())
+        .timeout(std::time::Duration::from_secs(30))
+        .assert()
+        .success();
+
+    let copied = dest_dir.path().join(source_dir.path().file_name().unwrap());
+    assert!(copied.join("keep.txt").exists());
+    assert!(!copied.join("scratch.bin").exists());
+    assert!(!copied.join("cache").exists());
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn test_preserve_flags_copies_nodump() {
+    let source_dir = assert_fs::TempDir::new().unwrap();
+    let dest_dir = assert_fs::TempDir::new().unwrap();
+    let source = source_dir.child("flagged.txt");
+    source.write_str("content").unwrap();
+    if !set_nodump(source.path()) {
+        eprintln!("Skipping: inode flags not supported here");
+        return;
+    }
+
+    assert_cmd::Command::new(cargo::cargo_bin!("cpx"))
+        .arg("--no-config")
+        .arg("-p")
+        .arg("flags")
+        .arg(source.path())
+        .arg(dest_dir.path())
+        .timeout(std::time::Duration::from_secs(30))
+        .assert()
+        .success();
+
+    let output = std::process::Command::new("lsattr")
+        .arg("-d")
+        .arg(dest_dir.path().join("flagged.txt"))
+        .output()
+        .unwrap();
+    let attrs = String::from_utf8_lossy(&output.stdout);
+    assert!(attrs.split_whitespace().next().unwrap().contains('d'));
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn test_preserve_flags_immutable_hard_links() {
+    let chattr = |flag: &str, path: &std::path::Path| {
+        std::process::Command::new("chattr")
+            .arg(flag)
+            .arg(path)
+            .status()
+            .is_ok_and(|status| status.success())
+    };
+    let source_dir = assert_fs::TempDir::new().unwrap();
+    let dest_dir = assert_fs::TempDir::new().unwrap();
+    let locked = source_dir.child("locked.txt");
+    locked.write_str("content").unwrap();
+    fs::hard_link(locked.path(), source_dir.child("alias.txt").path()).unwrap();
+    // +i needs CAP_LINUX_IMMUTABLE
+    if !chattr("+i", locked.path()) {
+        eprintln!("Skipping: can't set immutable flags here");
+        return;
+    }
+
+    let output = Command::new(cargo::cargo_bin!("cpx"))
+        .arg("--no-config")
+        .arg("-r")
+        .arg("-j")
+        .arg("1")
+        .arg("-p")
+        .arg("links,flags")
+        .arg(source_dir.path())
+        .arg(dest_dir.path())
+        .output()
+        .unwrap();
+
+    let copied = dest_dir.path().join(source_dir.path().file_name().unwrap());
+    let copies = [copied.join("locked.txt"), copied.join("alias.txt")];
+    let lsattr = std::process::Command::new("lsattr")
+        .args(&copies)
+        .output()
+        .unwrap();
+    for path in copies.iter().chain([&locked.path().to_path_buf()]) {
+        chattr("-i", path);
+    }
+
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        fs::metadata(&copies[0]).unwrap().ino(),
+        fs::metadata(&copies[1]).unwrap().ino()
+    );
+    let lsattr = String::from_utf8_lossy(&lsattr.stdout);
+    assert!(
+        lsattr
+            .lines()
+            .all(|line| line.split_whitespace().next().unwrap().contains('i'))
+    );
+}
+
+/// An ACL in the kernel's xattr format: user::rw- user:1000:r-- group::r-- mask::r-- other::r--
+#[cfg(target_os = "linux")]
+fn sample_acl() -> Vec<u8> {
+    let mut acl = 2u32.to_le_bytes().to_vec();
+    for (tag, perm, id) in [
+        (0x01u16, 6u16, u32::MAX),
+        (0x02, 4, 1000),
+        (0x04, 4, u32::MAX),
+        (0x10, 4, u32::MAX),
+        (0x20, 4, u32::MAX),
+    ] {
+        acl.extend_from_slice(&tag.to_le_bytes());
+        acl.extend_from_slice(&perm.to_le_bytes());
+        acl.extend_from_slice(&id.to_le_bytes());
+    }
+    acl
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn test_preserve_acl_copies_access_and_default_acls() {
+    let source_dir = assert_fs::TempDir::new().unwrap();
+    let dest_dir = assert_fs::TempDir::new().unwrap();
+    let shared = source_dir.child("shared");
+    shared.child("notes.txt").write_str("notes").unwrap();
+    if xattr::set(
+        shared.child("notes.txt").path(),
+        "system.posix_acl_access",
+        &sample_acl(),
+    )
+    .is_err()
+        || xattr::set(shared.path(), "system.posix_acl_default", &sample_acl()).is_err()
+    {
+        eprintln!("Skipping: ACLs not supported here");
+        return;
+    }
+
+    Command::new(cargo::cargo_bin!("cpx"))
+        .arg("--no-config")
+        .arg("-r")
+        .arg("-p")
+        .arg("acl")
+        .arg(shared.path())
+        .arg(dest_dir.path())
+        .assert()
+        .success();
+
+    let copied = dest_dir.child("shared");
+    assert_eq!(
+        xattr::get(copied.child("notes.txt").path(), "system.posix_acl_access").unwrap(),
+        Some(sample_acl())
+    );
+    assert_eq!(
+        xattr::get(copied.path(), "system.posix_acl_default").unwrap(),
+        Some(sample_acl())
+    );
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn test_copy_pseudo_filesystem_file() {
+    let temp = assert_fs::TempDir::new().unwrap();
+    let dest = temp.child("status");
+
+    Command::new(cargo::cargo_bin!("cpx"))
+        .arg("--no-config")
+        .arg("/proc/self/status")
+        .arg(dest.path())
+        .assert()
+        .success();
+
+    // /proc reports a size of 0, the data only shows up when read
+    let content = fs::read_to_string(dest.path()).unwrap();
+    assert!(content.contains("Pid:"));
+}
+
+#[test]
+fn test_on_source_change_rejects_unknown_mode() {
+    let temp = assert_fs::TempDir::new().unwrap();
+    let source = temp.child("source.txt");
+    source.write_str("content").unwrap();
+
+    Command::new(cargo::cargo_bin!("cpx"))
+        .arg("--no-config")
+        .arg("--on-source-change=ignore")
+        .arg(source.path())
+        .arg(temp.child("dest.txt").path())
+        .assert()
+        .failure();
+
+    Command::new(cargo::cargo_bin!("cpx"))
+        .arg("--no-config")
+        .arg("--on-source-change=fail")
+        .arg(source.path())
+        .arg(temp.child("dest.txt").path())
+        .assert()
+        .success();
+    temp.child("dest.txt").assert("content");
+}
+
+#[test]
+fn test_source_change_retry_keeps_backup() {
+    use std::io::Write;
+
+    let temp = assert_fs::TempDir::new().unwrap();
+    let source = temp.child("growing.bin");
+    let dest = temp.child("dest.bin");
+
+    source.write_binary(&vec![b'x'; 32 * 1024 * 1024]).unwrap();
+    dest.write_str("old content").unwrap();
+
+    let mut child = Command::new(cargo::cargo_bin!("cpx"))
+        .arg("--no-config")
+        .arg("-b")
+        .arg("simple")
+        .arg("--on-source-change=retry")
+        .arg("--source-change-retries=2")
+        .arg(source.path())
+        .arg(dest.path())
+        .spawn()
+        .unwrap();
+
+    // Keep appending to the source, so every attempt sees it change
+    let mut writer = fs::OpenOptions::new()
+        .append(true)
+        .open(source.path())
+        .unwrap();
+    let status = loop {
+        if let Some(status) = child.try_wait().unwrap() {
+            break status;
+        }
+        writer.write_all(b"y").unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(1));
+    };
+
+    // Reported as changed, and the retries didn't back up the torn copies
+    assert_eq!(status.code(), Some(3));
+    temp.child("dest.bin~").assert("old content");
+    assert!(fs::metadata(dest.path()).unwrap().len() >= 32 * 1024 * 1024);
+}
+
+#[test]
+fn test_source_change_fail_has_its_own_exit_code() {
+    use std::io::Write;
+
+    let temp = assert_fs::TempDir::new().unwrap();
+    let source = temp.child("growing.bin");
+    let dest = temp.child("dest.bin");
+    source.write_binary(&vec![b'x'; 32 * 1024 * 1024]).unwrap();
+
+    let mut child = Command::new(cargo::cargo_bin!("cpx"))
+        .arg("--no-config")
+        .arg("--on-source-change=fail")
+        .arg(source.path())
+        .arg(dest.path())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    let mut writer = fs::OpenOptions::new()
+        .append(true)
+        .open(source.path())
+        .unwrap();
+    while child.try_wait().unwrap().is_none() {
+        writer.write_all(b"y").unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(1));
+    }
+    let output = child.wait_with_output().unwrap();
+
+    assert_eq!(output.status.code(), Some(4));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Source changed during copy"));
+    dest.assert(predicate::path::missing());
+}
+
+#[test]
+#[cfg(unix)]
+fn test_source_change_retry_keeps_hard_links() {
+    use std::io::Write;
+    use std::os::unix::fs::MetadataExt;
+
+    let temp = assert_fs::TempDir::new().unwrap();
+    let source_dir = temp.child("source");
+    let dest_dir = temp.child("dest");
+    let manifest = temp.child("SHA256SUMS");
+    let original = source_dir.child("original.bin");
+
+    source_dir.create_dir_all().unwrap();
+    original
+        .write_binary(&vec![b'x'; 32 * 1024 * 1024])
+        .unwrap();
+    fs::hard_link(original.path(), source_dir.child("alias.bin").path()).unwrap();
+
+    let mut child = Command::new(cargo::cargo_bin!("cpx"))
+        .arg("--no-config")
+        .arg("-r")
+        .arg("-j")
+        .arg("1")
+        .arg("-p")
+        .arg("links")
+        .arg("--manifest")
+        .arg(manifest.path())
+        .arg("--on-source-change=retry")
+        .arg("--source-change-retries=2")
+        .arg(source_dir.path())
+        .arg(dest_dir.path())
+        .spawn()
+        .unwrap();
+
+    let mut writer = fs::OpenOptions::new()
+        .append(true)
+        .open(original.path())
+        .unwrap();
+    let status = loop {
+        if let Some(status) = child.try_wait().unwrap() {
+            break status;
+        }
+        writer.write_all(b"y").unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(1));
+    };
+    assert_eq!(status.code(), Some(3));
+
+    // The retries rewrote the shared copy instead of replacing one of its names
+    let copied = dest_dir.path().join("source");
+    let first = fs::metadata(copied.join("original.bin")).unwrap();
+    let second = fs::metadata(copied.join("alias.bin")).unwrap();
+    assert_eq!(first.ino(), second.ino());
+    assert_eq!(first.nlink(), 2);
+
+    let content = fs::read_to_string(manifest.path()).unwrap();
+    assert_eq!(content.lines().count(), 2);
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn test_out_of_space_is_reported() {
+    let temp = assert_fs::TempDir::new().unwrap();
+    let source = temp.child("source.txt");
+    source.write_str("does not fit").unwrap();
+
+    // Every write to /dev/full fails with ENOSPC
+    Command::new(cargo::cargo_bin!("cpx"))
+        .arg("--no-config")
+        .arg(source.path())
+        .arg("/dev/full")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Destination is out of space"))
+        .stderr(predicate::str::contains("12 B still needed"));
+
+    assert!(std::path::Path::new("/dev/full").exists());
+}
+
+#[cfg(unix)]
+fn cpx_with_umask(umask: &str) -> Command {
+    let mut cmd = Command::new("sh");
+    cmd.arg("-c")
+        .arg(format!("umask {} && exec \"$@\"", umask))
+        .arg("sh")
+        .arg(cargo::cargo_bin!("cpx"))
+        .arg("--no-config");
+    cmd
+}
+
+#[test]
+#[cfg(unix)]
+fn test_new_files_get_source_mode_minus_umask() {
+    let source_dir = assert_fs::TempDir::new().unwrap();
+    let dest_dir = assert_fs::TempDir::new().unwrap();
+    let script = source_dir.child("run.sh");
+    script.write_str("#!/bin/sh\n").unwrap();
+    fs::set_permissions(script.path(), fs::Permissions::from_mode(0o4755)).unwrap();
+    source_dir
+        .child("locked/file.txt")
+        .write_str("data")
+        .unwrap();
+    fs::set_permissions(
+        source_dir.child("locked").path(),
+        fs::Permissions::from_mode(0o555),
+    )
+    .unwrap();
+
+    cpx_with_umask("027")
+        .arg("-r")
+        .arg(source_dir.path())
+        .arg(dest_dir.path())
+        .assert()
+        .success();
+
+    let copied = dest_dir.path().join(source_dir.path().file_name().unwrap());
+    let mode = |path: std::path::PathBuf| fs::metadata(path).unwrap().mode() & 0o7777;
+    // setuid is dropped, the exec bits survive and the umask applies
+    assert_eq!(mode(copied.join("run.sh")), 0o750);
+    assert_eq!(mode(copied.join("locked/file.txt")), 0o640);
+    // A read-only source directory is filled first and gets its mode afterwards
+    assert_eq!(mode(copied.join("locked")), 0o550);
+
+    fs::set_permissions(
+        source_dir.child("locked").path(),
+        fs::Permissions::from_mode(0o755),
+    )
+    .unwrap();
+    fs::set_permissions(copied.join("locked"), fs::Permissions::from_mode(0o755)).unwrap();
+}
+
+#[test]
+#[cfg(unix)]
+fn test_existing_destination_mode_handling() {
+    let temp = assert_fs::TempDir::new().unwrap();
+    let source = temp.child("source.txt");
+    source.write_str("new").unwrap();
+    fs::set_permissions(source.path(), fs::Permissions::from_mode(0o755)).unwrap();
+    let dest = temp.child("dest.txt");
+    dest.write_str("old").unwrap();
+    fs::set_permissions(dest.path(), fs::Permissions::from_mode(0o600)).unwrap();
+
+    cpx_with_umask("077")
+        .arg(source.path())
+        .arg(dest.path())
+        .assert()
+        .success();
+    assert_eq!(fs::metadata(dest.path()).unwrap().mode() & 0o7777, 0o600);
+
+    // --preserve=mode sets the exact source mode, regardless of the umask
+    cpx_with_umask("077")
+        .arg("--preserve=mode")
+        .arg(source.path())
+        .arg(dest.path())
+        .assert()
+        .success();
+    assert_eq!(fs::metadata(dest.path()).unwrap().mode() & 0o7777, 0o755);
+    dest.assert("new");
+}
+
+#[test]
+#[cfg(unix)]
+fn test_directory_metadata_applied_after_contents() {
+    let source_dir = assert_fs::TempDir::new().unwrap();
+    let dest_dir = assert_fs::TempDir::new().unwrap();
+    source_dir.child("sub/file.txt").write_str("data").unwrap();
+    let sub = source_dir.child("sub");
+    fs::set_permissions(sub.path(), fs::Permissions::from_mode(0o555)).unwrap();
+    for dir in [sub.path(), source_dir.path()] {
+        filetime::set_file_mtime(dir, filetime::FileTime::from_unix_time(1_000_000, 0)).unwrap();
+    }
+
+    Command::new(cargo::cargo_bin!("cpx"))
+        .arg("--no-config")
+        .arg("-r")
+        .arg("-p")
+        .arg("mode,timestamps")
+        .arg(source_dir.path())
+        .arg(dest_dir.path())
+        .assert()
+        .success();
+
+    let copied = dest_dir.path().join(source_dir.path().file_name().unwrap());
+    for dir in [copied.clone(), copied.join("sub")] {
+        assert_eq!(fs::metadata(&dir).unwrap().mtime(), 1_000_000);
+    }
+    assert_eq!(
+        fs::metadata(copied.join("sub")).unwrap().mode() & 0o777,
+        0o555
+    );
+    assert_eq!(
+        fs::read_to_string(copied.join("sub/file.txt")).unwrap(),
+        "data"
+    );
+
+    fs::set_permissions(sub.path(), fs::Permissions::from_mode(0o755)).unwrap();
+    fs::set_permissions(copied.join("sub"), fs::Permissions::from_mode(0o755)).unwrap();
+}
+
+#[test]
+#[cfg(unix)]
+fn test_parents_directories_take_source_attributes() {
+    let temp = assert_fs::TempDir::new().unwrap();
+    let dest_dir = assert_fs::TempDir::new().unwrap();
+    temp.child("a/b/file.txt").write_str("data").unwrap();
+    fs::set_permissions(temp.child("a").path(), fs::Permissions::from_mode(0o750)).unwrap();
+    filetime::set_file_mtime(
+        temp.child("a/b").path(),
+        filetime::FileTime::from_unix_time(2_000_000, 0),
+    )
+    .unwrap();
+
+    Command::new(cargo::cargo_bin!("cpx"))
+        .current_dir(temp.path())
+        .arg("--no-config")
+        .arg("--parents")
+        .arg("-p")
+        .arg("mode,timestamps")
+        .arg("a/b/file.txt")
+        .arg(dest_dir.path())
+        .assert()
+        .success();
+
+    dest_dir.child("a/b/file.txt").assert("data");
+    assert_eq!(
+        fs::metadata(dest_dir.child("a").path()).unwrap().mode() & 0o777,
+        0o750
+    );
+    assert_eq!(
+        fs::metadata(dest_dir.child("a/b").path()).unwrap().mtime(),
+        2_000_000
+    );
+}
+
+#[test]
+#[cfg(unix)]
+fn test_preserve_timestamps_exactly_including_symlinks() {
+    use filetime::FileTime;
+
+    let source_dir = assert_fs::TempDir::new().unwrap();
+    let dest_dir = assert_fs::TempDir::new().unwrap();
+    let file = source_dir.child("file.txt");
+    file.write_str("data").unwrap();
+    symlink("file.txt", source_dir.child("link").path()).unwrap();
+
+    let atime = FileTime::from_unix_time(1_500_000_000, 111_111_111);
+    let mtime = FileTime::from_unix_time(1_600_000_000, 222_222_222);
+    let link_mtime = FileTime::from_unix_time(1_300_000_000, 333_333_333);
+    filetime::set_file_times(file.path(), atime, mtime).unwrap();
+    filetime::set_symlink_file_times(source_dir.child("link").path(), link_mtime, link_mtime)
+        .unwrap();
+
+    Command::new(cargo::cargo_bin!("cpx"))
+        .arg("--no-config")
+        .arg("-r")
+        .arg("-P")
+        .arg("-p")
+        .arg("timestamps,ownership")
+        .arg(source_dir.path())
+        .arg(dest_dir.path())
+        .assert()
+        .success();
+
+    let copied = dest_dir.path().join(source_dir.path().file_name().unwrap());
+    let file_metadata = fs::metadata(copied.join("file.txt")).unwrap();
+    assert_eq!(FileTime::from_last_access_time(&file_metadata), atime);
+    assert_eq!(FileTime::from_last_modification_time(&file_metadata), mtime);
+
+    let link_metadata = fs::symlink_metadata(copied.join("link")).unwrap();
+    assert!(link_metadata.file_type().is_symlink());
+    assert_eq!(
+        FileTime::from_last_modification_time(&link_metadata),
+        link_mtime
+    );
+}
+
+#[test]
+#[cfg(unix)]
+fn test_no_preserve_subtracts_from_preserve_all() {
+    use filetime::FileTime;
+    use std::os::unix::fs::PermissionsExt;
+
+    let source_dir = assert_fs::TempDir::new().unwrap();
+    let dest_dir = assert_fs::TempDir::new().unwrap();
+    let file = source_dir.child("file.txt");
+    file.write_str("data").unwrap();
+    fs::set_permissions(file.path(), fs::Permissions::from_mode(0o640)).unwrap();
+    let mtime = FileTime::from_unix_time(1_600_000_000, 0);
+    filetime::set_file_mtime(file.path(), mtime).unwrap();
+
+    Command::new(cargo::cargo_bin!("cpx"))
+        .arg("--no-config")
+        .arg("-p")
+        .arg("all")
+        .arg("--no-preserve=timestamps,ownership")
+        .arg(file.path())
+        .arg(dest_dir.path())
+        .assert()
+        .success();
+
+    let metadata = fs::metadata(dest_dir.path().join("file.txt")).unwrap();
+    assert_eq!(metadata.permissions().mode() & 0o777, 0o640);
+    assert_ne!(FileTime::from_last_modification_time(&metadata), mtime);
+}
+
+#[test]
+#[cfg(unix)]
+fn test_chown_and_usermap() {
+    use std::os::unix::fs::MetadataExt;
+
+    // Changing ownership needs root
+    if unsafe { libc::geteuid() } != 0 {
+        return;
+    }
+
+    let source_dir = assert_fs::TempDir::new().unwrap();
+    let dest_dir = assert_fs::TempDir::new().unwrap();
+    let file = source_dir.child("file.txt");
+    file.write_str("data").unwrap();
+    std::os::unix::fs::chown(file.path(), Some(1234), Some(1234)).unwrap();
+
+    Command::new(cargo::cargo_bin!("cpx"))
+        .arg("--no-config")
+        .arg("-p")
+        .arg("ownership")
+        .arg("--usermap=1000-1999:4321")
+        .arg("--groupmap=1234:4444")
+        .arg(file.path())
+        .arg(dest_dir.path().join("mapped.txt"))
+        .assert()
+        .success();
+    let metadata = fs::metadata(dest_dir.path().join("mapped.txt")).unwrap();
+    assert_eq!((metadata.uid(), metadata.gid()), (4321, 4444));
+
+    Command::new(cargo::cargo_bin!("cpx"))
+        .arg("--no-config")
+        .arg("--chown=:5555")
+        .arg(file.path())
+        .arg(dest_dir.path().join("chowned.txt"))
+        .assert()
+        .success();
+    let metadata = fs::metadata(dest_dir.path().join("chowned.txt")).unwrap();
+    assert_eq!((metadata.uid(), metadata.gid()), (1234, 5555));
+}
+
+#[test]
+#[cfg(unix)]
+fn test_chown_with_preserve_strict() {
+    use std::os::unix::fs::MetadataExt;
+
+    // Changing ownership needs root
+    if unsafe { libc::geteuid() } != 0 {
+        return;
+    }
+
+    let temp = assert_fs::TempDir::new().unwrap();
+    let file = temp.child("file.txt");
+    file.write_str("data").unwrap();
+
+    // --preserve-strict must not drop the owner mapping
+    Command::new(cargo::cargo_bin!("cpx"))
+        .arg("--no-config")
+        .arg("--preserve-strict")
+        .arg("--chown=1234:5555")
+        .arg(file.path())
+        .arg(temp.child("chowned.txt").path())
+        .assert()
+        .success();
+    let metadata = fs::metadata(temp.child("chowned.txt").path()).unwrap();
+    assert_eq!((metadata.uid(), metadata.gid()), (1234, 5555));
+}
+
+#[test]
+#[cfg(unix)]
+fn test_chmod_rules_rewrite_files_and_directories() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let source_dir = assert_fs::TempDir::new().unwrap();
+    let dest_dir = assert_fs::TempDir::new().unwrap();
+    let file = source_dir.child("sub/run.sh");
+    file.write_str("#!/bin/sh\n").unwrap();
+    fs::set_permissions(file.path(), fs::Permissions::from_mode(0o777)).unwrap();
+    fs::set_permissions(
+        source_dir.child("sub").path(),
+        fs::Permissions::from_mode(0o700),
+    )
+    .unwrap();
+
+    Command::new(cargo::cargo_bin!("cpx"))
+        .arg("--no-config")
+        .arg("-r")
+        .arg("-p")
+        .arg("mode")
+        .arg("--chmod=D755,F644,g-w")
+        .arg("--chmod=Fu+x")
+        .arg(source_dir.path())
+        .arg(dest_dir.path())
+        .assert()
+        .success();
+
+    let copied = dest_dir.path().join(source_dir.path().file_name().unwrap());
+    let mode = |path: &std::path::Path| fs::metadata(path).unwrap().permissions().mode() & 0o7777;
+    assert_eq!(mode(&copied.join("sub")), 0o755);
+    assert_eq!(mode(&copied.join("sub/run.sh")), 0o744);
+}
+
+#[test]
+fn test_no_preserve_rejects_unknown_attribute() {
+    let temp = assert_fs::TempDir::new().unwrap();
+    let file = temp.child("file.txt");
+    file.write_str("data").unwrap();
+
+    Command::new(cargo::cargo_bin!("cpx"))
+        .arg("--no-config")
+        .arg("--no-preserve=bogus")
+        .arg(file.path())
+        .arg(temp.path().join("out.txt"))
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "unable to parse no-preserve attribute",
+        ));
+}
+
+#[test]
+fn test_metadata_sidecar_restore() {
+    let temp = assert_fs::TempDir::new().unwrap();
+    let source_dir = temp.child("source");
+    source_dir.create_dir_all().unwrap();
+    let file = source_dir.child("data.bin");
+    file.write_str("payload").unwrap();
+    fs::set_permissions(file.path(), fs::Permissions::from_mode(0o640)).unwrap();
+    let mtime = filetime::FileTime::from_unix_time(1_600_000_000, 0);
+    filetime::set_file_mtime(file.path(), mtime).unwrap();
+    let dest_dir = temp.child("dest");
+
+    Command::new(cargo::cargo_bin!("cpx"))
+        .arg("--no-config")
+        .arg("-r")
+        .arg("--metadata-sidecar")
+        .arg(source_dir.path())
+        .arg(dest_dir.path())
+        .assert()
+        .success();
+
+    let copied = dest_dir.path().join("source/data.bin");
+    assert!(dest_dir.path().join(".cpx-metadata").exists());
+
+    // Simulate a trip through a filesystem that dropped the metadata
+    fs::set_permissions(&copied, fs::Permissions::from_mode(0o666)).unwrap();
+    filetime::set_file_mtime(&copied, filetime::FileTime::now()).unwrap();
+
+    Command::new(cargo::cargo_bin!("cpx"))
+        .arg("restore-metadata")
+        .arg(dest_dir.path())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Restored metadata of 2 entries"));
+
+    let restored = fs::metadata(&copied).unwrap();
+    assert_eq!(restored.permissions().mode() & 0o7777, 0o640);
+    assert_eq!(
+        filetime::FileTime::from_last_modification_time(&restored),
+        mtime
+    );
+}
+
+#[test]
+fn test_metadata_sidecar_records_symbolic_links() {
+    let temp = assert_fs::TempDir::new().unwrap();
+    let source_dir = temp.child("source");
+    source_dir.create_dir_all().unwrap();
+    source_dir.child("data.bin").write_str("payload").unwrap();
+    let dest_dir = temp.child("dest");
+
+    Command::new(cargo::cargo_bin!("cpx"))
+        .arg("--no-config")
+        .arg("-r")
+        .arg("-s")
+        .arg("absolute")
+        .arg("--metadata-sidecar")
+        .arg(source_dir.path())
+        .arg(dest_dir.path())
+        .assert()
+        .success();
+
+    let sidecar = fs::read_to_string(dest_dir.path().join(".cpx-metadata")).unwrap();
+    assert!(
+        sidecar.contains("\"path\":\"source/data.bin\""),
+        "{}",
+        sidecar
+    );
+}
+
+#[test]
+fn test_xattr_exclude_patterns() {
+    let temp = assert_fs::TempDir::new().unwrap();
+    let source = temp.child("source.txt");
+    source.write_str("data").unwrap();
+    if xattr::set(source.path(), "user.keep", b"1").is_err() {
+        return;
+    }
+    xattr::set(source.path(), "user.com.dropbox.attrs", b"2").unwrap();
+    let dest = temp.child("dest.txt");
+
+    Command::new(cargo::cargo_bin!("cpx"))
+        .arg("--no-config")
+        .arg("-p")
+        .arg("xattr")
+        .arg("--xattr-exclude=user.com.dropbox.*")
+        .arg(source.path())
+        .arg(dest.path())
+        .assert()
+        .success();
+
+    assert_eq!(xattr::get(dest.path(), "user.keep").unwrap().unwrap(), b"1");
+    assert!(
+        xattr::get(dest.path(), "user.com.dropbox.attrs")
+            .unwrap()
+            .is_none()
+    );
+}
+
+#[test]
+fn test_respect_gitignore() {
+    let temp = assert_fs::TempDir::new().unwrap();
+    let source_dir = temp.child("project");
+    source_dir
+        .child(".gitignore")
+        .write_str("*.log\n/target\n")
+        .unwrap();
+    source_dir
+        .child("main.rs")
+        .write_str("fn main() {}")
+        .unwrap();
+    source_dir.child("debug.log").write_str("log").unwrap();
+    source_dir.child("target/out").write_str("bin").unwrap();
+    source_dir
+        .child("docs/target/page.md")
+        .write_str("md")
+        .unwrap();
+    source_dir
+        .child("docs/.cpxignore")
+        .write_str("!keep.log\n")
+        .unwrap();
+    source_dir.child("docs/keep.log").write_str("kept").unwrap();
+    let dest_dir = temp.child("dest");
+
+    Command::new(cargo::cargo_bin!("cpx"))
+        .arg("--no-config")
+        .arg("-r")
+        .arg("--respect-gitignore")
+        .arg(source_dir.path())
+        .arg(dest_dir.path())
+        .assert()
+        .success();
+
+    let copied = dest_dir.path().join("project");
+    assert!(copied.join("main.rs").exists());
+    assert!(copied.join(".gitignore").exists());
+    assert!(!copied.join("debug.log").exists());
+    assert!(!copied.join("target").exists());
+    assert!(copied.join("docs/target/page.md").exists());
+    assert!(copied.join("docs/keep.log").exists());
+}
+
+#[test]
+fn test_include_then_exclude_everything_else() {
+    let temp = assert_fs::TempDir::new().unwrap();
+    let source_dir = temp.child("lake");
+    source_dir
+        .child("2024/01/part-0.parquet")
+        .write_str("p")
+        .unwrap();
+    source_dir
+        .child("2024/01/schema.json")
+        .write_str("{}")
+        .unwrap();
+    source_dir
+        .child("2024/01/notes.txt")
+        .write_str("n")
+        .unwrap();
+    source_dir.child("scratch/tmp.txt").write_str("t").unwrap();
+    let dest_dir = temp.child("dest");
+
+    Command::new(cargo::cargo_bin!("cpx"))
+        .arg("--no-config")
+        .arg("-r")
+        .arg("--include=*.parquet")
+        .arg("--include=*.json")
+        .arg("--exclude=*")
+        .arg(source_dir.path())
+        .arg(dest_dir.path())
+        .assert()
+        .success();
+
+    let copied = dest_dir.path().join("lake");
+    assert!(copied.join("2024/01/part-0.parquet").exists());
+    assert!(copied.join("2024/01/schema.json").exists());
+    assert!(!copied.join("2024/01/notes.txt").exists());
+    // Directories without anything included aren't created
+    assert!(!copied.join("scratch").exists());
+}
+
+#[test]
+fn test_interleaved_include_and_exclude_keep_command_line_order() {
+    let temp = assert_fs::TempDir::new().unwrap();
+    let source_dir = temp.child("app");
+    source_dir.child("package.json").write_str("{}").unwrap();
+    source_dir.child("README.md").write_str("r").unwrap();
+    source_dir
+        .child("conf/settings.json")
+        .write_str("{}")
+        .unwrap();
+    source_dir
+        .child("node_modules/dep/package.json")
+        .write_str("{}")
+        .unwrap();
+    let dest_dir = temp.child("dest");
+
+    // The exclude ahead of the include wins for node_modules, the one after it for the rest
+    Command::new(cargo::cargo_bin!("cpx"))
+        .arg("--no-config")
+        .arg("-r")
+        .arg("--exclude")
+        .arg("node_modules")
+        .arg("--include")
+        .arg("*.json")
+        .arg("--exclude")
+        .arg("*")
+        .arg(source_dir.path())
+        .arg(dest_dir.path())
+        .assert()
+        .success();
+
+    let copied = dest_dir.path().join("app");
+    assert!(copied.join("package.json").exists());
+    assert!(copied.join("conf/settings.json").exists());
+    assert!(!copied.join("README.md").exists());
+    assert!(!copied.join("node_modules").exists());
+}
+
+#[test]
+fn test_filter_rules_from_config_follow_command_line_flags() {
+    let temp = assert_fs::TempDir::new().unwrap();
+    temp.child("cpxconfig.toml")
+        .write_str(
+            r#"
+[filter]
+rules = ["+ *.json", "- *"]
+"#,
+        )
+        .unwrap();
+    let source_dir = temp.child("app");
+    source_dir.child("package.json").write_str("{}").unwrap();
+    source_dir.child("README.md").write_str("r").unwrap();
+    source_dir.child("keep.md").write_str("k").unwrap();
+    source_dir
+        .child("node_modules/dep/package.json")
+        .write_str("{}")
+        .unwrap();
+
+    // Command-line rules are checked before the config's
+    Command::new(cargo::cargo_bin!("cpx"))
+        .current_dir(temp.path())
+        .arg("-r")
+        .arg("--exclude")
+        .arg("node_modules")
+        .arg("--include")
+        .arg("keep.md")
+        .arg("app")
+        .arg("dest")
+        .assert()
+        .success();
+
+    let copied = temp.path().join("dest/app");
+    assert!(copied.join("package.json").exists());
+    assert!(copied.join("keep.md").exists());
+    assert!(!copied.join("README.md").exists());
+    assert!(!copied.join("node_modules").exists());
+}
+
+#[test]
+fn test_files_from0_stdin_keeps_layout() {
+    let temp = assert_fs::TempDir::new().unwrap();
+    let source_dir = temp.child("src");
+    source_dir.child("a/one.txt").write_str("1").unwrap();
+    source_dir.child("b/c/two.txt").write_str("2").unwrap();
+    source_dir.child("b/unlisted.txt").write_str("x").unwrap();
+    let dest_dir = temp.child("dst");
+    dest_dir.create_dir_all().unwrap();
+
+    // assert_cmd's Command, which can feed stdin
+    assert_cmd::Command::new(cargo::cargo_bin!("cpx"))
+        .arg("--no-config")
+        .arg("--files-from0")
+        .arg("-")
+        .arg(source_dir.path())
+        .arg(dest_dir.path())
+        .write_stdin("a/one.txt\0./b/c/two.txt\0")
+        .assert()
+        .success();
+
+    assert_eq!(
+        fs::read_to_string(dest_dir.path().join("a/one.txt")).unwrap(),
+        "1"
+    );
+    assert_eq!(
+        fs::read_to_string(dest_dir.path().join("b/c/two.txt")).unwrap(),
+        "2"
+    );
+    assert!(!dest_dir.path().join("b/unlisted.txt").exists());
+}
+
+#[test]
+fn test_files_from_creates_destination_and_filters_from_base() {
+    let temp = assert_fs::TempDir::new().unwrap();
+    let source_dir = temp.child("src");
+    source_dir.child("a/x.txt").write_str("x").unwrap();
+    source_dir.child("a/y.log").write_str("y").unwrap();
+    source_dir.child("b/z.txt").write_str("z").unwrap();
+    let dest_dir = temp.child("dst");
+
+    // Rooted patterns are relative to the base, not to each listed directory
+    assert_cmd::Command::new(cargo::cargo_bin!("cpx"))
+        .arg("--no-config")
+        .arg("-r")
+        .arg("--files-from0")
+        .arg("-")
+        .arg("-e")
+        .arg("/a/y.log")
+        .arg("-e")
+        .arg("/x.txt")
+        .arg(source_dir.path())
+        .arg(dest_dir.path())
+        .write_stdin("a\0b\0")
+        .assert()
+        .success();
+
+    assert!(dest_dir.path().join("a/x.txt").exists());
+    assert!(!dest_dir.path().join("a/y.log").exists());
+    assert!(dest_dir.path().join("b/z.txt").exists());
+}
+
+#[test]
+fn test_files_from_empty_list_copies_nothing() {
+    let temp = assert_fs::TempDir::new().unwrap();
+    let source_dir = temp.child("src");
+    source_dir.child("a.txt").write_str("a").unwrap();
+    let dest_dir = temp.child("dst");
+
+    assert_cmd::Command::new(cargo::cargo_bin!("cpx"))
+        .arg("--no-config")
+        .arg("--files-from")
+        .arg("-")
+        .arg(source_dir.path())
+        .arg(dest_dir.path())
+        .write_stdin("")
+        .assert()
+        .success();
+
+    assert!(!dest_dir.path().exists());
+}
+
+#[test]
+fn test_files_from_reports_missing_paths_and_copies_the_rest() {
+    let temp = assert_fs::TempDir::new().unwrap();
+    let source_dir = temp.child("src");
+    source_dir.child("a.txt").write_str("a").unwrap();
+    source_dir.child("c/d.txt").write_str("d").unwrap();
+    let dest_dir = temp.child("dst");
+
+    assert_cmd::Command::new(cargo::cargo_bin!("cpx"))
+        .arg("--no-config")
+        .arg("--files-from")
+        .arg("-")
+        .arg(source_dir.path())
+        .arg(dest_dir.path())
+        .write_stdin("a.txt\nmissing.txt\nc/d.txt\n")
+        .assert()
+        .failure()
+        .stderr(
+            predicate::str::contains("missing.txt")
+                .and(predicate::str::contains("1 listed path(s) do not exist")),
+        );
+
+    assert!(dest_dir.path().join("a.txt").exists());
+    assert!(dest_dir.path().join("c/d.txt").exists());
+
+    let output = assert_cmd::Command::new(cargo::cargo_bin!("cpx"))
+        .arg("--no-config")
+        .arg("--progress=json")
+        .arg("--files-from")
+        .arg("-")
+        .arg(source_dir.path())
+        .arg(temp.child("json").path())
+        .write_stdin("a.txt\nmissing.txt\n")
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let warned = String::from_utf8(output.stderr)
+        .unwrap()
+        .lines()
+        .filter_map(|line| serde_json::from_str::<serde_json::Value>(line).ok())
+        .any(|e| e["event"] == "warning" && e["source"].as_str().unwrap().ends_with("missing.txt"));
+    assert!(warned);
+}
+
+#[test]
+fn test_exclude_from_file() {
+    let temp = assert_fs::TempDir::new().unwrap();
+    let source_dir = temp.child("project");
+    source_dir
+        .child("main.rs")
+        .write_str("fn main() {}")
+        .unwrap();
+    source_dir
+        .child("target/debug/app")
+        .write_str("bin")
+        .unwrap();
+    source_dir.child("notes, draft.txt").write_str("n").unwrap();
+    let excludes = temp.child("excludes.txt");
+    excludes
+        .write_str("# build output\ntarget/\nnotes, draft.txt\n")
+        .unwrap();
+    let dest_dir = temp.child("dest");
+
+    Command::new(cargo::cargo_bin!("cpx"))
+        .arg("--no-config")
+        .arg("-r")
+        .arg("--exclude-from")
+        .arg(excludes.path())
+        .arg(source_dir.path())
+        .arg(dest_dir.path())
+        .assert()
+        .success();
+
+    let copied = dest_dir.path().join("project");
+    assert!(copied.join("main.rs").exists());
+    assert!(!copied.join("target").exists());
+    assert!(!copied.join("notes, draft.txt").exists());
+}
+
+#[test]
+fn test_filter_expression() {
+    let temp = assert_fs::TempDir::new().unwrap();
+    let source_dir = temp.child("lake");
+    source_dir
+        .child("big.parquet")
+        .write_binary(&vec![0u8; 4096])
+        .unwrap();
+    source_dir.child("small.parquet").write_str("x").unwrap();
+    source_dir
+        .child("old/big.csv")
+        .write_binary(&vec![0u8; 4096])
+        .unwrap();
+    let old = filetime::FileTime::from_unix_time(1_000_000_000, 0);
+    filetime::set_file_mtime(source_dir.child("old/big.csv").path(), old).unwrap();
+    let dest_dir = temp.child("dest");
+
+    Command::new(cargo::cargo_bin!("cpx"))
+        .arg("--no-config")
+        .arg("-r")
+        .arg("--filter")
+        .arg("size > 1K and mtime < 7d")
+        .arg("--filter")
+        .arg("type == file")
+        .arg(source_dir.path())
+        .arg(dest_dir.path())
+        .assert()
+        .success();
+
+    let copied = dest_dir.path().join("lake");
+    assert!(copied.join("big.parquet").exists());
+    assert!(!copied.join("small.parquet").exists());
+    assert!(copied.join("old").is_dir());
+    assert!(!copied.join("old/big.csv").exists());
+
+    Command::new(cargo::cargo_bin!("cpx"))
+        .arg("--no-config")
+        .arg("-r")
+        .arg("--filter")
+        .arg("size >")
+        .arg(source_dir.path())
+        .arg(dest_dir.path())
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Invalid filter expression"));
+}
+
+#[test]
+fn test_exclude_presets() {
+    let temp = assert_fs::TempDir::new().unwrap();
+    let source_dir = temp.child("app");
+    source_dir
+        .child("src/main.rs")
+        .write_str("fn main() {}")
+        .unwrap();
+    source_dir
+        .child("target/debug/app")
+        .write_str("bin")
+        .unwrap();
+    source_dir
+        .child("web/node_modules/left-pad/index.js")
+        .write_str("js")
+        .unwrap();
+    source_dir.child("web/.DS_Store").write_str("junk").unwrap();
+    let dest_dir = temp.child("dest");
+
+    Command::new(cargo::cargo_bin!("cpx"))
+        .arg("--no-config")
+        .arg("-r")
+        .arg("--exclude-preset")
+        .arg("rust,node,macos-junk")
+        .arg(source_dir.path())
+        .arg(dest_dir.path())
+        .assert()
+        .success();
+
+    let copied = dest_dir.path().join("app");
+    assert!(copied.join("src/main.rs").exists());
+    assert!(!copied.join("target").exists());
+    assert!(!copied.join("web/node_modules").exists());
+    assert!(!copied.join("web/.DS_Store").exists());
+
+    Command::new(cargo::cargo_bin!("cpx"))
+        .arg("--no-config")
+        .arg("-r")
+        .arg("--exclude-preset")
+        .arg("cobol")
+        .arg(source_dir.path())
+        .arg(dest_dir.path())
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("unknown exclude preset 'cobol'"));
+}
+
+#[test]
+fn test_config_presets() {
+    Command::new(cargo::cargo_bin!("cpx"))
+        .arg("config")
+        .arg("presets")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("node_modules/"))
+        .stdout(predicate::str::contains("macos-junk"));
+}
+
+#[test]
+fn test_exclude_caches_and_markers() {
+    let temp = assert_fs::TempDir::new().unwrap();
+    let source_dir = temp.child("home");
+    source_dir.child("notes.txt").write_str("keep").unwrap();
+    source_dir
+        .child(".cache/thumbs/CACHEDIR.TAG")
+        .write_str("Signature: 8a477f597d28d172789f06886806bc55\n")
+        .unwrap();
+    source_dir
+        .child(".cache/thumbs/a.png")
+        .write_str("png")
+        .unwrap();
+    source_dir
+        .child("not-a-cache/CACHEDIR.TAG")
+        .write_str("hello\n")
+        .unwrap();
+    source_dir.child("scratch/.nobackup").write_str("").unwrap();
+    source_dir
+        .child("scratch/big.iso")
+        .write_str("iso")
+        .unwrap();
+    let dest_dir = temp.child("dest");
+
+    Command::new(cargo::cargo_bin!("cpx"))
+        .arg("--no-config")
+        .arg("-r")
+        .arg("--exclude-caches")
+        .arg("--exclude-if-present")
+        .arg(".nobackup")
+        .arg(source_dir.path())
+        .arg(dest_dir.path())
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(
+            "Skipped 2 directories marked by an exclusion file",
+        ));
+
+    let copied = dest_dir.path().join("home");
+    assert!(copied.join("notes.txt").exists());
+    assert!(copied.join(".cache").is_dir());
+    assert!(!copied.join(".cache/thumbs").exists());
+    assert!(copied.join("not-a-cache/CACHEDIR.TAG").exists());
+    assert!(!copied.join("scratch").exists());
+}
+
+#[test]
+fn test_exclude_existing_absolute_path_without_prefix() {
+    let temp = assert_fs::TempDir::new().unwrap();
+    let source_dir = temp.child("proj");
+    source_dir.child("secrets/key").write_str("k").unwrap();
+    source_dir.child("main.rs").write_str("m").unwrap();
+    let secrets = source_dir.child("secrets");
+    temp.child("cpxconfig.toml")
+        .write_str(&format!(
+            "[filter]\npatterns = [\"{}\"]\n",
+            secrets.path().display()
+        ))
+        .unwrap();
+
+    // An absolute path on the command line, as older versions accepted it
+    Command::new(cargo::cargo_bin!("cpx"))
+        .arg("--no-config")
+        .arg("-r")
+        .arg("-e")
+        .arg(secrets.path())
+        .arg(source_dir.path())
+        .arg(temp.path().join("from-flag"))
+        .assert()
+        .success();
+    assert!(temp.path().join("from-flag/proj/main.rs").exists());
+    assert!(!temp.path().join("from-flag/proj/secrets").exists());
+
+    // ...and in the config's patterns
+    Command::new(cargo::cargo_bin!("cpx"))
+        .current_dir(temp.path())
+        .arg("-r")
+        .arg("proj")
+        .arg("from-config")
+        .assert()
+        .success();
+    assert!(temp.path().join("from-config/proj/main.rs").exists());
+    assert!(!temp.path().join("from-config/proj/secrets").exists());
+}
+
+#[test]
+fn test_check_ignore_and_explain_excludes() {
+    let temp = assert_fs::TempDir::new().unwrap();
+    let source_dir = temp.child("project");
+    source_dir
+        .child("src/main.rs")
+        .write_str("fn main() {}")
+        .unwrap();
+    source_dir.child("build/out.o").write_str("obj").unwrap();
+    source_dir.child("debug.log").write_str("log").unwrap();
+    source_dir
+        .child(".gitignore")
+        .write_str("# logs\n*.log\n")
+        .unwrap();
+
+    Command::new(cargo::cargo_bin!("cpx"))
+        .arg("check-ignore")
+        .arg("--no-config")
+        .arg("--respect-gitignore")
+        .arg("-e")
+        .arg("build")
+        .arg(source_dir.path())
+        .arg(source_dir.child("build/out.o").path())
+        .arg(source_dir.child("debug.log").path())
+        .arg(source_dir.child("src/main.rs").path())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "out.o: excluded by basename 'build' from --exclude",
+        ))
+        .stdout(predicate::str::contains(
+            "debug.log: excluded by glob '*.log' from",
+        ))
+        .stdout(predicate::str::contains(".gitignore:2"))
+        .stdout(predicate::str::contains("main.rs: not excluded"));
+
+    Command::new(cargo::cargo_bin!("cpx"))
+        .arg("check-ignore")
+        .arg("--no-config")
+        .arg(source_dir.path())
+        .arg(source_dir.child("src/main.rs").path())
+        .assert()
+        .code(1);
+
+    // `..` is resolved before checking that the path is inside the source
+    Command::new(cargo::cargo_bin!("cpx"))
+        .arg("check-ignore")
+        .arg("--no-config")
+        .arg(source_dir.path())
+        .arg(source_dir.path().join("../elsewhere/x"))
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("is not inside"));
+    Command::new(cargo::cargo_bin!("cpx"))
+        .arg("check-ignore")
+        .arg("--no-config")
+        .arg("-e")
+        .arg("build")
+        .arg(source_dir.path())
+        .arg(source_dir.path().join("src/../build/out.o"))
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("excluded by basename 'build'"));
+
+    let dest_dir = temp.child("dest");
+    Command::new(cargo::cargo_bin!("cpx"))
+        .arg("--no-config")
+        .arg("-r")
+        .arg("--explain-excludes")
+        .arg("-e")
+        .arg("build")
+        .arg(source_dir.path())
+        .arg(dest_dir.path())
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(
+            "build: excluded by basename 'build' from --exclude",
+        ))
+        .stderr(predicate::str::contains("out.o").not());
+    assert!(dest_dir.path().join("project/src/main.rs").exists());
+}